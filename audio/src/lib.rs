@@ -0,0 +1,135 @@
+// Audio output via the system's default sound device, for the desktop frontends
+
+use songbird_core::cpu::Cpu;
+
+use cpal::{Device, SampleFormat, Stream, StreamConfig};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+// Enough room for a couple of frames worth of samples
+const DRAIN_BUF_SIZE: usize = 4096;
+// Most audio allowed to build up before old samples are dropped, in seconds
+const MAX_LATENCY_SECS: u32 = 1;
+
+pub struct Audio {
+    _stream: Stream,
+    queue: Arc<Mutex<VecDeque<i16>>>,
+    sample_rate: u32,
+    drain_buf: Vec<i16>,
+}
+
+impl Audio {
+    /// ```
+    /// New
+    ///
+    /// Opens the default output device
+    ///
+    /// Output:
+    ///     Audio object, if a device is available (Option<Audio>)
+    /// ```
+    pub fn new() -> Option<Audio> {
+        let host = cpal::default_host();
+        let device = host.default_output_device()?;
+        let supported = device.default_output_config().ok()?;
+        let format = supported.sample_format();
+        let config: StreamConfig = supported.into();
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+
+        let stream = match format {
+            SampleFormat::I16 => { build_stream::<i16>(&device, &config, queue.clone())? },
+            SampleFormat::U16 => { build_stream::<u16>(&device, &config, queue.clone())? },
+            SampleFormat::F32 => { build_stream::<f32>(&device, &config, queue.clone())? },
+        };
+        stream.play().ok()?;
+
+        Some(Audio {
+            _stream: stream,
+            queue,
+            sample_rate: config.sample_rate.0,
+            drain_buf: vec![0; DRAIN_BUF_SIZE],
+        })
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// ```
+    /// Get queued
+    ///
+    /// Number of stereo sample frames waiting to be played
+    ///
+    /// Output:
+    ///     Queue length (usize)
+    /// ```
+    pub fn get_queued(&self) -> usize {
+        self.queue.lock().unwrap().len() / 2
+    }
+
+    /// ```
+    /// Queue samples
+    ///
+    /// Moves all generated audio out of the emulator and into the playback queue
+    ///
+    /// Input:
+    ///     Game Boy CPU (&mut Cpu)
+    /// ```
+    pub fn queue_samples(&mut self, gb: &mut Cpu) {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            let len = gb.drain_audio(&mut self.drain_buf);
+            if len == 0 {
+                break;
+            }
+            queue.extend(&self.drain_buf[..len]);
+        }
+
+        let max_len = (2 * self.sample_rate * MAX_LATENCY_SECS) as usize;
+        if queue.len() > max_len {
+            let excess = queue.len() - max_len;
+            queue.drain(..excess);
+        }
+    }
+}
+
+/// ```
+/// Build stream
+///
+/// Creates an output stream which plays back interleaved stereo samples from the queue
+///
+/// Inputs:
+///     Output device (&Device)
+///     Stream configuration (&StreamConfig)
+///     Shared sample queue (Arc<Mutex<VecDeque<i16>>>)
+///
+/// Output:
+///     Audio stream, if it could be built (Option<Stream>)
+/// ```
+fn build_stream<T: cpal::Sample>(device: &Device, config: &StreamConfig, queue: Arc<Mutex<VecDeque<i16>>>) -> Option<Stream> {
+    let channels = config.channels as usize;
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            let mut queue = queue.lock().unwrap();
+            for frame in data.chunks_mut(channels) {
+                // Play silence if the emulator falls behind
+                let left = queue.pop_front().unwrap_or(0);
+                let right = queue.pop_front().unwrap_or(0);
+
+                for (i, sample) in frame.iter_mut().enumerate() {
+                    let val = match (channels, i) {
+                        (1, _) => { ((left as i32 + right as i32) / 2) as i16 },
+                        (_, 0) => { left },
+                        (_, 1) => { right },
+                        _ => { 0 }
+                    };
+                    *sample = T::from(&val);
+                }
+            }
+        },
+        |err| eprintln!("Audio stream error: {}", err)
+    ).ok()
+}
@@ -0,0 +1,238 @@
+use std::f64::consts::PI;
+
+/*
+ * Band-limited sample buffer
+ *
+ * The APU's output changes at up to ~1 MiHz, far above any host sample rate,
+ * so simply picking every Nth value would alias badly. Instead, each change in
+ * amplitude is recorded as a band-limited step: a windowed sinc impulse is
+ * added at the (fractional) output position where the change occurred, and the
+ * buffer is integrated as samples are read out. A gentle high-pass filter is
+ * applied at the end, removing the DC offset just like the real hardware's
+ * output capacitor.
+ */
+
+// Number of output samples each step is spread across
+const KERNEL_WIDTH: usize = 16;
+// Number of fractional sample positions with precomputed kernels
+const KERNEL_PHASES: usize = 64;
+// Amount of audio kept when the oldest samples are discarded, in seconds
+const MAX_BUFFER_SECS: usize = 1;
+// Multiple of that allowed to build up first, so unread samples are dropped in bulk
+const HIGH_WATER_FACTOR: usize = 2;
+// Cutoff frequency of the DC removal filter, in Hz
+const HIGH_PASS_FREQ: f64 = 20.0;
+
+#[derive(Clone)]
+pub struct BlipBuf {
+    kernels: Vec<[f32; KERNEL_WIDTH]>,
+    buffer: Vec<f32>,
+    factor: f64,
+    offset: f64,
+    integrator: f32,
+    capacitor: f32,
+    high_pass: f32,
+    max_samples: usize,
+}
+
+impl BlipBuf {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> BlipBuf {
+        let mut blip = BlipBuf {
+            kernels: build_kernels(),
+            buffer: Vec::new(),
+            factor: 0.0,
+            offset: 0.0,
+            integrator: 0.0,
+            capacitor: 0.0,
+            high_pass: 0.0,
+            max_samples: 0,
+        };
+        blip.set_rates(clock_rate, sample_rate);
+        blip
+    }
+
+    /// ```
+    /// Set rates
+    ///
+    /// Changes the input clock rate and output sample rate, clearing any buffered audio
+    ///
+    /// Inputs:
+    ///     Rate deltas are clocked at, in Hz (u32)
+    ///     Rate of output samples, in Hz (u32)
+    /// ```
+    pub fn set_rates(&mut self, clock_rate: u32, sample_rate: u32) {
+        self.factor = sample_rate as f64 / clock_rate as f64;
+        self.high_pass = (1.0 - (-2.0 * PI * HIGH_PASS_FREQ / sample_rate as f64).exp()) as f32;
+        self.max_samples = sample_rate as usize * MAX_BUFFER_SECS;
+        self.clear();
+    }
+
+    /// ```
+    /// Clear
+    ///
+    /// Discards all buffered audio
+    /// ```
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.buffer.resize(KERNEL_WIDTH, 0.0);
+        self.offset = 0.0;
+        self.integrator = 0.0;
+        self.capacitor = 0.0;
+    }
+
+    /// ```
+    /// Add delta
+    ///
+    /// Records a change in amplitude at the given time in the current frame
+    ///
+    /// Inputs:
+    ///     Clocks since the start of the frame (u32)
+    ///     Change in amplitude (f32)
+    /// ```
+    pub fn add_delta(&mut self, time: u32, delta: f32) {
+        let pos = self.offset + time as f64 * self.factor;
+        let index = pos as usize;
+        let phase = ((pos - index as f64) * KERNEL_PHASES as f64) as usize;
+
+        let needed = index + KERNEL_WIDTH;
+        if self.buffer.len() < needed {
+            self.buffer.resize(needed, 0.0);
+        }
+
+        let kernel = &self.kernels[phase];
+        for (sample, k) in self.buffer[index..needed].iter_mut().zip(kernel.iter()) {
+            *sample += delta * k;
+        }
+    }
+
+    /// ```
+    /// End frame
+    ///
+    /// Ends the current frame, making its samples available to be read
+    ///
+    /// Input:
+    ///     Length of the frame, in clocks (u32)
+    /// ```
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.offset += clocks as f64 * self.factor;
+        let needed = self.samples_avail() + KERNEL_WIDTH;
+        if self.buffer.len() < needed {
+            self.buffer.resize(needed, 0.0);
+        }
+
+        // Nobody is reading samples, so drop the oldest ones. This is only done once
+        // well past the limit, as shifting the buffer down costs as much as its length.
+        let avail = self.samples_avail();
+        if avail > self.max_samples * HIGH_WATER_FACTOR {
+            self.discard(avail - self.max_samples);
+        }
+    }
+
+    /// ```
+    /// Samples available
+    ///
+    /// Number of finished samples ready to be read
+    ///
+    /// Output:
+    ///     Sample count (usize)
+    /// ```
+    pub fn samples_avail(&self) -> usize {
+        self.offset as usize
+    }
+
+    /// ```
+    /// Read samples
+    ///
+    /// Reads finished samples into an interleaved buffer, removing them from this one
+    ///
+    /// Inputs:
+    ///     Output buffer (&mut [i16])
+    ///     Distance between samples in output, 2 for stereo (usize)
+    ///     Number of samples to read (usize)
+    /// ```
+    pub fn read_samples(&mut self, out: &mut [i16], stride: usize, count: usize) {
+        let count = count.min(self.samples_avail());
+
+        for i in 0..count {
+            self.integrator += self.buffer[i];
+            let sample = self.integrator - self.capacitor;
+            self.capacitor += sample * self.high_pass;
+            out[i * stride] = sample.round().max(i16::MIN as f32).min(i16::MAX as f32) as i16;
+        }
+
+        self.remove(count);
+    }
+}
+
+// Private functions
+impl BlipBuf {
+    /// ```
+    /// Discard
+    ///
+    /// Throws away the oldest finished samples, keeping the integrator in sync
+    ///
+    /// Input:
+    ///     Number of samples to drop (usize)
+    /// ```
+    fn discard(&mut self, count: usize) {
+        for i in 0..count {
+            self.integrator += self.buffer[i];
+        }
+        self.capacitor = self.integrator;
+        self.remove(count);
+    }
+
+    /// ```
+    /// Remove
+    ///
+    /// Shifts the buffer forward once samples have been consumed
+    ///
+    /// Input:
+    ///     Number of samples consumed (usize)
+    /// ```
+    fn remove(&mut self, count: usize) {
+        self.buffer.drain(0..count);
+        if self.buffer.len() < KERNEL_WIDTH {
+            self.buffer.resize(KERNEL_WIDTH, 0.0);
+        }
+        self.offset -= count as f64;
+    }
+}
+
+/// ```
+/// Build kernels
+///
+/// Precomputes a Blackman-windowed sinc impulse for each fractional phase.
+/// Each kernel is normalized so a step always ends at exactly its full height.
+///
+/// Output:
+///     Kernel for each phase (Vec<[f32; KERNEL_WIDTH]>)
+/// ```
+fn build_kernels() -> Vec<[f32; KERNEL_WIDTH]> {
+    let half = (KERNEL_WIDTH / 2) as f64;
+    let mut kernels = Vec::with_capacity(KERNEL_PHASES);
+
+    for phase in 0..KERNEL_PHASES {
+        let frac = phase as f64 / KERNEL_PHASES as f64;
+        let mut kernel = [0.0; KERNEL_WIDTH];
+        let mut sum = 0.0;
+
+        for (i, k) in kernel.iter_mut().enumerate() {
+            // Distance from the impulse's center, in samples
+            let x = i as f64 - half - frac + 1.0;
+            let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+            let w = (x + half) / KERNEL_WIDTH as f64;
+            let window = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+            let val = sinc * window;
+            *k = val as f32;
+            sum += val;
+        }
+
+        for k in kernel.iter_mut() {
+            *k /= sum as f32;
+        }
+        kernels.push(kernel);
+    }
+
+    kernels
+}
@@ -0,0 +1,165 @@
+/*
+ * Volume envelope
+ *
+ * Used by both pulse channels and the noise channel, set via NRx2
+ *
+ * Bit | Function
+ * ----+---------
+ * 7-4 | Initial volume
+ * 3   | Direction (1 = increase)
+ * 2-0 | Period (0 = disabled)
+ *
+ * The envelope is clocked at 64 Hz by the frame sequencer
+ */
+
+use crate::utils::ModifyBits;
+use crate::state::{StateError, StateReader, StateWriter};
+
+const DIRECTION_BIT: u8 = 3;
+const MAX_VOLUME: u8 = 0x0F;
+
+#[derive(Copy, Clone)]
+pub struct Envelope {
+    initial: u8,
+    increase: bool,
+    period: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub fn new() -> Envelope {
+        Envelope {
+            initial: 0,
+            increase: false,
+            period: 0,
+            volume: 0,
+            timer: 0,
+        }
+    }
+
+    /// ```
+    /// Write register
+    ///
+    /// Updates the envelope settings from a write to NRx2
+    ///
+    /// Input:
+    ///     Value written (u8)
+    /// ```
+    pub fn write_reg(&mut self, val: u8) {
+        self.initial = val >> 4;
+        self.increase = val.get_bit(DIRECTION_BIT);
+        self.period = val & 0b111;
+    }
+
+    /// ```
+    /// Read register
+    ///
+    /// Returns the value of NRx2
+    ///
+    /// Output:
+    ///     Register value (u8)
+    /// ```
+    pub fn read_reg(&self) -> u8 {
+        let dir = if self.increase { 1 << DIRECTION_BIT } else { 0 };
+        (self.initial << 4) | dir | self.period
+    }
+
+    /// ```
+    /// Is DAC enabled
+    ///
+    /// The channel's DAC is powered whenever the upper five bits of NRx2 are non-zero
+    ///
+    /// Output:
+    ///     Whether the DAC is on (bool)
+    /// ```
+    pub fn is_dac_enabled(&self) -> bool {
+        self.initial != 0 || self.increase
+    }
+
+    /// ```
+    /// Trigger
+    ///
+    /// Restarts the envelope from its initial volume
+    /// ```
+    pub fn trigger(&mut self) {
+        self.volume = self.initial;
+        self.timer = reload(self.period);
+    }
+
+    /// ```
+    /// Clock
+    ///
+    /// Steps the volume up or down once the envelope period has elapsed
+    /// ```
+    pub fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer == 0 {
+            self.timer = reload(self.period);
+            if self.increase && self.volume < MAX_VOLUME {
+                self.volume += 1;
+            } else if !self.increase && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+
+    pub fn get_volume(&self) -> u8 {
+        self.volume
+    }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes the volume envelope to a save state
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.initial);
+        state.write_bool(self.increase);
+        state.write_u8(self.period);
+        state.write_u8(self.volume);
+        state.write_u8(self.timer);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the volume envelope from a save state
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.initial = state.read_u8()?;
+        self.increase = state.read_bool()?;
+        self.period = state.read_u8()?;
+        self.volume = state.read_u8()?;
+        self.timer = state.read_u8()?;
+        Ok(())
+    }
+}
+
+/// ```
+/// Reload
+///
+/// A period of 0 is treated as 8 when reloading the timer
+///
+/// Input:
+///     Period from register (u8)
+///
+/// Output:
+///     Timer value (u8)
+/// ```
+fn reload(period: u8) -> u8 {
+    if period == 0 { 8 } else { period }
+}
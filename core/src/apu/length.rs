@@ -0,0 +1,142 @@
+use crate::state::{StateError, StateReader, StateWriter};
+
+/*
+ * Length counter
+ *
+ * Every channel has a length counter, which disables the channel once it
+ * reaches zero. It is clocked at 256 Hz by the frame sequencer, but only
+ * counts down if the length enable bit in NRx4 is set.
+ *
+ * Pulse and noise channels count down from 64, the wave channel from 256.
+ */
+
+#[derive(Copy, Clone)]
+pub struct Length {
+    counter: u16,
+    enabled: bool,
+    max: u16,
+}
+
+impl Length {
+    pub fn new(max: u16) -> Length {
+        Length {
+            counter: 0,
+            enabled: false,
+            max,
+        }
+    }
+
+    /// ```
+    /// Load
+    ///
+    /// Loads a new length value from the NRx1 register
+    ///
+    /// Input:
+    ///     Length data written by the game (u16)
+    /// ```
+    pub fn load(&mut self, val: u16) {
+        self.counter = self.max - val;
+    }
+
+    /// ```
+    /// Clock
+    ///
+    /// Clocks the length counter, called from the frame sequencer
+    ///
+    /// Output:
+    ///     Whether the counter has expired and the channel should be disabled (bool)
+    /// ```
+    pub fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter == 0;
+        }
+
+        false
+    }
+
+    /// ```
+    /// Set enabled
+    ///
+    /// Handles a write to the length enable bit of NRx4
+    ///
+    /// Inputs:
+    ///     Whether length counting is now enabled (bool)
+    ///     Whether the next frame sequencer step will not clock length (bool)
+    ///
+    /// Output:
+    ///     Whether the extra clock expired the counter (bool)
+    /// ```
+    pub fn set_enabled(&mut self, enable: bool, len_quirk: bool) -> bool {
+        let was_enabled = self.enabled;
+        self.enabled = enable;
+
+        // Enabling the counter in the first half of a length period clocks it an extra time
+        if len_quirk && !was_enabled && enable && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter == 0;
+        }
+
+        false
+    }
+
+    /// ```
+    /// Trigger
+    ///
+    /// Reloads the counter if it has expired when the channel is triggered
+    ///
+    /// Input:
+    ///     Whether the next frame sequencer step will not clock length (bool)
+    /// ```
+    pub fn trigger(&mut self, len_quirk: bool) {
+        if self.counter == 0 {
+            self.counter = self.max;
+            if self.enabled && len_quirk {
+                self.counter -= 1;
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// ```
+    /// Disable
+    ///
+    /// Clears the length enable bit, leaving the counter untouched
+    /// ```
+    pub fn disable(&mut self) {
+        self.enabled = false;
+    }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes the length counter to a save state
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.counter);
+        state.write_bool(self.enabled);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the length counter from a save state
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.counter = state.read_u16()?;
+        self.enabled = state.read_bool()?;
+        Ok(())
+    }
+}
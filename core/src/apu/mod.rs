@@ -0,0 +1,530 @@
+mod blip;
+mod envelope;
+mod length;
+mod noise;
+mod pulse;
+mod wave;
+
+use blip::BlipBuf;
+use noise::Noise;
+use pulse::Pulse;
+use wave::{Wave, WAVE_RAM_SIZE};
+use crate::utils::ModifyBits;
+use crate::state::{StateError, StateReader, StateWriter};
+
+/*
+ * Audio Processing Unit
+ *
+ * +----------------------+ $FF10
+ * | Channel 1 - Pulse    |
+ * | with frequency sweep |
+ * +----------------------+ $FF15
+ * | Channel 2 - Pulse    |
+ * +----------------------+ $FF1A
+ * | Channel 3 - Wave     |
+ * +----------------------+ $FF1F
+ * | Channel 4 - Noise    |
+ * +----------------------+ $FF24
+ * | Control and status   |
+ * +----------------------+ $FF27
+ * |        Unused        |
+ * +----------------------+ $FF30
+ * |       Wave RAM       |
+ * +----------------------+ $FF3F
+ *
+ * The frame sequencer runs at 512 Hz, and clocks the other units as follows:
+ *
+ * Step | Length | Sweep | Envelope
+ * -----+--------+-------+---------
+ * 0    | Clock  |       |
+ * 1    |        |       |
+ * 2    | Clock  | Clock |
+ * 3    |        |       |
+ * 4    | Clock  |       |
+ * 5    |        |       |
+ * 6    | Clock  | Clock |
+ * 7    |        |       | Clock
+ *
+ * Each channel's DAC converts its 0-15 digital output into an analog value,
+ * which is then routed to the left and right terminals by NR51, and scaled
+ * by the master volume in NR50.
+ *
+ * NR50 | Bits | Function
+ * -----+------+-----------------
+ *      | 6-4  | Left volume
+ *      | 2-0  | Right volume
+ *
+ * NR51 | Bits | Function
+ * -----+------+-----------------
+ *      | 7-4  | Channel 4-1 left
+ *      | 3-0  | Channel 4-1 right
+ */
+
+// =============
+// = Constants =
+// =============
+pub const SOUND_START: u16  = 0xFF10;
+pub const SOUND_STOP: u16   = 0xFF3F;
+pub const NUM_CHANNELS: usize = 4;
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+const CH1_START: u16        = 0xFF10;
+const CH1_STOP: u16         = 0xFF14;
+const CH2_START: u16        = 0xFF15;
+const CH2_STOP: u16         = 0xFF19;
+const CH3_START: u16        = 0xFF1A;
+const CH3_STOP: u16         = 0xFF1E;
+const CH4_START: u16        = 0xFF1F;
+const CH4_STOP: u16         = 0xFF23;
+const NR50: u16             = 0xFF24;
+const NR51: u16             = 0xFF25;
+const NR52: u16             = 0xFF26;
+const WAVE_RAM_START: u16   = 0xFF30;
+const WAVE_RAM_STOP: u16    = WAVE_RAM_START + (WAVE_RAM_SIZE as u16) - 1;
+
+// NRx1 is the only register that can be written while powered off
+const LENGTH_REG: u16 = 1;
+
+const POWER_BIT: u8 = 7;
+const NR52_MASK: u8 = 0x70;
+
+// Frame sequencer runs at 512 Hz, or every 2048 M-cycles
+const FRAME_SEQ_PERIOD: u16 = 2048;
+const FRAME_SEQ_STEPS: u8 = 8;
+const T_CYCLES_PER_M: i32 = 4;
+
+// The mixer is sampled once per M-cycle
+const CLOCK_RATE: u32 = 1_048_576;
+// Scales the mixed output (at most 4 channels * 15 * 8 volume) to fill an i16
+const OUTPUT_GAIN: i32 = 64;
+
+#[derive(Clone)]
+pub struct APU {
+    ch1: Pulse,
+    ch2: Pulse,
+    ch3: Wave,
+    ch4: Noise,
+    nr50: u8,
+    nr51: u8,
+    power: bool,
+    frame_step: u8,
+    frame_cycles: u16,
+    left_buf: BlipBuf,
+    right_buf: BlipBuf,
+    last_left: i32,
+    last_right: i32,
+    output_enabled: bool,
+}
+
+impl Default for APU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl APU {
+    // ==================
+    // = Public methods =
+    // ==================
+    pub fn new() -> APU {
+        APU {
+            ch1: Pulse::new(true),
+            ch2: Pulse::new(false),
+            ch3: Wave::new(),
+            ch4: Noise::new(),
+            nr50: 0,
+            nr51: 0,
+            power: true,
+            frame_step: 0,
+            frame_cycles: 0,
+            left_buf: BlipBuf::new(CLOCK_RATE, DEFAULT_SAMPLE_RATE),
+            right_buf: BlipBuf::new(CLOCK_RATE, DEFAULT_SAMPLE_RATE),
+            last_left: 0,
+            last_right: 0,
+            output_enabled: true,
+        }
+    }
+
+    /// ```
+    /// Tick
+    ///
+    /// Advances the sound channels and frame sequencer, and generates audio samples
+    ///
+    /// Input:
+    ///     Number of M-cycles elapsed (u8)
+    /// ```
+    pub fn tick(&mut self, cycles: u8) {
+        for time in 0..(cycles as u32) {
+            if self.power {
+                self.ch1.tick(T_CYCLES_PER_M);
+                self.ch2.tick(T_CYCLES_PER_M);
+                self.ch3.tick(T_CYCLES_PER_M);
+                self.ch4.tick(T_CYCLES_PER_M);
+
+                self.frame_cycles += 1;
+                if self.frame_cycles >= FRAME_SEQ_PERIOD {
+                    self.frame_cycles = 0;
+                    self.step_frame_sequencer();
+                }
+            }
+
+            if self.output_enabled {
+                self.mix(time);
+            }
+        }
+
+        if self.output_enabled {
+            self.left_buf.end_frame(cycles as u32);
+            self.right_buf.end_frame(cycles as u32);
+        }
+    }
+
+    /// ```
+    /// Set output enabled
+    ///
+    /// Turns generation of audio samples on or off, for hosts that never play them.
+    /// The channels keep running either way. Any pending samples are discarded.
+    ///
+    /// Input:
+    ///     Whether to generate samples (bool)
+    /// ```
+    pub fn set_output_enabled(&mut self, enabled: bool) {
+        self.output_enabled = enabled;
+        self.left_buf.clear();
+        self.right_buf.clear();
+        self.last_left = 0;
+        self.last_right = 0;
+    }
+
+    /// ```
+    /// Set sample rate
+    ///
+    /// Sets the rate audio samples are generated at, discarding any pending samples
+    ///
+    /// Input:
+    ///     Output sample rate, in Hz (u32)
+    /// ```
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.left_buf.set_rates(CLOCK_RATE, rate);
+        self.right_buf.set_rates(CLOCK_RATE, rate);
+        self.last_left = 0;
+        self.last_right = 0;
+    }
+
+    /// ```
+    /// Drain samples
+    ///
+    /// Moves generated audio into the given buffer, as interleaved left/right pairs
+    ///
+    /// Input:
+    ///     Output buffer (&mut [i16])
+    ///
+    /// Output:
+    ///     Number of i16 values written, two per stereo frame (usize)
+    /// ```
+    pub fn drain_samples(&mut self, out: &mut [i16]) -> usize {
+        let count = (out.len() / 2).min(self.samples_avail());
+        self.left_buf.read_samples(out, 2, count);
+        self.right_buf.read_samples(&mut out[1..], 2, count);
+        count * 2
+    }
+
+    /// ```
+    /// Samples available
+    ///
+    /// Number of stereo frames ready to be drained
+    ///
+    /// Output:
+    ///     Frame count (usize)
+    /// ```
+    pub fn samples_avail(&self) -> usize {
+        self.left_buf.samples_avail().min(self.right_buf.samples_avail())
+    }
+
+    /// ```
+    /// Get channel outputs
+    ///
+    /// Returns the digital output of each channel, or None if its DAC is powered off
+    ///
+    /// Output:
+    ///     Amplitudes for channels 1-4, from 0-15 ([Option<u8>; 4])
+    /// ```
+    pub fn get_channel_outputs(&self) -> [Option<u8>; NUM_CHANNELS] {
+        let dac = |enabled: bool, output: u8| if enabled { Some(output) } else { None };
+
+        [
+            dac(self.ch1.is_dac_enabled(), self.ch1.get_output()),
+            dac(self.ch2.is_dac_enabled(), self.ch2.get_output()),
+            dac(self.ch3.is_dac_enabled(), self.ch3.get_output()),
+            dac(self.ch4.is_dac_enabled(), self.ch4.get_output()),
+        ]
+    }
+
+    /// ```
+    /// Read register
+    ///
+    /// Reads from a sound register or wave RAM
+    ///
+    /// Input:
+    ///     Address, from $FF10-$FF3F (u16)
+    ///
+    /// Output:
+    ///     Register value (u8)
+    /// ```
+    pub fn read_reg(&self, addr: u16) -> u8 {
+        match addr {
+            CH1_START..=CH1_STOP => { self.ch1.read_reg(addr - CH1_START) },
+            CH2_START..=CH2_STOP => { self.ch2.read_reg(addr - CH2_START) },
+            CH3_START..=CH3_STOP => { self.ch3.read_reg(addr - CH3_START) },
+            CH4_START..=CH4_STOP => { self.ch4.read_reg(addr - CH4_START) },
+            NR50 => { self.nr50 },
+            NR51 => { self.nr51 },
+            NR52 => { self.read_nr52() },
+            WAVE_RAM_START..=WAVE_RAM_STOP => {
+                self.ch3.read_wave_ram((addr - WAVE_RAM_START) as usize)
+            },
+            _ => { 0xFF }
+        }
+    }
+
+    /// ```
+    /// Write register
+    ///
+    /// Writes to a sound register or wave RAM
+    ///
+    /// Inputs:
+    ///     Address, from $FF10-$FF3F (u16)
+    ///     Value to write (u8)
+    /// ```
+    pub fn write_reg(&mut self, addr: u16, val: u8) {
+        // Wave RAM and NR52 are always accessible
+        match addr {
+            NR52 => {
+                self.write_nr52(val);
+                return;
+            },
+            WAVE_RAM_START..=WAVE_RAM_STOP => {
+                self.ch3.write_wave_ram((addr - WAVE_RAM_START) as usize, val);
+                return;
+            },
+            _ => {}
+        }
+
+        // While powered off, all other registers are read-only,
+        // except for the length counters on DMG
+        if !self.power {
+            match addr {
+                CH1_START..=CH1_STOP if addr - CH1_START == LENGTH_REG => { self.ch1.write_length(val) },
+                CH2_START..=CH2_STOP if addr - CH2_START == LENGTH_REG => { self.ch2.write_length(val) },
+                CH3_START..=CH3_STOP if addr - CH3_START == LENGTH_REG => { self.ch3.write_length(val) },
+                CH4_START..=CH4_STOP if addr - CH4_START == LENGTH_REG => { self.ch4.write_length(val) },
+                _ => {}
+            }
+            return;
+        }
+
+        let len_quirk = self.is_len_quirk();
+        match addr {
+            CH1_START..=CH1_STOP => { self.ch1.write_reg(addr - CH1_START, val, len_quirk) },
+            CH2_START..=CH2_STOP => { self.ch2.write_reg(addr - CH2_START, val, len_quirk) },
+            CH3_START..=CH3_STOP => { self.ch3.write_reg(addr - CH3_START, val, len_quirk) },
+            CH4_START..=CH4_STOP => { self.ch4.write_reg(addr - CH4_START, val, len_quirk) },
+            NR50 => { self.nr50 = val },
+            NR51 => { self.nr51 = val },
+            _ => {}
+        }
+    }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes the APU and all of its channels to a save state
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        self.ch1.save_state(state);
+        self.ch2.save_state(state);
+        self.ch3.save_state(state);
+        self.ch4.save_state(state);
+        state.write_u8(self.nr50);
+        state.write_u8(self.nr51);
+        state.write_bool(self.power);
+        state.write_u8(self.frame_step);
+        state.write_u16(self.frame_cycles);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the APU from a save state. Any audio not yet played is discarded.
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.ch1.load_state(state)?;
+        self.ch2.load_state(state)?;
+        self.ch3.load_state(state)?;
+        self.ch4.load_state(state)?;
+        self.nr50 = state.read_u8()?;
+        self.nr51 = state.read_u8()?;
+        self.power = state.read_bool()?;
+        self.frame_step = state.read_u8()?;
+        self.frame_cycles = state.read_u16()?;
+
+        self.left_buf.clear();
+        self.right_buf.clear();
+        self.last_left = 0;
+        self.last_right = 0;
+        Ok(())
+    }
+
+    // ===================
+    // = Private methods =
+    // ===================
+
+    /// ```
+    /// Step frame sequencer
+    ///
+    /// Clocks the length, sweep, and envelope units for the current step
+    /// ```
+    fn step_frame_sequencer(&mut self) {
+        match self.frame_step {
+            0 | 4 => {
+                self.clock_lengths();
+            },
+            2 | 6 => {
+                self.clock_lengths();
+                self.ch1.clock_sweep();
+            },
+            7 => {
+                self.ch1.clock_envelope();
+                self.ch2.clock_envelope();
+                self.ch4.clock_envelope();
+            },
+            _ => {}
+        }
+
+        self.frame_step = (self.frame_step + 1) % FRAME_SEQ_STEPS;
+    }
+
+    /// ```
+    /// Mix
+    ///
+    /// Combines the channel outputs into the left and right terminals,
+    /// recording any change in amplitude into the sample buffers
+    ///
+    /// Input:
+    ///     M-cycles since the start of this tick (u32)
+    /// ```
+    fn mix(&mut self, time: u32) {
+        let mut left = 0;
+        let mut right = 0;
+
+        for (i, output) in self.get_channel_outputs().iter().enumerate() {
+            // DAC maps 0-15 onto an analog range centered around 0
+            let analog = match output {
+                Some(val) => { 2 * (*val as i32) - 15 },
+                None => { 0 }
+            };
+
+            if self.nr51.get_bit(i as u8 + 4) {
+                left += analog;
+            }
+            if self.nr51.get_bit(i as u8) {
+                right += analog;
+            }
+        }
+
+        let left_vol = ((self.nr50 >> 4) & 0b111) as i32 + 1;
+        let right_vol = (self.nr50 & 0b111) as i32 + 1;
+        left *= left_vol * OUTPUT_GAIN;
+        right *= right_vol * OUTPUT_GAIN;
+
+        if left != self.last_left {
+            self.left_buf.add_delta(time, (left - self.last_left) as f32);
+            self.last_left = left;
+        }
+        if right != self.last_right {
+            self.right_buf.add_delta(time, (right - self.last_right) as f32);
+            self.last_right = right;
+        }
+    }
+
+    /// ```
+    /// Clock lengths
+    ///
+    /// Clocks the length counter of every channel
+    /// ```
+    fn clock_lengths(&mut self) {
+        self.ch1.clock_length();
+        self.ch2.clock_length();
+        self.ch3.clock_length();
+        self.ch4.clock_length();
+    }
+
+    /// ```
+    /// Is length quirk
+    ///
+    /// Whether the next frame sequencer step won't clock the length counters.
+    /// Some writes to NRx4 behave differently during this half of the period.
+    ///
+    /// Output:
+    ///     If the next step is a non-length step (bool)
+    /// ```
+    fn is_len_quirk(&self) -> bool {
+        self.frame_step & 1 == 1
+    }
+
+    /// ```
+    /// Read NR52
+    ///
+    /// Gets the power status, as well as which channels are currently enabled
+    ///
+    /// Output:
+    ///     Value of NR52 (u8)
+    /// ```
+    fn read_nr52(&self) -> u8 {
+        let mut val = NR52_MASK;
+        val.write_bit(POWER_BIT, self.power);
+        val.write_bit(0, self.ch1.is_enabled());
+        val.write_bit(1, self.ch2.is_enabled());
+        val.write_bit(2, self.ch3.is_enabled());
+        val.write_bit(3, self.ch4.is_enabled());
+        val
+    }
+
+    /// ```
+    /// Write NR52
+    ///
+    /// Powers the APU on or off. Powering off clears every sound register,
+    /// although the wave RAM and (on DMG) the length counters are left intact.
+    ///
+    /// Input:
+    ///     Value written (u8)
+    /// ```
+    fn write_nr52(&mut self, val: u8) {
+        let power = val.get_bit(POWER_BIT);
+
+        if self.power && !power {
+            self.ch1.power_off();
+            self.ch2.power_off();
+            self.ch3.power_off();
+            self.ch4.power_off();
+            self.nr50 = 0;
+            self.nr51 = 0;
+        } else if !self.power && power {
+            self.frame_step = 0;
+            self.frame_cycles = 0;
+            self.ch1.reset_duty();
+            self.ch2.reset_duty();
+        }
+
+        self.power = power;
+    }
+}
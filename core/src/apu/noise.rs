@@ -0,0 +1,282 @@
+use crate::apu::envelope::Envelope;
+use crate::apu::length::Length;
+use crate::utils::ModifyBits;
+use crate::state::{StateError, StateReader, StateWriter};
+
+/*
+ * Noise channel
+ *
+ * Channel 4 ($FF20-$FF23), outputs the low bit of a linear feedback shift register
+ *
+ * Reg  | Bits | Function
+ * -----+------+----------------------------
+ * NR41 | 5-0  | Length load (64 - L)
+ * NR42 | 7-0  | Volume envelope
+ * NR43 | 7-4  | Clock shift
+ *      | 3    | LFSR width (1 = 7 bits)
+ *      | 2-0  | Divisor code
+ * NR44 | 7    | Trigger
+ *      | 6    | Length enable
+ */
+
+const LENGTH_MAX: u16 = 64;
+const TRIGGER_BIT: u8 = 7;
+const LENGTH_EN_BIT: u8 = 6;
+const WIDTH_BIT: u8 = 3;
+const LFSR_SEED: u16 = 0x7FFF;
+
+const DIVISORS: [i32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+// Values that are always read back as 1s, per register
+const READ_MASKS: [u8; 5] = [0xFF, 0xFF, 0x00, 0x00, 0xBF];
+
+#[derive(Copy, Clone)]
+pub struct Noise {
+    enabled: bool,
+    clock_shift: u8,
+    short_mode: bool,
+    divisor_code: u8,
+    lfsr: u16,
+    timer: i32,
+    length: Length,
+    envelope: Envelope,
+}
+
+impl Noise {
+    pub fn new() -> Noise {
+        Noise {
+            enabled: false,
+            clock_shift: 0,
+            short_mode: false,
+            divisor_code: 0,
+            lfsr: LFSR_SEED,
+            timer: 0,
+            length: Length::new(LENGTH_MAX),
+            envelope: Envelope::new(),
+        }
+    }
+
+    /// ```
+    /// Tick
+    ///
+    /// Advances the channel's frequency timer, shifting the LFSR
+    ///
+    /// Input:
+    ///     Number of T-cycles elapsed (i32)
+    /// ```
+    pub fn tick(&mut self, cycles: i32) {
+        self.timer -= cycles;
+        while self.timer <= 0 {
+            self.timer += self.get_period();
+
+            let xor = (self.lfsr & 0b1) ^ ((self.lfsr >> 1) & 0b1);
+            self.lfsr = (self.lfsr >> 1) | (xor << 14);
+            // In 7-bit mode, the result is also copied into bit 6
+            if self.short_mode {
+                self.lfsr.write_bit(6, xor != 0);
+            }
+        }
+    }
+
+    /// ```
+    /// Get output
+    ///
+    /// Returns the current digital output of the channel
+    ///
+    /// Output:
+    ///     Channel amplitude, from 0-15 (u8)
+    /// ```
+    pub fn get_output(&self) -> u8 {
+        if !self.enabled || self.lfsr.get_bit(0) {
+            return 0;
+        }
+
+        self.envelope.get_volume()
+    }
+
+    /// ```
+    /// Read register
+    ///
+    /// Reads one of the channel's registers
+    ///
+    /// Input:
+    ///     Register index, 0-4 (u16)
+    ///
+    /// Output:
+    ///     Register value (u8)
+    /// ```
+    pub fn read_reg(&self, reg: u16) -> u8 {
+        let val = match reg {
+            2 => { self.envelope.read_reg() },
+            3 => {
+                let width = if self.short_mode { 1 << WIDTH_BIT } else { 0 };
+                (self.clock_shift << 4) | width | self.divisor_code
+            },
+            4 if self.length.is_enabled() => { 1 << LENGTH_EN_BIT },
+            _ => { 0 }
+        };
+
+        val | READ_MASKS[reg as usize]
+    }
+
+    /// ```
+    /// Write register
+    ///
+    /// Writes to one of the channel's registers
+    ///
+    /// Inputs:
+    ///     Register index, 0-4 (u16)
+    ///     Value to write (u8)
+    ///     Whether the next frame sequencer step will not clock length (bool)
+    /// ```
+    pub fn write_reg(&mut self, reg: u16, val: u8, len_quirk: bool) {
+        match reg {
+            1 => {
+                self.length.load((val & 0x3F) as u16);
+            },
+            2 => {
+                self.envelope.write_reg(val);
+                if !self.envelope.is_dac_enabled() {
+                    self.enabled = false;
+                }
+            },
+            3 => {
+                self.clock_shift = val >> 4;
+                self.short_mode = val.get_bit(WIDTH_BIT);
+                self.divisor_code = val & 0b111;
+            },
+            4 => {
+                let expired = self.length.set_enabled(val.get_bit(LENGTH_EN_BIT), len_quirk);
+                if val.get_bit(TRIGGER_BIT) {
+                    self.trigger(len_quirk);
+                } else if expired {
+                    self.enabled = false;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// ```
+    /// Write length
+    ///
+    /// Writes the length counter alone, allowed on DMG even when the APU is off
+    ///
+    /// Input:
+    ///     Value written to NR41 (u8)
+    /// ```
+    pub fn write_length(&mut self, val: u8) {
+        self.length.load((val & 0x3F) as u16);
+    }
+
+    /// ```
+    /// Clock length
+    ///
+    /// Clocks length counter, disabling channel if it expires
+    /// ```
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    /// ```
+    /// Clock envelope
+    ///
+    /// Clocks the volume envelope
+    /// ```
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn is_dac_enabled(&self) -> bool {
+        self.envelope.is_dac_enabled()
+    }
+
+    /// ```
+    /// Power off
+    ///
+    /// Clears all channel registers, except for the length counter
+    /// ```
+    pub fn power_off(&mut self) {
+        let mut length = self.length;
+        length.disable();
+        *self = Noise { length, ..Noise::new() };
+    }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes the noise channel to a save state
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_u8(self.clock_shift);
+        state.write_bool(self.short_mode);
+        state.write_u8(self.divisor_code);
+        state.write_u16(self.lfsr);
+        state.write_i32(self.timer);
+        self.length.save_state(state);
+        self.envelope.save_state(state);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the noise channel from a save state
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = state.read_bool()?;
+        self.clock_shift = state.read_u8()?;
+        self.short_mode = state.read_bool()?;
+        self.divisor_code = state.read_u8()?;
+        self.lfsr = state.read_u16()?;
+        self.timer = state.read_i32()?;
+        self.length.load_state(state)?;
+        self.envelope.load_state(state)
+    }
+}
+
+// Private functions
+impl Noise {
+    /// ```
+    /// Trigger
+    ///
+    /// Restarts the channel, following a write to NR44 with bit 7 set
+    ///
+    /// Input:
+    ///     Whether the next frame sequencer step will not clock length (bool)
+    /// ```
+    fn trigger(&mut self, len_quirk: bool) {
+        self.enabled = self.envelope.is_dac_enabled();
+        self.length.trigger(len_quirk);
+        self.timer = self.get_period();
+        self.envelope.trigger();
+        self.lfsr = LFSR_SEED;
+    }
+
+    /// ```
+    /// Get period
+    ///
+    /// Time between LFSR shifts, in T-cycles
+    ///
+    /// Output:
+    ///     Timer period (i32)
+    /// ```
+    fn get_period(&self) -> i32 {
+        DIVISORS[self.divisor_code as usize] << self.clock_shift
+    }
+}
@@ -0,0 +1,394 @@
+use crate::apu::envelope::Envelope;
+use crate::apu::length::Length;
+use crate::utils::ModifyBits;
+use crate::state::{StateError, StateReader, StateWriter};
+
+/*
+ * Pulse (square wave) channels
+ *
+ * Channel 1 ($FF10-$FF14) and Channel 2 ($FF16-$FF19)
+ * Only channel 1 has a frequency sweep unit
+ *
+ * Reg  | Bits | Function
+ * -----+------+----------------------------
+ * NRx0 | 6-4  | Sweep period (CH1 only)
+ *      | 3    | Sweep negate (CH1 only)
+ *      | 2-0  | Sweep shift (CH1 only)
+ * NRx1 | 7-6  | Duty cycle
+ *      | 5-0  | Length load (64 - L)
+ * NRx2 | 7-0  | Volume envelope
+ * NRx3 | 7-0  | Frequency, low bits
+ * NRx4 | 7    | Trigger
+ *      | 6    | Length enable
+ *      | 2-0  | Frequency, high bits
+ */
+
+const LENGTH_MAX: u16 = 64;
+const MAX_FREQ: u16 = 2047;
+const TRIGGER_BIT: u8 = 7;
+const LENGTH_EN_BIT: u8 = 6;
+const NEGATE_BIT: u8 = 3;
+
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 1], // 12.5%
+    [1, 0, 0, 0, 0, 0, 0, 1], // 25%
+    [1, 0, 0, 0, 0, 1, 1, 1], // 50%
+    [0, 1, 1, 1, 1, 1, 1, 0], // 75%
+];
+
+// Values that are always read back as 1s, per register
+const READ_MASKS: [u8; 5] = [0x80, 0x3F, 0x00, 0xFF, 0xBF];
+
+#[derive(Copy, Clone)]
+pub struct Pulse {
+    enabled: bool,
+    has_sweep: bool,
+    duty: u8,
+    duty_pos: usize,
+    freq: u16,
+    timer: i32,
+    length: Length,
+    envelope: Envelope,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_timer: u8,
+    sweep_enabled: bool,
+    sweep_negated: bool,
+    shadow_freq: u16,
+}
+
+impl Pulse {
+    pub fn new(has_sweep: bool) -> Pulse {
+        Pulse {
+            enabled: false,
+            has_sweep,
+            duty: 0,
+            duty_pos: 0,
+            freq: 0,
+            timer: 0,
+            length: Length::new(LENGTH_MAX),
+            envelope: Envelope::new(),
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_timer: 0,
+            sweep_enabled: false,
+            sweep_negated: false,
+            shadow_freq: 0,
+        }
+    }
+
+    /// ```
+    /// Tick
+    ///
+    /// Advances the channel's frequency timer
+    ///
+    /// Input:
+    ///     Number of T-cycles elapsed (i32)
+    /// ```
+    pub fn tick(&mut self, cycles: i32) {
+        self.timer -= cycles;
+        while self.timer <= 0 {
+            self.timer += self.get_period();
+            self.duty_pos = (self.duty_pos + 1) % 8;
+        }
+    }
+
+    /// ```
+    /// Get output
+    ///
+    /// Returns the current digital output of the channel
+    ///
+    /// Output:
+    ///     Channel amplitude, from 0-15 (u8)
+    /// ```
+    pub fn get_output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+
+        DUTY_TABLE[self.duty as usize][self.duty_pos] * self.envelope.get_volume()
+    }
+
+    /// ```
+    /// Read register
+    ///
+    /// Reads one of the channel's registers
+    ///
+    /// Input:
+    ///     Register index, 0-4 (u16)
+    ///
+    /// Output:
+    ///     Register value (u8)
+    /// ```
+    pub fn read_reg(&self, reg: u16) -> u8 {
+        let val = match reg {
+            0 => {
+                if self.has_sweep {
+                    let negate = if self.sweep_negate { 1 << NEGATE_BIT } else { 0 };
+                    (self.sweep_period << 4) | negate | self.sweep_shift
+                } else {
+                    0xFF
+                }
+            },
+            1 => { self.duty << 6 },
+            2 => { self.envelope.read_reg() },
+            4 if self.length.is_enabled() => { 1 << LENGTH_EN_BIT },
+            _ => { 0 }
+        };
+
+        val | READ_MASKS[reg as usize]
+    }
+
+    /// ```
+    /// Write register
+    ///
+    /// Writes to one of the channel's registers
+    ///
+    /// Inputs:
+    ///     Register index, 0-4 (u16)
+    ///     Value to write (u8)
+    ///     Whether the next frame sequencer step will not clock length (bool)
+    /// ```
+    pub fn write_reg(&mut self, reg: u16, val: u8, len_quirk: bool) {
+        match reg {
+            0 if self.has_sweep => {
+                self.sweep_period = (val >> 4) & 0b111;
+                self.sweep_negate = val.get_bit(NEGATE_BIT);
+                self.sweep_shift = val & 0b111;
+                // Leaving negate mode after a negated calculation disables the channel
+                if !self.sweep_negate && self.sweep_negated {
+                    self.enabled = false;
+                }
+            },
+            1 => {
+                self.duty = val >> 6;
+                self.length.load((val & 0x3F) as u16);
+            },
+            2 => {
+                self.envelope.write_reg(val);
+                if !self.envelope.is_dac_enabled() {
+                    self.enabled = false;
+                }
+            },
+            3 => {
+                self.freq = (self.freq & 0x700) | (val as u16);
+            },
+            4 => {
+                self.freq = (self.freq & 0xFF) | (((val & 0b111) as u16) << 8);
+                let expired = self.length.set_enabled(val.get_bit(LENGTH_EN_BIT), len_quirk);
+                if val.get_bit(TRIGGER_BIT) {
+                    self.trigger(len_quirk);
+                } else if expired {
+                    self.enabled = false;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// ```
+    /// Write length
+    ///
+    /// Writes the length counter alone, allowed on DMG even when the APU is off
+    ///
+    /// Input:
+    ///     Value written to NRx1 (u8)
+    /// ```
+    pub fn write_length(&mut self, val: u8) {
+        self.length.load((val & 0x3F) as u16);
+    }
+
+    /// ```
+    /// Clock length
+    ///
+    /// Clocks length counter, disabling channel if it expires
+    /// ```
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    /// ```
+    /// Clock envelope
+    ///
+    /// Clocks the volume envelope
+    /// ```
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// ```
+    /// Clock sweep
+    ///
+    /// Clocks the frequency sweep unit, only used by channel 1
+    /// ```
+    pub fn clock_sweep(&mut self) {
+        if !self.has_sweep {
+            return;
+        }
+
+        self.sweep_timer = self.sweep_timer.saturating_sub(1);
+        if self.sweep_timer == 0 {
+            self.sweep_timer = if self.sweep_period == 0 { 8 } else { self.sweep_period };
+
+            if self.sweep_enabled && self.sweep_period != 0 {
+                let new_freq = self.calc_sweep();
+                if new_freq <= MAX_FREQ && self.sweep_shift != 0 {
+                    self.shadow_freq = new_freq;
+                    self.freq = new_freq;
+                    // Frequency is calculated again, but only checked for overflow
+                    self.calc_sweep();
+                }
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn is_dac_enabled(&self) -> bool {
+        self.envelope.is_dac_enabled()
+    }
+
+    /// ```
+    /// Reset duty
+    ///
+    /// Duty step is reset whenever the APU is powered on
+    /// ```
+    pub fn reset_duty(&mut self) {
+        self.duty_pos = 0;
+    }
+
+    /// ```
+    /// Power off
+    ///
+    /// Clears all channel registers, except for the length counter
+    /// ```
+    pub fn power_off(&mut self) {
+        let mut length = self.length;
+        length.disable();
+        *self = Pulse { length, ..Pulse::new(self.has_sweep) };
+    }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes the pulse channel to a save state
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_u8(self.duty);
+        state.write_u8(self.duty_pos as u8);
+        state.write_u16(self.freq);
+        state.write_i32(self.timer);
+        self.length.save_state(state);
+        self.envelope.save_state(state);
+        state.write_u8(self.sweep_period);
+        state.write_bool(self.sweep_negate);
+        state.write_u8(self.sweep_shift);
+        state.write_u8(self.sweep_timer);
+        state.write_bool(self.sweep_enabled);
+        state.write_bool(self.sweep_negated);
+        state.write_u16(self.shadow_freq);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the pulse channel from a save state
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = state.read_bool()?;
+        self.duty = state.read_u8()?;
+        self.duty_pos = state.read_u8()? as usize;
+        self.freq = state.read_u16()?;
+        self.timer = state.read_i32()?;
+        self.length.load_state(state)?;
+        self.envelope.load_state(state)?;
+        self.sweep_period = state.read_u8()?;
+        self.sweep_negate = state.read_bool()?;
+        self.sweep_shift = state.read_u8()?;
+        self.sweep_timer = state.read_u8()?;
+        self.sweep_enabled = state.read_bool()?;
+        self.sweep_negated = state.read_bool()?;
+        self.shadow_freq = state.read_u16()?;
+        Ok(())
+    }
+}
+
+// Private functions
+impl Pulse {
+    /// ```
+    /// Trigger
+    ///
+    /// Restarts the channel, following a write to NRx4 with bit 7 set
+    ///
+    /// Input:
+    ///     Whether the next frame sequencer step will not clock length (bool)
+    /// ```
+    fn trigger(&mut self, len_quirk: bool) {
+        self.enabled = self.envelope.is_dac_enabled();
+        self.length.trigger(len_quirk);
+        self.timer = self.get_period();
+        self.envelope.trigger();
+
+        if self.has_sweep {
+            self.shadow_freq = self.freq;
+            self.sweep_timer = if self.sweep_period == 0 { 8 } else { self.sweep_period };
+            self.sweep_enabled = self.sweep_period != 0 || self.sweep_shift != 0;
+            self.sweep_negated = false;
+            if self.sweep_shift != 0 {
+                self.calc_sweep();
+            }
+        }
+    }
+
+    /// ```
+    /// Calculate sweep
+    ///
+    /// Calculates the next swept frequency, disabling the channel on overflow
+    ///
+    /// Output:
+    ///     New frequency (u16)
+    /// ```
+    fn calc_sweep(&mut self) -> u16 {
+        let delta = self.shadow_freq >> self.sweep_shift;
+        let new_freq = if self.sweep_negate {
+            self.sweep_negated = true;
+            self.shadow_freq - delta
+        } else {
+            self.shadow_freq + delta
+        };
+
+        if new_freq > MAX_FREQ {
+            self.enabled = false;
+        }
+
+        new_freq
+    }
+
+    /// ```
+    /// Get period
+    ///
+    /// Length of one duty step, in T-cycles
+    ///
+    /// Output:
+    ///     Timer period (i32)
+    /// ```
+    fn get_period(&self) -> i32 {
+        (2048 - self.freq as i32) * 4
+    }
+}
@@ -0,0 +1,326 @@
+use crate::apu::length::Length;
+use crate::utils::ModifyBits;
+use crate::state::{StateError, StateReader, StateWriter};
+
+/*
+ * Wave channel
+ *
+ * Channel 3 ($FF1A-$FF1E), plays back 32 4-bit samples from wave RAM ($FF30-$FF3F)
+ *
+ * Reg  | Bits | Function
+ * -----+------+----------------------------
+ * NR30 | 7    | DAC power
+ * NR31 | 7-0  | Length load (256 - L)
+ * NR32 | 6-5  | Volume code (0 = mute, 1 = 100%, 2 = 50%, 3 = 25%)
+ * NR33 | 7-0  | Frequency, low bits
+ * NR34 | 7    | Trigger
+ *      | 6    | Length enable
+ *      | 2-0  | Frequency, high bits
+ */
+
+pub const WAVE_RAM_SIZE: usize = 16;
+
+const LENGTH_MAX: u16 = 256;
+const SAMPLE_NUM: usize = WAVE_RAM_SIZE * 2;
+const DAC_BIT: u8 = 7;
+const TRIGGER_BIT: u8 = 7;
+const LENGTH_EN_BIT: u8 = 6;
+
+// Amount to shift the sample right, per volume code
+const VOLUME_SHIFT: [u8; 4] = [4, 0, 1, 2];
+
+// Values that are always read back as 1s, per register
+const READ_MASKS: [u8; 5] = [0x7F, 0xFF, 0x9F, 0xFF, 0xBF];
+
+#[derive(Copy, Clone)]
+pub struct Wave {
+    enabled: bool,
+    dac_enabled: bool,
+    volume_code: u8,
+    freq: u16,
+    timer: i32,
+    position: usize,
+    sample: u8,
+    length: Length,
+    wave_ram: [u8; WAVE_RAM_SIZE],
+}
+
+impl Wave {
+    pub fn new() -> Wave {
+        Wave {
+            enabled: false,
+            dac_enabled: false,
+            volume_code: 0,
+            freq: 0,
+            timer: 0,
+            position: 0,
+            sample: 0,
+            length: Length::new(LENGTH_MAX),
+            wave_ram: [0; WAVE_RAM_SIZE],
+        }
+    }
+
+    /// ```
+    /// Tick
+    ///
+    /// Advances the channel's frequency timer
+    ///
+    /// Input:
+    ///     Number of T-cycles elapsed (i32)
+    /// ```
+    pub fn tick(&mut self, cycles: i32) {
+        self.timer -= cycles;
+        while self.timer <= 0 {
+            self.timer += self.get_period();
+            self.position = (self.position + 1) % SAMPLE_NUM;
+            self.sample = self.get_sample(self.position);
+        }
+    }
+
+    /// ```
+    /// Get output
+    ///
+    /// Returns the current digital output of the channel
+    ///
+    /// Output:
+    ///     Channel amplitude, from 0-15 (u8)
+    /// ```
+    pub fn get_output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+
+        self.sample >> VOLUME_SHIFT[self.volume_code as usize]
+    }
+
+    /// ```
+    /// Read register
+    ///
+    /// Reads one of the channel's registers
+    ///
+    /// Input:
+    ///     Register index, 0-4 (u16)
+    ///
+    /// Output:
+    ///     Register value (u8)
+    /// ```
+    pub fn read_reg(&self, reg: u16) -> u8 {
+        let val = match reg {
+            0 if self.dac_enabled => { 1 << DAC_BIT },
+            2 => { self.volume_code << 5 },
+            4 if self.length.is_enabled() => { 1 << LENGTH_EN_BIT },
+            _ => { 0 }
+        };
+
+        val | READ_MASKS[reg as usize]
+    }
+
+    /// ```
+    /// Write register
+    ///
+    /// Writes to one of the channel's registers
+    ///
+    /// Inputs:
+    ///     Register index, 0-4 (u16)
+    ///     Value to write (u8)
+    ///     Whether the next frame sequencer step will not clock length (bool)
+    /// ```
+    pub fn write_reg(&mut self, reg: u16, val: u8, len_quirk: bool) {
+        match reg {
+            0 => {
+                self.dac_enabled = val.get_bit(DAC_BIT);
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            },
+            1 => {
+                self.length.load(val as u16);
+            },
+            2 => {
+                self.volume_code = (val >> 5) & 0b11;
+            },
+            3 => {
+                self.freq = (self.freq & 0x700) | (val as u16);
+            },
+            4 => {
+                self.freq = (self.freq & 0xFF) | (((val & 0b111) as u16) << 8);
+                let expired = self.length.set_enabled(val.get_bit(LENGTH_EN_BIT), len_quirk);
+                if val.get_bit(TRIGGER_BIT) {
+                    self.trigger(len_quirk);
+                } else if expired {
+                    self.enabled = false;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// ```
+    /// Write length
+    ///
+    /// Writes the length counter alone, allowed on DMG even when the APU is off
+    ///
+    /// Input:
+    ///     Value written to NR31 (u8)
+    /// ```
+    pub fn write_length(&mut self, val: u8) {
+        self.length.load(val as u16);
+    }
+
+    /// ```
+    /// Read wave RAM
+    ///
+    /// Reads a byte of wave RAM. While the channel is playing, only the byte
+    /// currently being played back can be accessed.
+    ///
+    /// Input:
+    ///     Offset into wave RAM (usize)
+    ///
+    /// Output:
+    ///     Two packed samples (u8)
+    /// ```
+    pub fn read_wave_ram(&self, offset: usize) -> u8 {
+        if self.enabled {
+            self.wave_ram[self.position / 2]
+        } else {
+            self.wave_ram[offset]
+        }
+    }
+
+    /// ```
+    /// Write wave RAM
+    ///
+    /// Writes a byte of wave RAM, with the same restrictions as reading
+    ///
+    /// Inputs:
+    ///     Offset into wave RAM (usize)
+    ///     Two packed samples (u8)
+    /// ```
+    pub fn write_wave_ram(&mut self, offset: usize, val: u8) {
+        if self.enabled {
+            self.wave_ram[self.position / 2] = val;
+        } else {
+            self.wave_ram[offset] = val;
+        }
+    }
+
+    /// ```
+    /// Clock length
+    ///
+    /// Clocks length counter, disabling channel if it expires
+    /// ```
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn is_dac_enabled(&self) -> bool {
+        self.dac_enabled
+    }
+
+    /// ```
+    /// Power off
+    ///
+    /// Clears all channel registers, except for the length counter and wave RAM
+    /// ```
+    pub fn power_off(&mut self) {
+        let mut length = self.length;
+        length.disable();
+        *self = Wave { length, wave_ram: self.wave_ram, ..Wave::new() };
+    }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes the wave channel, including wave RAM, to a save state
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.enabled);
+        state.write_bool(self.dac_enabled);
+        state.write_u8(self.volume_code);
+        state.write_u16(self.freq);
+        state.write_i32(self.timer);
+        state.write_u8(self.position as u8);
+        state.write_u8(self.sample);
+        self.length.save_state(state);
+        state.write_bytes(&self.wave_ram);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the wave channel from a save state
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = state.read_bool()?;
+        self.dac_enabled = state.read_bool()?;
+        self.volume_code = state.read_u8()?;
+        self.freq = state.read_u16()?;
+        self.timer = state.read_i32()?;
+        self.position = state.read_u8()? as usize;
+        self.sample = state.read_u8()?;
+        self.length.load_state(state)?;
+        state.read_bytes(&mut self.wave_ram)
+    }
+}
+
+// Private functions
+impl Wave {
+    /// ```
+    /// Trigger
+    ///
+    /// Restarts the channel, following a write to NR34 with bit 7 set
+    ///
+    /// Input:
+    ///     Whether the next frame sequencer step will not clock length (bool)
+    /// ```
+    fn trigger(&mut self, len_quirk: bool) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger(len_quirk);
+        // There is a short delay before the first sample is fetched
+        self.timer = self.get_period() + 6;
+        self.position = 0;
+    }
+
+    /// ```
+    /// Get sample
+    ///
+    /// Fetches a 4-bit sample from wave RAM, upper nibble first
+    ///
+    /// Input:
+    ///     Sample index, 0-31 (usize)
+    ///
+    /// Output:
+    ///     Sample value (u8)
+    /// ```
+    fn get_sample(&self, index: usize) -> u8 {
+        let byte = self.wave_ram[index / 2];
+        if index & 1 == 0 { byte >> 4 } else { byte & 0x0F }
+    }
+
+    /// ```
+    /// Get period
+    ///
+    /// Length of one sample, in T-cycles
+    ///
+    /// Output:
+    ///     Timer period (i32)
+    /// ```
+    fn get_period(&self) -> i32 {
+        (2048 - self.freq as i32) * 2
+    }
+}
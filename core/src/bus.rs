@@ -1,8 +1,12 @@
+use crate::apu::{APU, SOUND_START, SOUND_STOP};
 use crate::cartridge::{Cart, ROM_START, ROM_STOP, EXT_RAM_START, EXT_RAM_STOP};
 use crate::io::{Buttons, IO};
+use crate::memory::MemoryInterface;
 use crate::ppu::PPU;
 use crate::ppu::palette::Palettes;
-use crate::utils::{BYTE, DISP_SIZE};
+use crate::serial::{Serial, SerialDevice, SB, SC};
+use crate::state::{StateError, StateReader, StateWriter};
+use crate::utils::{BYTE, DISP_SIZE, ModifyBits};
 
 /*
  * RAM Map
@@ -31,7 +35,7 @@ use crate::utils::{BYTE, DISP_SIZE};
  * +----Internal-RAM------+ $C000
  * |   Work RAM Bank 0    |
  * +----------------------+ $D000
- * |   Work RAM Bank 1    |
+ * |  Work RAM Bank 1-7   |
  * +--------ECHO----------+ $E000
  * | Echo of Internal RAM |
  * +----------------------+ $FE00
@@ -56,6 +60,51 @@ use crate::utils::{BYTE, DISP_SIZE};
 const JOYPAD_REG: u16 = 0xFF00;
 const DMA_REG: u16 = 0xFF46;
 const OAM: u16 = 0xFE00;
+const SVBK_REG: u16 = 0xFF70;
+const KEY0_REG: u16 = 0xFF4C;
+const BOOT_REG: u16 = 0xFF50;
+const HDMA1_REG: u16 = 0xFF51;
+const HDMA2_REG: u16 = 0xFF52;
+const HDMA3_REG: u16 = 0xFF53;
+const HDMA4_REG: u16 = 0xFF54;
+const HDMA5_REG: u16 = 0xFF55;
+
+/*
+ * CGB VRAM DMA
+ *
+ * HDMA1-2 set the source address, HDMA3-4 the destination in VRAM.
+ * The lower four bits of both are ignored.
+ *
+ * HDMA5
+ * Bit | Info
+ * ----+-----------------------------------------------------
+ * 7   | Mode (0: General purpose, 1: HBlank)
+ * 6-0 | Length, in 16 byte blocks, minus 1
+ *
+ * General purpose DMA copies everything at once, halting the CPU.
+ * HBlank DMA copies one block at the start of each HBlank.
+ * Writing bit 7 = 0 during an HBlank DMA cancels it.
+ * Reading HDMA5 returns the remaining length, with bit 7 clear if still active.
+ */
+const HDMA_BLOCK_SIZE: u16 = 0x10;
+const HDMA_MODE_BIT: u8    = 7;
+const HDMA_LEN_MASK: u8    = 0x7F;
+const HDMA_ADDR_MASK: u8   = 0xF0;
+const HDMA_DST_MASK: u16   = 0x1FFF;
+
+/*
+ * Boot ROM
+ *
+ * Mapped over $0000-$00FF until a non-zero value is written to $FF50.
+ * The CGB boot ROM is larger, and is also mapped over $0200-$08FF,
+ * leaving the cartridge header at $0100-$01FF visible.
+ *
+ * When running a DMG game, the CGB boot ROM writes $04 to KEY0 ($FF4C) to
+ * switch into DMG compatibility mode, having set up palettes for the game.
+ */
+const BOOT_ROM_STOP: u16        = 0x00FF;
+const CGB_BOOT_ROM_START: u16   = 0x0200;
+const KEY0_DMG_MODE: u8         = 0x04;
 
 // RAM ranges
 // NOTE: Rust *still* doesn't allow exclusive ranges in match statements
@@ -64,12 +113,32 @@ const VRAM_START: u16           = ROM_STOP + 1;
 const VRAM_STOP: u16            = 0x9FFF;
 const WORK_RAM_START: u16       = EXT_RAM_STOP + 1;
 // const WORK_RAM_END: u16 = 0xDFFF;
+const WRAM_BANK_START: u16      = 0xD000;
+const WRAM_BANK_STOP: u16       = 0xDFFF;
 const RAM_END: u16              = 0xFFFF;
 
+// CGB has seven switchable work RAM banks, selected by SVBK
+const WRAM_BANK_SIZE: usize     = 0x1000;
+const WRAM_BANK_NUM: usize      = 7;
+const SVBK_MASK: u8             = 0b111;
+
 pub struct Bus {
     rom: Cart,
     io: IO,
-    ppu: PPU
+    ppu: PPU,
+    apu: APU,
+    serial: Serial,
+    wram: [u8; WRAM_BANK_SIZE * WRAM_BANK_NUM],
+    wram_bank: u8,
+    hdma_src: u16,
+    hdma_dst: u16,
+    hdma_len: u8,
+    hdma_active: bool,
+    dma_blocks: u16,
+    cgb: bool,
+    cgb_hardware: bool,
+    boot_rom: Vec<u8>,
+    boot_mapped: bool,
 }
 
 // ==================
@@ -81,12 +150,41 @@ impl Default for Bus {
     }
 }
 
+impl MemoryInterface for Bus {
+    const SYSTEM_IO: bool = true;
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        Bus::read_ram(self, addr)
+    }
+
+    fn write_ram(&mut self, addr: u16, val: u8) -> bool {
+        Bus::write_ram(self, addr, val)
+    }
+
+    fn is_cgb(&self) -> bool {
+        Bus::is_cgb(self)
+    }
+}
+
 impl Bus {
     pub fn new() -> Bus {
         Bus {
             rom: Cart::new(),
             io: IO::new(),
-            ppu: PPU::new()
+            ppu: PPU::new(),
+            apu: APU::new(),
+            serial: Serial::new(),
+            wram: [0; WRAM_BANK_SIZE * WRAM_BANK_NUM],
+            wram_bank: 0,
+            hdma_src: 0,
+            hdma_dst: 0,
+            hdma_len: HDMA_LEN_MASK,
+            hdma_active: false,
+            dma_blocks: 0,
+            cgb: false,
+            cgb_hardware: false,
+            boot_rom: Vec::new(),
+            boot_mapped: false,
         }
     }
 
@@ -95,11 +193,44 @@ impl Bus {
     ///
     /// Loads game into ROM
     ///
-    /// Input:
+    /// Inputs:
     ///     ROM data (&[u8])
+    ///     Whether the hardware supports CGB features (bool)
     /// ```
-    pub fn load_game(&mut self, rom: &[u8]) {
+    pub fn load_game(&mut self, rom: &[u8], cgb_hardware: bool) {
         self.rom.load_cart(&rom);
+        self.cgb_hardware = cgb_hardware;
+        // CGB features are only enabled if both the console and game support them
+        // The CGB boot ROM always starts in CGB mode, and decides for itself
+        self.cgb = cgb_hardware && (self.rom.is_cgb() || self.boot_mapped);
+        self.ppu.set_cgb(self.cgb);
+        self.serial.set_cgb(self.cgb);
+    }
+
+    /// ```
+    /// Load boot ROM
+    ///
+    /// Maps a boot ROM over the start of the cartridge, until the boot ROM disables it.
+    /// Must be loaded before the game.
+    ///
+    /// Input:
+    ///     Boot ROM data (&[u8])
+    /// ```
+    pub fn load_boot_rom(&mut self, boot_rom: &[u8]) {
+        self.boot_rom = boot_rom.to_vec();
+        self.boot_mapped = true;
+    }
+
+    /// ```
+    /// Is CGB
+    ///
+    /// Returns whether the loaded game runs in Game Boy Color mode
+    ///
+    /// Output:
+    ///     Whether game supports CGB (bool)
+    /// ```
+    pub fn is_cgb(&self) -> bool {
+        self.cgb
     }
 
     /// ```
@@ -127,14 +258,44 @@ impl Bus {
     /// ```
     pub fn read_ram(&self, addr: u16) -> u8 {
         match addr {
+            ROM_START..=ROM_STOP if self.is_boot_rom_addr(addr) => {
+                self.boot_rom[addr as usize]
+            },
             ROM_START..=ROM_STOP | EXT_RAM_START..=EXT_RAM_STOP => {
                 self.rom.read_cart(addr)
             },
             VRAM_START..=VRAM_STOP | WORK_RAM_START..=RAM_END => {
-                if addr == JOYPAD_REG {
-                    self.io.read_btns()
-                } else {
-                    self.ppu.read_vram(addr)
+                match addr {
+                    JOYPAD_REG => {
+                        self.io.read_btns()
+                    },
+                    SB..=SC => {
+                        self.serial.read_reg(addr)
+                    },
+                    SOUND_START..=SOUND_STOP => {
+                        self.apu.read_reg(addr)
+                    },
+                    BOOT_REG => {
+                        0xFF
+                    },
+                    SVBK_REG if self.cgb => {
+                        0xF8 | self.wram_bank
+                    },
+                    HDMA1_REG..=HDMA4_REG if self.cgb => {
+                        // Write only
+                        0xFF
+                    },
+                    HDMA5_REG if self.cgb => {
+                        let mut val = self.hdma_len;
+                        val.write_bit(HDMA_MODE_BIT, !self.hdma_active);
+                        val
+                    },
+                    WRAM_BANK_START..=WRAM_BANK_STOP if self.cgb => {
+                        self.wram[self.get_wram_index(addr)]
+                    },
+                    _ => {
+                        self.ppu.read_vram(addr)
+                    }
                 }
             }
         }
@@ -165,6 +326,42 @@ impl Bus {
                     DMA_REG => {
                         self.oam_dma(val);
                     },
+                    SB..=SC => {
+                        self.serial.write_reg(addr, val);
+                    },
+                    SOUND_START..=SOUND_STOP => {
+                        self.apu.write_reg(addr, val);
+                    },
+                    BOOT_REG => {
+                        if val != 0 {
+                            self.boot_mapped = false;
+                        }
+                    },
+                    KEY0_REG if self.boot_mapped && self.cgb_hardware => {
+                        self.set_key0(val);
+                    },
+                    SVBK_REG if self.cgb => {
+                        self.wram_bank = val & SVBK_MASK;
+                    },
+                    HDMA1_REG if self.cgb => {
+                        self.hdma_src = (self.hdma_src & 0x00FF) | ((val as u16) << BYTE);
+                    },
+                    HDMA2_REG if self.cgb => {
+                        self.hdma_src = (self.hdma_src & 0xFF00) | ((val & HDMA_ADDR_MASK) as u16);
+                    },
+                    HDMA3_REG if self.cgb => {
+                        self.hdma_dst = (self.hdma_dst & 0x00FF) | ((val as u16) << BYTE);
+                    },
+                    HDMA4_REG if self.cgb => {
+                        self.hdma_dst = (self.hdma_dst & 0xFF00) | ((val & HDMA_ADDR_MASK) as u16);
+                    },
+                    HDMA5_REG if self.cgb => {
+                        self.start_hdma(val);
+                    },
+                    WRAM_BANK_START..=WRAM_BANK_STOP if self.cgb => {
+                        let index = self.get_wram_index(addr);
+                        self.wram[index] = val;
+                    },
                     _ => {
                         self.ppu.write_vram(addr, val);
                     }
@@ -174,6 +371,316 @@ impl Bus {
         }
     }
 
+    /// ```
+    /// Get ROM CRC
+    ///
+    /// Returns the checksum of the loaded game
+    ///
+    /// Output:
+    ///     ROM checksum (u32)
+    /// ```
+    pub fn get_rom_crc(&self) -> u32 {
+        self.rom.get_rom_crc()
+    }
+
+    /// ```
+    /// Get WRAM
+    ///
+    /// Returns the contents of work RAM, including all CGB banks if enabled
+    ///
+    /// Output:
+    ///     Work RAM contents, 8 KiB or 32 KiB (Vec<u8>)
+    /// ```
+    pub fn get_wram(&self) -> Vec<u8> {
+        let mut wram: Vec<u8> = (WORK_RAM_START..WRAM_BANK_START).map(|addr| self.ppu.read_vram(addr)).collect();
+        if self.cgb {
+            wram.extend_from_slice(&self.wram);
+        } else {
+            wram.extend((WRAM_BANK_START..=WRAM_BANK_STOP).map(|addr| self.ppu.read_vram(addr)));
+        }
+        wram
+    }
+
+    /// ```
+    /// Set WRAM
+    ///
+    /// Replaces the contents of work RAM, in the same layout as get_wram
+    ///
+    /// Input:
+    ///     Work RAM contents (&[u8])
+    /// ```
+    pub fn set_wram(&mut self, data: &[u8]) {
+        let bank_0_len = (WRAM_BANK_START - WORK_RAM_START) as usize;
+        for (i, &val) in data.iter().enumerate() {
+            if i < bank_0_len {
+                self.ppu.write_vram(WORK_RAM_START + i as u16, val);
+            } else if self.cgb {
+                if let Some(byte) = self.wram.get_mut(i - bank_0_len) {
+                    *byte = val;
+                }
+            } else if i < bank_0_len + WRAM_BANK_SIZE {
+                self.ppu.write_vram(WORK_RAM_START + i as u16, val);
+            }
+        }
+    }
+
+    /// ```
+    /// Get VRAM bank
+    ///
+    /// Returns the contents of one of the video RAM banks
+    ///
+    /// Input:
+    ///     Bank number, 0 or 1 (usize)
+    ///
+    /// Output:
+    ///     VRAM bank contents (&[u8])
+    /// ```
+    pub fn get_vram_bank(&self, bank: usize) -> &[u8] {
+        self.ppu.get_vram_bank(bank)
+    }
+
+    /// ```
+    /// Set VRAM bank
+    ///
+    /// Replaces the contents of one of the video RAM banks
+    ///
+    /// Inputs:
+    ///     Bank number, 0 or 1 (usize)
+    ///     New bank contents (&[u8])
+    /// ```
+    pub fn set_vram_bank(&mut self, bank: usize, data: &[u8]) {
+        self.ppu.set_vram_bank(bank, data);
+    }
+
+    /// ```
+    /// Get palette RAM
+    ///
+    /// Returns the CGB background and object palette RAM
+    ///
+    /// Output:
+    ///     Background and object palette data (&[u8], &[u8])
+    /// ```
+    pub fn get_pal_ram(&self) -> (&[u8], &[u8]) {
+        self.ppu.get_pal_ram()
+    }
+
+    /// ```
+    /// Set palette RAM
+    ///
+    /// Replaces the CGB background and object palette RAM
+    ///
+    /// Inputs:
+    ///     Background palette data (&[u8])
+    ///     Object palette data (&[u8])
+    /// ```
+    pub fn set_pal_ram(&mut self, bg: &[u8], obj: &[u8]) {
+        self.ppu.set_pal_ram(bg, obj);
+    }
+
+    /// ```
+    /// Get MBC writes
+    ///
+    /// Lists register writes which put the cartridge into its current banking state
+    ///
+    /// Output:
+    ///     Address and value of each write (Vec<(u16, u8)>)
+    /// ```
+    pub fn get_mbc_writes(&self) -> Vec<(u16, u8)> {
+        self.rom.get_mbc_writes()
+    }
+
+    /// ```
+    /// Reset banking
+    ///
+    /// Returns the cartridge's banking registers to their power on state
+    /// ```
+    pub fn reset_banking(&mut self) {
+        self.rom.reset_banking();
+    }
+
+    /// ```
+    /// Get ROM bank
+    ///
+    /// Returns which ROM bank is mapped to $4000-$7FFF
+    ///
+    /// Output:
+    ///     ROM bank number (u16)
+    /// ```
+    pub fn get_rom_bank(&self) -> u16 {
+        self.rom.get_rom_bank()
+    }
+
+    /// ```
+    /// Get RAM bank
+    ///
+    /// Returns which external RAM bank is mapped to $A000-$BFFF
+    ///
+    /// Output:
+    ///     RAM bank number (u8)
+    /// ```
+    pub fn get_ram_bank(&self) -> u8 {
+        self.rom.get_ram_bank()
+    }
+
+    /// ```
+    /// Has RTC
+    ///
+    /// Returns whether the cartridge has a real time clock
+    ///
+    /// Output:
+    ///     If cartridge has an RTC (bool)
+    /// ```
+    pub fn has_rtc(&self) -> bool {
+        self.rom.has_rtc()
+    }
+
+    /// ```
+    /// Get RTC registers
+    ///
+    /// Returns the cartridge real time clock's current and latched time
+    ///
+    /// Output:
+    ///     Current and latched registers ([u8], [u8])
+    /// ```
+    pub fn get_rtc_regs(&self) -> ([u8; 5], [u8; 5]) {
+        self.rom.get_rtc_regs()
+    }
+
+    /// ```
+    /// Set RTC registers
+    ///
+    /// Sets the cartridge real time clock's current and latched time
+    ///
+    /// Inputs:
+    ///     Current registers ([u8])
+    ///     Latched registers ([u8])
+    ///     Time passed since the registers were saved, in seconds (u64)
+    /// ```
+    pub fn set_rtc_regs(&mut self, current: [u8; 5], latched: [u8; 5], extra_secs: u64) {
+        self.rom.set_rtc_regs(current, latched, extra_secs);
+    }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes the bus's own memory, followed by every component attached to it, to a save state
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.wram);
+        state.write_u8(self.wram_bank);
+        state.write_u16(self.hdma_src);
+        state.write_u16(self.hdma_dst);
+        state.write_u8(self.hdma_len);
+        state.write_bool(self.hdma_active);
+        state.write_u16(self.dma_blocks);
+        state.write_bool(self.cgb);
+        state.write_bool(self.cgb_hardware);
+        // The boot ROM itself belongs to whoever loaded it, so only whether it's mapped is saved
+        state.write_bool(self.boot_mapped);
+
+        self.rom.save_state(state);
+        self.io.save_state(state);
+        self.ppu.save_state(state);
+        self.apu.save_state(state);
+        self.serial.save_state(state);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the bus and its components from a save state.
+    /// Everything is read into copies first, so nothing changes if the state is invalid.
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let mut wram = self.wram;
+        state.read_bytes(&mut wram)?;
+        let wram_bank = state.read_u8()?;
+        let hdma_src = state.read_u16()?;
+        let hdma_dst = state.read_u16()?;
+        let hdma_len = state.read_u8()?;
+        let hdma_active = state.read_bool()?;
+        let dma_blocks = state.read_u16()?;
+        let cgb = state.read_bool()?;
+        let cgb_hardware = state.read_bool()?;
+        let boot_mapped = state.read_bool()?;
+        if boot_mapped && self.boot_rom.is_empty() {
+            return Err(StateError::NoBootRom);
+        }
+
+        let mut rom = self.rom.clone();
+        rom.load_state(state)?;
+        let mut io = self.io.clone();
+        io.load_state(state)?;
+        let mut ppu = self.ppu.clone();
+        ppu.load_state(state)?;
+        let mut apu = self.apu.clone();
+        apu.load_state(state)?;
+        // The serial port holds the connected device, so it can't be copied,
+        // but it's read last and only changes once all of its fields are valid
+        self.serial.load_state(state)?;
+
+        self.wram = wram;
+        self.wram_bank = wram_bank;
+        self.hdma_src = hdma_src;
+        self.hdma_dst = hdma_dst;
+        self.hdma_len = hdma_len;
+        self.hdma_active = hdma_active;
+        self.dma_blocks = dma_blocks;
+        self.cgb = cgb;
+        self.cgb_hardware = cgb_hardware;
+        self.boot_mapped = boot_mapped;
+        self.rom = rom;
+        self.io = io;
+        self.ppu = ppu;
+        self.apu = apu;
+        self.serial.set_cgb(self.cgb);
+        Ok(())
+    }
+
+    /// ```
+    /// HBlank DMA
+    ///
+    /// Copies the next block of an active HBlank DMA, called at the start of each HBlank
+    /// ```
+    pub fn hblank_dma(&mut self) {
+        if !self.hdma_active {
+            return;
+        }
+
+        self.copy_hdma_block();
+        if self.hdma_len == 0 {
+            // Transfer complete
+            self.hdma_active = false;
+            self.hdma_len = HDMA_LEN_MASK;
+        } else {
+            self.hdma_len -= 1;
+        }
+    }
+
+    /// ```
+    /// Take DMA blocks
+    ///
+    /// Returns how many VRAM DMA blocks were copied since last called,
+    /// as the CPU is halted while they are transferred
+    ///
+    /// Output:
+    ///     Number of 16 byte blocks copied (u16)
+    /// ```
+    pub fn take_dma_blocks(&mut self) -> u16 {
+        let blocks = self.dma_blocks;
+        self.dma_blocks = 0;
+        blocks
+    }
+
     /// ```
     /// Toggle button
     ///
@@ -262,6 +769,136 @@ impl Bus {
         self.ppu.set_status(mode);
     }
 
+    /// ```
+    /// Tick serial
+    ///
+    /// Advances any serial transfer in progress
+    ///
+    /// Input:
+    ///     Number of CPU cycles elapsed (u8)
+    ///
+    /// Output:
+    ///     Whether a transfer finished (bool)
+    /// ```
+    pub fn tick_serial(&mut self, cycles: u8) -> bool {
+        self.serial.tick(cycles)
+    }
+
+    /// ```
+    /// Connect serial
+    ///
+    /// Plugs a device into the serial port
+    ///
+    /// Input:
+    ///     Device to connect (Box<dyn SerialDevice + Send>)
+    /// ```
+    pub fn connect_serial(&mut self, device: Box<dyn SerialDevice + Send>) {
+        self.serial.connect(device);
+    }
+
+    /// ```
+    /// Disconnect serial
+    ///
+    /// Unplugs the device from the serial port
+    ///
+    /// Output:
+    ///     Device which was connected, if any (Option<Box<dyn SerialDevice + Send>>)
+    /// ```
+    pub fn disconnect_serial(&mut self) -> Option<Box<dyn SerialDevice + Send>> {
+        self.serial.disconnect()
+    }
+
+    pub fn is_serial_lockstep(&self) -> bool {
+        self.serial.is_lockstep()
+    }
+
+    /// ```
+    /// Set serial capture
+    ///
+    /// Sets whether to keep every byte sent over the serial port
+    ///
+    /// Input:
+    ///     Whether to capture sent bytes (bool)
+    /// ```
+    pub fn set_serial_capture(&mut self, enabled: bool) {
+        self.serial.set_capture(enabled);
+    }
+
+    /// ```
+    /// Take serial output
+    ///
+    /// Removes the bytes captured from the serial port so far
+    ///
+    /// Output:
+    ///     Bytes sent since last taken (Vec<u8>)
+    /// ```
+    pub fn take_serial_output(&mut self) -> Vec<u8> {
+        self.serial.take_output()
+    }
+
+    /// ```
+    /// Tick APU
+    ///
+    /// Advances the audio processing unit
+    ///
+    /// Input:
+    ///     Number of M-cycles elapsed (u8)
+    /// ```
+    pub fn tick_apu(&mut self, cycles: u8) {
+        self.apu.tick(cycles);
+    }
+
+    /// ```
+    /// Set sample rate
+    ///
+    /// Sets the rate the APU generates audio samples at
+    ///
+    /// Input:
+    ///     Sample rate, in Hz (u32)
+    /// ```
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.apu.set_sample_rate(rate);
+    }
+
+    /// ```
+    /// Set audio enabled
+    ///
+    /// Turns generation of audio samples on or off
+    ///
+    /// Input:
+    ///     Whether to generate samples (bool)
+    /// ```
+    pub fn set_audio_enabled(&mut self, enabled: bool) {
+        self.apu.set_output_enabled(enabled);
+    }
+
+    /// ```
+    /// Drain audio
+    ///
+    /// Moves generated audio samples into the given buffer
+    ///
+    /// Input:
+    ///     Buffer for interleaved stereo samples (&mut [i16])
+    ///
+    /// Output:
+    ///     Number of values written (usize)
+    /// ```
+    pub fn drain_audio(&mut self, out: &mut [i16]) -> usize {
+        self.apu.drain_samples(out)
+    }
+
+    /// ```
+    /// Audio samples available
+    ///
+    /// Number of stereo sample frames waiting to be drained
+    ///
+    /// Output:
+    ///     Number of frames (usize)
+    /// ```
+    pub fn audio_samples_avail(&self) -> usize {
+        self.apu.samples_avail()
+    }
+
     pub fn set_sys_pal(&mut self, pal: Palettes) {
         self.ppu.set_sys_pal(pal);
     }
@@ -281,6 +918,105 @@ impl Bus {
 
 // Private functions
 impl Bus {
+    /// ```
+    /// Is boot ROM address
+    ///
+    /// Whether reading this address should come from the boot ROM rather than the cartridge
+    ///
+    /// Input:
+    ///     RAM address (u16)
+    ///
+    /// Output:
+    ///     If address is mapped to the boot ROM (bool)
+    /// ```
+    fn is_boot_rom_addr(&self, addr: u16) -> bool {
+        if !self.boot_mapped {
+            return false;
+        }
+
+        let in_cgb_area = addr >= CGB_BOOT_ROM_START && (addr as usize) < self.boot_rom.len();
+        (addr <= BOOT_ROM_STOP && (addr as usize) < self.boot_rom.len()) || in_cgb_area
+    }
+
+    /// ```
+    /// Set KEY0
+    ///
+    /// Handles the CGB boot ROM choosing between CGB and DMG compatibility mode
+    ///
+    /// Input:
+    ///     Value written to KEY0 (u8)
+    /// ```
+    fn set_key0(&mut self, val: u8) {
+        let dmg_mode = (val & KEY0_DMG_MODE) != 0;
+        self.cgb = !dmg_mode;
+        self.ppu.set_cgb(!dmg_mode);
+        self.ppu.set_dmg_compat(dmg_mode);
+        self.serial.set_cgb(!dmg_mode);
+    }
+
+    /// ```
+    /// Get WRAM index
+    ///
+    /// Finds where a $D000-$DFFF address is stored, given the selected CGB work RAM bank
+    ///
+    /// Input:
+    ///     RAM address (u16)
+    ///
+    /// Output:
+    ///     Index into switchable work RAM (usize)
+    /// ```
+    fn get_wram_index(&self, addr: u16) -> usize {
+        // Bank 0 can't be selected, it maps to bank 1 instead
+        let bank = self.wram_bank.max(1) as usize;
+        (bank - 1) * WRAM_BANK_SIZE + (addr - WRAM_BANK_START) as usize
+    }
+
+    /// ```
+    /// Start HDMA
+    ///
+    /// Handles a write to HDMA5, starting or cancelling a VRAM DMA transfer
+    ///
+    /// Input:
+    ///     Value written to HDMA5 (u8)
+    /// ```
+    fn start_hdma(&mut self, val: u8) {
+        let hblank_mode = val.get_bit(HDMA_MODE_BIT);
+
+        if self.hdma_active && !hblank_mode {
+            // Cancel ongoing HBlank DMA
+            self.hdma_active = false;
+            return;
+        }
+
+        self.hdma_len = val & HDMA_LEN_MASK;
+        if hblank_mode {
+            self.hdma_active = true;
+        } else {
+            // General purpose DMA copies everything at once
+            for _ in 0..=self.hdma_len {
+                self.copy_hdma_block();
+            }
+            self.hdma_len = HDMA_LEN_MASK;
+        }
+    }
+
+    /// ```
+    /// Copy HDMA block
+    ///
+    /// Copies the next 16 bytes of a VRAM DMA transfer
+    /// ```
+    fn copy_hdma_block(&mut self) {
+        for _ in 0..HDMA_BLOCK_SIZE {
+            let byte = self.read_ram(self.hdma_src);
+            let dst = VRAM_START | (self.hdma_dst & HDMA_DST_MASK);
+            self.ppu.write_vram(dst, byte);
+            self.hdma_src = self.hdma_src.wrapping_add(1);
+            self.hdma_dst = self.hdma_dst.wrapping_add(1);
+        }
+
+        self.dma_blocks += 1;
+    }
+
     /// ```
     /// OAM DMA transfer
     ///
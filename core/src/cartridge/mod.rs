@@ -5,13 +5,16 @@ mod mbc5;
 mod rtc;
 
 use std::str::from_utf8;
+use std::sync::Arc;
 use mbc1::{mbc1_read_byte, mbc1_write_byte};
 use mbc2::{mbc2_read_byte, mbc2_write_byte};
 use mbc3::{mbc3_read_byte, mbc3_write_byte};
 use mbc5::{mbc5_read_byte, mbc5_write_byte};
 use rtc::RTC;
+use crate::state::{StateError, StateReader, StateWriter};
+use crate::utils::crc32;
 
-const ROM_BANK_SIZE: usize = 0x4000;
+pub const ROM_BANK_SIZE: usize = 0x4000;
 const RAM_BANK_SIZE: usize = 0x2000;
 const RAM_SIZES: [usize; 6] = [
     0,          // 0 KiB
@@ -93,17 +96,20 @@ pub enum MBC {
     MBC5
 }
 
+#[derive(Clone)]
 pub struct Cart {
     mbc: MBC,
     rom_bank: u16,
     ram_bank: u8,
-    rom: Vec<u8>,
+    // Shared, so copies made while loading a save state don't duplicate the ROM
+    rom: Arc<[u8]>,
     ram: Vec<u8>,
     ext_ram_enable: bool,
     rom_mode: bool,
     cgb: bool,
     rtc: RTC,
     has_battery: bool,
+    rom_crc: u32,
 }
 
 // ==================
@@ -121,13 +127,14 @@ impl Cart {
             mbc: MBC::NONE,
             rom_bank: 1,
             ram_bank: 0,
-            rom: Vec::new(),
+            rom: Arc::from(Vec::new()),
             ram: Vec::new(),
             ext_ram_enable: false,
             rom_mode: true,
             cgb: false,
             rtc: RTC::new(),
             has_battery: false,
+            rom_crc: 0,
         }
     }
 
@@ -152,9 +159,8 @@ impl Cart {
     ///     Array of game data
     /// ```
     pub fn load_cart(&mut self, rom: &[u8]) {
-        for byte in rom {
-            self.rom.push(*byte);
-        }
+        self.rom = Arc::from(rom);
+        self.rom_crc = crc32(&self.rom);
         self.set_mbc();
         self.set_cgb();
         self.init_ext_ram();
@@ -255,6 +261,30 @@ impl Cart {
         self.rom_bank
     }
 
+    /// ```
+    /// Get RAM bank number
+    ///
+    /// Returns external RAM bank number, used for debugging
+    ///
+    /// Output:
+    ///     RAM bank number (u8)
+    /// ```
+    pub fn get_ram_bank(&self) -> u8 {
+        self.ram_bank
+    }
+
+    /// ```
+    /// Get MBC
+    ///
+    /// Returns the memory bank controller type, from the ROM header
+    ///
+    /// Output:
+    ///     MBC type (MBC)
+    /// ```
+    pub fn get_mbc(&self) -> MBC {
+        self.mbc
+    }
+
     /// ```
     /// Has battery
     ///
@@ -266,6 +296,170 @@ impl Cart {
     pub fn has_battery(&self) -> bool {
         self.has_battery
     }
+
+    /// ```
+    /// Is CGB
+    ///
+    /// Returns whether game supports Game Boy Color features
+    ///
+    /// Output:
+    ///     Whether cartridge supports CGB (bool)
+    /// ```
+    pub fn is_cgb(&self) -> bool {
+        self.cgb
+    }
+
+    /// ```
+    /// Get ROM CRC
+    ///
+    /// Returns the CRC32 of the whole ROM, used to identify the game
+    ///
+    /// Output:
+    ///     ROM checksum (u32)
+    /// ```
+    pub fn get_rom_crc(&self) -> u32 {
+        self.rom_crc
+    }
+
+    /// ```
+    /// Get MBC writes
+    ///
+    /// Lists register writes which put a freshly loaded cartridge into the current banking state
+    ///
+    /// Output:
+    ///     Address and value of each write (Vec<(u16, u8)>)
+    /// ```
+    pub fn get_mbc_writes(&self) -> Vec<(u16, u8)> {
+        let ram_enable = if self.ext_ram_enable { 0x0A } else { 0x00 };
+
+        match self.mbc {
+            MBC::MBC1 => {
+                let upper = if self.rom_mode { ((self.rom_bank >> 5) & 0b11) as u8 } else { self.ram_bank };
+                vec![
+                    (RAM_ENABLE_START, ram_enable),
+                    (ROM_RAM_MODE_START, if self.rom_mode { 0x00 } else { 0x01 }),
+                    (ROM_BANK_NUM_START, (self.rom_bank & 0x1F) as u8),
+                    (RAM_BANK_NUM_START, upper),
+                ]
+            },
+            MBC::MBC2 => {
+                // MBC2 uses address bit 8 to tell ROM bank writes apart
+                vec![(RAM_ENABLE_START, ram_enable), (ROM_BANK_NUM_START | 0x0100, self.rom_bank as u8)]
+            },
+            MBC::MBC3 => {
+                vec![
+                    (RAM_ENABLE_START, ram_enable),
+                    (ROM_BANK_NUM_START, self.rom_bank as u8),
+                    (RAM_BANK_NUM_START, self.ram_bank),
+                ]
+            },
+            MBC::MBC5 => {
+                let high_bit = if (self.rom_bank >> 8) != 0 { 0x01 } else { 0x00 };
+                vec![
+                    (RAM_ENABLE_START, ram_enable),
+                    (ROM_BANK_NUM_START, (self.rom_bank & 0xFF) as u8),
+                    (0x3000, high_bit),
+                    (RAM_BANK_NUM_START, self.ram_bank),
+                ]
+            },
+            MBC::NONE | MBC::HuC1 => {
+                Vec::new()
+            }
+        }
+    }
+
+    /// ```
+    /// Reset banking
+    ///
+    /// Returns the banking registers to their power on state
+    /// ```
+    pub fn reset_banking(&mut self) {
+        self.rom_bank = 1;
+        self.ram_bank = 0;
+        self.ext_ram_enable = false;
+        self.rom_mode = true;
+    }
+
+    /// ```
+    /// Has RTC
+    ///
+    /// Returns whether the cartridge's MBC has a real time clock
+    ///
+    /// Output:
+    ///     If cartridge has an RTC (bool)
+    /// ```
+    pub fn has_rtc(&self) -> bool {
+        self.mbc == MBC::MBC3
+    }
+
+    /// ```
+    /// Get RTC registers
+    ///
+    /// Returns the real time clock's current and latched time
+    ///
+    /// Output:
+    ///     Current and latched registers ([u8], [u8])
+    /// ```
+    pub fn get_rtc_regs(&self) -> ([u8; 5], [u8; 5]) {
+        (self.rtc.get_regs(), self.rtc.get_latched())
+    }
+
+    /// ```
+    /// Set RTC registers
+    ///
+    /// Sets the real time clock's current and latched time
+    ///
+    /// Inputs:
+    ///     Current registers ([u8])
+    ///     Latched registers ([u8])
+    ///     Time passed since the registers were saved, in seconds (u64)
+    /// ```
+    pub fn set_rtc_regs(&mut self, current: [u8; 5], latched: [u8; 5], extra_secs: u64) {
+        self.rtc.set_regs(current, latched, extra_secs);
+    }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes the cartridge's banking state, external RAM and clock to a save state.
+    /// The ROM itself isn't saved.
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u16(self.rom_bank);
+        state.write_u8(self.ram_bank);
+        state.write_bool(self.ext_ram_enable);
+        state.write_bool(self.rom_mode);
+        state.write_vec(&self.ram);
+        self.rtc.save_state(state);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the cartridge from a save state
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.rom_bank = state.read_u16()?;
+        self.ram_bank = state.read_u8()?;
+        self.ext_ram_enable = state.read_bool()?;
+        self.rom_mode = state.read_bool()?;
+
+        let ram = state.read_vec()?;
+        if ram.len() != self.ram.len() {
+            return Err(StateError::Invalid);
+        }
+        self.ram = ram;
+        self.rtc.load_state(state)
+    }
 }
 
 // ===================
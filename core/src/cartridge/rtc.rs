@@ -1,10 +1,17 @@
 extern crate wasm_timer;
 use wasm_timer::Instant;
+use crate::state::{StateError, StateReader, StateWriter};
+use crate::utils::ModifyBits;
+use std::time::Duration;
 
 const SECS_IN_MIN: u64 = 60;
 const MIN_IN_HOURS: u64 = 60;
 const HOURS_IN_DAYS: u64 = 24;
+const MAX_DAYS: u64 = 0x1FF;
+const DAY_HALT_BIT: u8 = 6;
+const DAY_CARRY_BIT: u8 = 7;
 
+#[derive(Clone)]
 pub struct RTC {
     starttime: Instant,
     pub seconds: u8,
@@ -79,4 +86,131 @@ impl RTC {
     pub fn is_enabled(&self) -> bool {
         self.enabled
     }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes the clock to a save state. The time elapsed is saved, rather than when it started.
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u64(self.starttime.elapsed().as_secs());
+        state.write_u8(self.seconds);
+        state.write_u8(self.minutes);
+        state.write_u8(self.hours);
+        state.write_u16(self.days);
+        state.write_bool(self.halted);
+        state.write_bool(self.enabled);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the clock from a save state
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.starttime = start_time(state.read_u64()?);
+        self.seconds = state.read_u8()?;
+        self.minutes = state.read_u8()?;
+        self.hours = state.read_u8()?;
+        self.days = state.read_u16()?;
+        self.halted = state.read_bool()?;
+        self.enabled = state.read_bool()?;
+        Ok(())
+    }
+
+    /// ```
+    /// Get registers
+    ///
+    /// Returns the current time in the RTC register layout, rather than the latched time
+    ///
+    /// Output:
+    ///     Seconds, minutes, hours, day low and day high registers ([u8])
+    /// ```
+    pub fn get_regs(&self) -> [u8; 5] {
+        let secs = self.starttime.elapsed().as_secs();
+        let days = secs / (SECS_IN_MIN * MIN_IN_HOURS * HOURS_IN_DAYS);
+        let mut day_high = ((days >> 8) & 0b1) as u8;
+        day_high.write_bit(DAY_HALT_BIT, self.halted);
+        day_high.write_bit(DAY_CARRY_BIT, days > MAX_DAYS);
+
+        [
+            (secs % SECS_IN_MIN) as u8,
+            ((secs / SECS_IN_MIN) % MIN_IN_HOURS) as u8,
+            ((secs / (SECS_IN_MIN * MIN_IN_HOURS)) % HOURS_IN_DAYS) as u8,
+            (days & 0xFF) as u8,
+            day_high,
+        ]
+    }
+
+    /// ```
+    /// Get latched
+    ///
+    /// Returns the latched time in the RTC register layout
+    ///
+    /// Output:
+    ///     Seconds, minutes, hours, day low and day high registers ([u8])
+    /// ```
+    pub fn get_latched(&self) -> [u8; 5] {
+        let mut day_high = ((self.days >> 8) & 0b1) as u8;
+        day_high.write_bit(DAY_HALT_BIT, self.halted);
+        day_high.write_bit(DAY_CARRY_BIT, self.days > MAX_DAYS as u16);
+
+        [self.seconds, self.minutes, self.hours, (self.days & 0xFF) as u8, day_high]
+    }
+
+    /// ```
+    /// Set registers
+    ///
+    /// Sets the current and latched time from RTC register values
+    ///
+    /// Inputs:
+    ///     Current seconds, minutes, hours, day low and day high ([u8])
+    ///     Latched seconds, minutes, hours, day low and day high ([u8])
+    ///     Extra time to add to the current time, in seconds (u64)
+    /// ```
+    pub fn set_regs(&mut self, current: [u8; 5], latched: [u8; 5], extra_secs: u64) {
+        let days = ((current[4] as u64 & 0b1) << 8) | current[3] as u64;
+        let hours = days * HOURS_IN_DAYS + current[2] as u64;
+        let mins = hours * MIN_IN_HOURS + current[1] as u64;
+        let secs = mins * SECS_IN_MIN + current[0] as u64;
+        self.starttime = start_time(secs.saturating_add(extra_secs));
+        self.halted = current[4].get_bit(DAY_HALT_BIT);
+
+        self.seconds = latched[0];
+        self.minutes = latched[1];
+        self.hours = latched[2];
+        self.days = ((latched[4] as u16 & 0b1) << 8) | latched[3] as u16;
+    }
+}
+
+/// ```
+/// Start time
+///
+/// Finds when a clock which has run for the given time would have started.
+/// Some systems can't go back further than when they booted, in which case
+/// the clock starts from now instead.
+///
+/// Input:
+///     Time the clock has been running, in seconds (u64)
+///
+/// Output:
+///     When the clock started (Instant)
+/// ```
+fn start_time(secs: u64) -> Instant {
+    let now = Instant::now();
+    let elapsed = Duration::from_secs(secs);
+    // The browser's clock is a float, which can go back as far as needed
+    #[cfg(target_arch = "wasm32")]
+    return now - elapsed;
+    #[cfg(not(target_arch = "wasm32"))]
+    return now.checked_sub(elapsed).unwrap_or(now);
 }
@@ -0,0 +1,549 @@
+extern crate wasm_timer;
+use wasm_timer::{SystemTime, UNIX_EPOCH};
+use crate::cpu::{Cpu, IE, KEY1, KEY1_PREPARE_BIT, KEY1_SPEED_BIT, Regs16};
+use crate::cpu::model::Model;
+use crate::cpu::timer::{DIV, TIMA};
+use crate::state::StateError;
+use crate::utils::ModifyBits;
+
+/*
+ * BESS - Best Effort Save State
+ *
+ * A save state format shared between emulators. Memory contents are stored
+ * first, followed by a list of blocks describing the rest of the machine,
+ * and finally a footer pointing at the first block.
+ *
+ * Footer (last 8 bytes of the file)
+ * Offset | Size | Info
+ * -------+------+----------------------------------
+ * 0      | 4    | Offset of the first block
+ * 4      | 4    | Magic, "BESS"
+ *
+ * Each block starts with a four character ID and its length, not
+ * including the header. All values are little-endian. Blocks we don't
+ * understand are skipped, as are any fields we don't support.
+ *
+ * Block | Info
+ * ------+---------------------------------------------------------
+ * NAME  | Name and version of the emulator which made the state
+ * INFO  | Title and global checksum of the game
+ * CORE  | Model, CPU registers, I/O registers and memory locations
+ * MBC   | Register writes which restore the cartridge's banking
+ * RTC   | MBC3 real time clock
+ * END   | Last block, always empty
+ *
+ * CORE
+ * Offset | Size | Info
+ * -------+------+----------------------------------
+ * $00    | 2    | Major version, 1
+ * $02    | 2    | Minor version, 1
+ * $04    | 4    | Model ID
+ * $08    | 12   | PC, AF, BC, DE, HL, SP
+ * $14    | 1    | IME
+ * $15    | 1    | IE
+ * $16    | 1    | Execution state (0: Running, 1: Halted, 2: Stopped)
+ * $17    | 1    | Reserved
+ * $18    | 128  | I/O registers, $FF00-$FF7F
+ * $98    | 56   | Size and offset of WRAM, VRAM, MBC RAM, OAM, HRAM,
+ *        |      | BG palettes and OBJ palettes
+ *
+ * RTC
+ * Offset | Size | Info
+ * -------+------+----------------------------------
+ * $00    | 20   | Current seconds, minutes, hours, days, days high
+ * $14    | 20   | Latched seconds, minutes, hours, days, days high
+ * $28    | 8    | UNIX timestamp when the state was made
+ *
+ * Each RTC register is padded out to four bytes.
+ */
+
+// =============
+// = Constants =
+// =============
+const BESS_MAGIC: &[u8; 4] = b"BESS";
+const FOOTER_SIZE: usize = 8;
+const BLOCK_HEADER_SIZE: usize = 8;
+const BESS_MAJOR: u16 = 1;
+const BESS_MINOR: u16 = 1;
+
+const CORE_SIZE: usize          = 0xD0;
+const CORE_MODEL_OFFSET: usize  = 0x04;
+const CORE_REGS_OFFSET: usize   = 0x08;
+const CORE_IME_OFFSET: usize    = 0x14;
+const CORE_IE_OFFSET: usize     = 0x15;
+const CORE_EXEC_OFFSET: usize   = 0x16;
+const CORE_IO_OFFSET: usize     = 0x18;
+const CORE_MEM_OFFSET: usize    = 0x98;
+const INFO_SIZE: usize          = 0x12;
+const RTC_SIZE: usize           = 0x30;
+const RTC_LATCHED_OFFSET: usize = 0x14;
+const RTC_TIME_OFFSET: usize    = 0x28;
+const RTC_REG_SIZE: usize       = 4;
+const VRAM_BANK_SIZE: usize     = 0x2000;
+
+const EXEC_RUNNING: u8 = 0;
+const EXEC_HALTED: u8 = 1;
+
+const IO_START: u16     = 0xFF00;
+const IO_SIZE: usize    = 0x80;
+const OAM_START: u16    = 0xFE00;
+const OAM_SIZE: usize   = 0xA0;
+const HRAM_START: u16   = 0xFF80;
+const HRAM_SIZE: usize  = 0x7F;
+const TITLE_ADDR: u16   = 0x0134;
+const TITLE_SIZE: usize = 0x10;
+const CHECKSUM_ADDR: u16 = 0x014E;
+
+// I/O registers which need special handling when restored
+const JOYPAD: u16 = 0xFF00;
+const STAT: u16 = 0xFF41;
+const LY: u16   = 0xFF44;
+const DMA: u16  = 0xFF46;
+const HDMA5: u16 = 0xFF55;
+const BCPD: u16 = 0xFF69;
+const OCPD: u16 = 0xFF6B;
+const NR52: u16 = 0xFF26;
+// NRx4 registers, whose top bit triggers the channel
+const TRIGGER_REGS: [u16; 4] = [0xFF14, 0xFF19, 0xFF1E, 0xFF23];
+const TRIGGER_BIT: u8 = 7;
+
+// Order of the memory regions in CORE
+#[derive(Copy, Clone)]
+enum Region {
+    Wram,
+    Vram,
+    MbcRam,
+    Oam,
+    Hram,
+    BgPal,
+    ObjPal,
+}
+const REGIONS: [Region; 7] = [
+    Region::Wram,
+    Region::Vram,
+    Region::MbcRam,
+    Region::Oam,
+    Region::Hram,
+    Region::BgPal,
+    Region::ObjPal,
+];
+
+impl Cpu {
+    /// ```
+    /// Export BESS
+    ///
+    /// Takes a snapshot of the machine in the BESS format, which other emulators can load
+    ///
+    /// Output:
+    ///     BESS save state data (Vec<u8>)
+    /// ```
+    pub fn export_bess(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+
+        // Memory regions go first, with their locations recorded in CORE
+        let mut regions = Vec::new();
+        for region in REGIONS.iter() {
+            let contents = self.get_region(*region);
+            regions.push((contents.len() as u32, data.len() as u32));
+            data.extend_from_slice(&contents);
+        }
+        let first_block = data.len() as u32;
+
+        let name = format!("Songbird v{}", env!("CARGO_PKG_VERSION"));
+        write_block(&mut data, b"NAME", name.as_bytes());
+
+        write_block(&mut data, b"INFO", &self.get_header_info());
+
+        let mut core = Vec::with_capacity(CORE_SIZE);
+        core.extend_from_slice(&BESS_MAJOR.to_le_bytes());
+        core.extend_from_slice(&BESS_MINOR.to_le_bytes());
+        core.extend_from_slice(get_model_id(self.model));
+        for reg in [
+            self.pc,
+            self.get_reg_16(Regs16::AF),
+            self.get_reg_16(Regs16::BC),
+            self.get_reg_16(Regs16::DE),
+            self.get_reg_16(Regs16::HL),
+            self.sp,
+        ].iter() {
+            core.extend_from_slice(&reg.to_le_bytes());
+        }
+        core.push(self.interrupt_enabled as u8);
+        core.push(self.read_ram(IE));
+        core.push(if self.halted { EXEC_HALTED } else { EXEC_RUNNING });
+        core.push(0);
+        core.extend((0..IO_SIZE as u16).map(|i| self.read_ram(IO_START + i)));
+        for (size, offset) in regions.iter() {
+            core.extend_from_slice(&size.to_le_bytes());
+            core.extend_from_slice(&offset.to_le_bytes());
+        }
+        write_block(&mut data, b"CORE", &core);
+
+        let mut mbc = Vec::new();
+        for (addr, val) in self.bus.get_mbc_writes().iter() {
+            mbc.extend_from_slice(&addr.to_le_bytes());
+            mbc.push(*val);
+        }
+        write_block(&mut data, b"MBC ", &mbc);
+
+        if self.bus.has_rtc() {
+            let (current, latched) = self.bus.get_rtc_regs();
+            let mut rtc = Vec::with_capacity(RTC_SIZE);
+            for reg in current.iter().chain(latched.iter()) {
+                rtc.extend_from_slice(&(*reg as u32).to_le_bytes());
+            }
+            rtc.extend_from_slice(&get_timestamp().to_le_bytes());
+            write_block(&mut data, b"RTC ", &rtc);
+        }
+
+        write_block(&mut data, b"END ", &[]);
+
+        data.extend_from_slice(&first_block.to_le_bytes());
+        data.extend_from_slice(BESS_MAGIC);
+        data
+    }
+
+    /// ```
+    /// Import BESS
+    ///
+    /// Restores the machine from a BESS save state, possibly made by another emulator.
+    /// The same game must already be loaded. Anything the state doesn't cover is left as is.
+    /// Rewind history from before the import is discarded.
+    ///
+    /// Input:
+    ///     BESS save state data (&[u8])
+    ///
+    /// Output:
+    ///     Whether the state was loaded, or why not (Result<(), StateError>)
+    /// ```
+    pub fn import_bess(&mut self, data: &[u8]) -> Result<(), StateError> {
+        self.replace_state(|gb| gb.restore_bess(data))
+    }
+}
+
+// Private functions
+impl Cpu {
+    /// ```
+    /// Restore BESS
+    ///
+    /// Loads a BESS save state into the machine, leaving it untouched if the state can't be read
+    ///
+    /// Input:
+    ///     BESS save state data (&[u8])
+    ///
+    /// Output:
+    ///     Whether the state was loaded, or why not (Result<(), StateError>)
+    /// ```
+    fn restore_bess(&mut self, data: &[u8]) -> Result<(), StateError> {
+        if data.len() < FOOTER_SIZE || &data[(data.len() - 4)..] != BESS_MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let footer = data.len() - FOOTER_SIZE;
+        let mut pos = read_u32(data, footer)? as usize;
+
+        // Find all the blocks before changing anything, so a bad state leaves the machine untouched
+        let mut core = None;
+        let mut mbc = None;
+        let mut rtc = None;
+        loop {
+            // Offsets come from the file, so they're checked before being added to
+            let len_pos = pos.checked_add(4).ok_or(StateError::Truncated)?;
+            let id = data.get(pos..len_pos).ok_or(StateError::Truncated)?;
+            let len = read_u32(data, len_pos)? as usize;
+            let start = pos.checked_add(BLOCK_HEADER_SIZE).ok_or(StateError::Truncated)?;
+            let end = start.checked_add(len).ok_or(StateError::Truncated)?;
+            let block = data.get(start..end).ok_or(StateError::Truncated)?;
+
+            match id {
+                b"END " => { break; },
+                b"INFO" if block.get(..INFO_SIZE) != Some(&self.get_header_info()[..]) => {
+                    return Err(StateError::WrongRom);
+                },
+                b"CORE" => { core = Some(block); },
+                b"MBC " => { mbc = Some(block); },
+                b"RTC " => { rtc = Some(block); },
+                _ => {
+                    // Unknown blocks are skipped
+                }
+            }
+            pos = end;
+        }
+
+        let core = core.ok_or(StateError::Invalid)?;
+        if core.len() < CORE_SIZE {
+            return Err(StateError::Truncated);
+        }
+        let major = read_u16(core, 0)?;
+        if major != BESS_MAJOR {
+            return Err(StateError::Version(major as u32));
+        }
+        // Only the model family needs to match, the game can't tell revisions apart
+        let is_cgb = core[CORE_MODEL_OFFSET] == b'C';
+        if is_cgb != self.model.is_cgb() {
+            return Err(StateError::WrongModel);
+        }
+
+        // Check all regions are present before loading any of them
+        let mut regions = Vec::new();
+        for i in 0..REGIONS.len() {
+            let size = read_u32(core, CORE_MEM_OFFSET + 8 * i)? as usize;
+            let offset = read_u32(core, CORE_MEM_OFFSET + 8 * i + 4)? as usize;
+            let end = offset.checked_add(size).ok_or(StateError::Truncated)?;
+            regions.push(data.get(offset..end).ok_or(StateError::Truncated)?);
+        }
+
+        let regs = CORE_REGS_OFFSET;
+        self.pc = read_u16(core, regs)?;
+        self.set_reg_16(Regs16::AF, read_u16(core, regs + 2)?);
+        self.set_reg_16(Regs16::BC, read_u16(core, regs + 4)?);
+        self.set_reg_16(Regs16::DE, read_u16(core, regs + 6)?);
+        self.set_reg_16(Regs16::HL, read_u16(core, regs + 8)?);
+        self.sp = read_u16(core, regs + 10)?;
+        self.interrupt_enabled = core[CORE_IME_OFFSET] != 0;
+        self.halted = core[CORE_EXEC_OFFSET] == EXEC_HALTED;
+        self.dma_stall = 0;
+        self.half_cycle = 0;
+
+        if let Some(mbc) = mbc {
+            self.bus.reset_banking();
+            for write in mbc.chunks_exact(3) {
+                let addr = u16::from_le_bytes([write[0], write[1]]);
+                self.bus.write_ram(addr, write[2]);
+            }
+        }
+
+        self.set_io_regs(&core[CORE_IO_OFFSET..(CORE_IO_OFFSET + IO_SIZE)]);
+        self.write_ram(IE, core[CORE_IE_OFFSET]);
+
+        for (region, contents) in REGIONS.iter().zip(regions.iter()) {
+            self.set_region(*region, contents);
+        }
+
+        if let Some(rtc) = rtc {
+            if self.bus.has_rtc() && rtc.len() >= RTC_SIZE {
+                let mut current = [0; 5];
+                let mut latched = [0; 5];
+                for i in 0..5 {
+                    current[i] = rtc[i * RTC_REG_SIZE];
+                    latched[i] = rtc[RTC_LATCHED_OFFSET + i * RTC_REG_SIZE];
+                }
+                let timestamp = u64::from_le_bytes(read_array(rtc, RTC_TIME_OFFSET)?);
+                let passed = get_timestamp().saturating_sub(timestamp);
+                self.bus.set_rtc_regs(current, latched, passed);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// ```
+    /// Get header info
+    ///
+    /// Gets the game's title and global checksum, used to identify it in the INFO block
+    ///
+    /// Output:
+    ///     Title and checksum bytes ([u8])
+    /// ```
+    fn get_header_info(&self) -> [u8; INFO_SIZE] {
+        let mut info = [0; INFO_SIZE];
+        for (i, byte) in info.iter_mut().take(TITLE_SIZE).enumerate() {
+            *byte = self.bus.read_ram(TITLE_ADDR + i as u16);
+        }
+        info[TITLE_SIZE] = self.bus.read_ram(CHECKSUM_ADDR);
+        info[TITLE_SIZE + 1] = self.bus.read_ram(CHECKSUM_ADDR + 1);
+        info
+    }
+
+    /// ```
+    /// Get region
+    ///
+    /// Copies out one of the memory regions stored in a BESS state
+    ///
+    /// Input:
+    ///     Memory region (Region)
+    ///
+    /// Output:
+    ///     Region contents (Vec<u8>)
+    /// ```
+    fn get_region(&self, region: Region) -> Vec<u8> {
+        match region {
+            Region::Wram => { self.bus.get_wram() },
+            Region::Vram => {
+                let mut vram = self.bus.get_vram_bank(0).to_vec();
+                if self.bus.is_cgb() {
+                    vram.extend_from_slice(self.bus.get_vram_bank(1));
+                }
+                vram
+            },
+            Region::MbcRam => { self.bus.get_ext_ram().to_vec() },
+            Region::Oam => { (0..OAM_SIZE as u16).map(|i| self.bus.read_ram(OAM_START + i)).collect() },
+            Region::Hram => { (0..HRAM_SIZE as u16).map(|i| self.bus.read_ram(HRAM_START + i)).collect() },
+            Region::BgPal if self.bus.is_cgb() => { self.bus.get_pal_ram().0.to_vec() },
+            Region::ObjPal if self.bus.is_cgb() => { self.bus.get_pal_ram().1.to_vec() },
+            Region::BgPal | Region::ObjPal => { Vec::new() }
+        }
+    }
+
+    /// ```
+    /// Set region
+    ///
+    /// Restores one of the memory regions stored in a BESS state
+    ///
+    /// Inputs:
+    ///     Memory region (Region)
+    ///     Region contents (&[u8])
+    /// ```
+    fn set_region(&mut self, region: Region, contents: &[u8]) {
+        match region {
+            Region::Wram => { self.bus.set_wram(contents) },
+            Region::Vram => {
+                let (bank_0, bank_1) = contents.split_at(contents.len().min(VRAM_BANK_SIZE));
+                self.bus.set_vram_bank(0, bank_0);
+                if self.bus.is_cgb() {
+                    self.bus.set_vram_bank(1, bank_1);
+                }
+            },
+            Region::MbcRam => {
+                // Cartridge RAM is only restored if it's the size we expect
+                if contents.len() == self.bus.get_ext_ram().len() {
+                    self.bus.write_ext_ram(contents);
+                }
+            },
+            Region::Oam => {
+                for (i, &val) in contents.iter().take(OAM_SIZE).enumerate() {
+                    self.bus.write_ram(OAM_START + i as u16, val);
+                }
+            },
+            Region::Hram => {
+                for (i, &val) in contents.iter().take(HRAM_SIZE).enumerate() {
+                    self.bus.write_ram(HRAM_START + i as u16, val);
+                }
+            },
+            Region::BgPal => {
+                let obj = self.bus.get_pal_ram().1.to_vec();
+                self.bus.set_pal_ram(contents, &obj);
+            },
+            Region::ObjPal => {
+                let bg = self.bus.get_pal_ram().0.to_vec();
+                self.bus.set_pal_ram(&bg, contents);
+            }
+        }
+    }
+
+    /// ```
+    /// Set I/O registers
+    ///
+    /// Restores the I/O registers, without starting any DMA transfers or sound channels
+    ///
+    /// Input:
+    ///     Registers $FF00-$FF7F (&[u8])
+    /// ```
+    fn set_io_regs(&mut self, io: &[u8]) {
+        let get = |addr: u16| io[(addr - IO_START) as usize];
+
+        // Sound must be powered on before its other registers can be written
+        self.write_ram(NR52, get(NR52));
+
+        for addr in IO_START..(IO_START + IO_SIZE as u16) {
+            let val = get(addr);
+            match addr {
+                NR52 | DMA | HDMA5 | BCPD | OCPD | STAT | LY => {
+                    // Handled separately, or would trigger a transfer
+                },
+                JOYPAD => {
+                    // Reading doesn't give back which keys were selected, so leave it to the game
+                },
+                DIV => {
+                    self.timer.set_div_counter((val as u16) << 8);
+                },
+                TIMA => {
+                    self.timer.set_tima(val);
+                },
+                KEY1 if self.bus.is_cgb() => {
+                    self.double_speed = val.get_bit(KEY1_SPEED_BIT);
+                    self.speed_switch = val.get_bit(KEY1_PREPARE_BIT);
+                },
+                _ if TRIGGER_REGS.contains(&addr) => {
+                    let mut val = val;
+                    val.clear_bit(TRIGGER_BIT);
+                    self.write_ram(addr, val);
+                },
+                _ => {
+                    self.write_ram(addr, val);
+                }
+            }
+        }
+
+        // Move the PPU to the saved scanline and mode
+        let (line, mode) = (get(LY), get(STAT) & 0b11);
+        self.write_ram(STAT, get(STAT));
+        self.bus.set_scanline(line);
+        self.bus.set_status_reg(mode);
+        self.clock.set_position(line, mode);
+    }
+}
+
+/// ```
+/// Write block
+///
+/// Appends a BESS block, with its header
+///
+/// Inputs:
+///     State data (&mut Vec<u8>)
+///     Block ID (&[u8; 4])
+///     Block contents (&[u8])
+/// ```
+fn write_block(data: &mut Vec<u8>, id: &[u8; 4], block: &[u8]) {
+    data.extend_from_slice(id);
+    data.extend_from_slice(&(block.len() as u32).to_le_bytes());
+    data.extend_from_slice(block);
+}
+
+/// ```
+/// Get model ID
+///
+/// The four character BESS name for each hardware model
+///
+/// Input:
+///     Hardware model (Model)
+///
+/// Output:
+///     Model ID (&[u8; 4])
+/// ```
+fn get_model_id(model: Model) -> &'static [u8; 4] {
+    match model {
+        Model::DMG0 => { b"GD0 " },
+        Model::DMG =>  { b"GDB " },
+        Model::MGB =>  { b"GM  " },
+        Model::SGB =>  { b"SN  " },
+        Model::SGB2 => { b"S2  " },
+        Model::CGB =>  { b"CCE " },
+        Model::AGB =>  { b"CAA " },
+    }
+}
+
+/// ```
+/// Get timestamp
+///
+/// Current time as a UNIX timestamp, for the RTC block
+///
+/// Output:
+///     Seconds since the UNIX epoch (u64)
+/// ```
+fn get_timestamp() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|t| t.as_secs()).unwrap_or(0)
+}
+
+fn read_array<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], StateError> {
+    let mut buf = [0; N];
+    buf.copy_from_slice(data.get(offset..(offset + N)).ok_or(StateError::Truncated)?);
+    Ok(buf)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, StateError> {
+    Ok(u16::from_le_bytes(read_array(data, offset)?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, StateError> {
+    Ok(u32::from_le_bytes(read_array(data, offset)?))
+}
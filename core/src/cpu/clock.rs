@@ -1,8 +1,12 @@
+use crate::state::{StateError, StateReader, StateWriter};
+
 // Constants
-const HBLANK_LEN: usize = 204;
-const VBLANK_LEN: usize = 456;
-const OAM_READ_LEN: usize = 80;
-const VRAM_READ_LEN: usize = 172;
+// Lengths are in M-cycles, which is what the CPU reports, so 1/4 of the 80/172/204/456 dots
+// the hardware spends in each mode
+const HBLANK_LEN: usize = 51;
+const VBLANK_LEN: usize = 114;
+const OAM_READ_LEN: usize = 20;
+const VRAM_READ_LEN: usize = 43;
 
 const VBLANK_LINE_START: u8 = 143;
 const VBLANK_LINE_END: u8 = VBLANK_LINE_START + 10;
@@ -14,7 +18,7 @@ pub enum ClockResults {
     RenderFrame
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub enum ModeTypes {
     HBLANK,
     VBLANK,
@@ -22,6 +26,7 @@ pub enum ModeTypes {
     VRAMReadMode
 }
 
+#[derive(Clone)]
 pub struct Clock {
     cycles: usize,
     line: u8,
@@ -135,6 +140,26 @@ impl Clock {
         }
     }
 
+    /// ```
+    /// Set position
+    ///
+    /// Moves the clock to the start of the given scanline and mode
+    ///
+    /// Inputs:
+    ///     Scanline (u8)
+    ///     Mode, as an int (u8)
+    /// ```
+    pub fn set_position(&mut self, line: u8, mode: u8) {
+        self.cycles = 0;
+        self.line = line;
+        self.mode = match mode & 0b11 {
+            0 => { ModeTypes::HBLANK },
+            1 => { ModeTypes::VBLANK },
+            2 => { ModeTypes::OAMReadMode },
+            _ => { ModeTypes::VRAMReadMode }
+        };
+    }
+
     /// ```
     /// Reset Line
     ///
@@ -156,4 +181,42 @@ impl Clock {
     pub fn is_vblank_interrupt(&self) -> bool {
         self.mode == ModeTypes::VBLANK
     }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes the clock's position in the frame to a save state
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u32(self.cycles as u32);
+        state.write_u8(self.line);
+        state.write_u8(self.get_mode());
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the clock from a save state
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.cycles = state.read_u32()? as usize;
+        self.line = state.read_u8()?;
+        self.mode = match state.read_u8()? {
+            0 => { ModeTypes::HBLANK },
+            1 => { ModeTypes::VBLANK },
+            2 => { ModeTypes::OAMReadMode },
+            3 => { ModeTypes::VRAMReadMode },
+            _ => { return Err(StateError::Invalid) }
+        };
+        Ok(())
+    }
 }
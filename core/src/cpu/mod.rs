@@ -1,11 +1,19 @@
+pub mod bess;
 pub mod clock;
+pub mod model;
 pub mod opcodes;
 pub mod timer;
 
 use clock::{Clock, ClockResults};
+use model::Model;
 use crate::bus::Bus;
+use crate::debug::access::{AccessBreak, AccessBreaks, AccessHit, AccessType};
 use crate::io::Buttons;
+use crate::memory::MemoryInterface;
 use crate::ppu::palette::Palettes;
+use crate::rewind::Rewind;
+use crate::serial::SerialDevice;
+use crate::state::{StateError, StateReader, StateWriter};
 use crate::utils::*;
 use timer::*;
 
@@ -14,6 +22,23 @@ use timer::*;
 // =============
 const IF: u16 = 0xFF0F; // Interrupt Flag
 const IE: u16 = 0xFFFF; // Interrupt Enable
+const KEY1: u16 = 0xFF4D; // CGB speed switch
+
+/*
+ * KEY1 - CGB Speed Switch
+ *
+ * Bit | Info
+ * ----+-------------------------------
+ * 7   | Current speed (0: normal, 1: double) (read only)
+ * 0   | Prepare speed switch
+ *
+ * Writing 1 to bit 0 and then executing STOP toggles double speed mode.
+ * In double speed, the CPU and timer run twice as fast, while the PPU and APU don't.
+ */
+const KEY1_SPEED_BIT: u8 = 7;
+const KEY1_PREPARE_BIT: u8 = 0;
+// Time the CPU is halted for each 16 byte VRAM DMA block, in normal speed M-cycles
+const HDMA_BLOCK_CYCLES: u16 = 8;
 const INTER_PRIORITIES: [Interrupts; 5] = [
     Interrupts::VBLANK,
     Interrupts::LCD_STAT,
@@ -60,7 +85,7 @@ pub enum Interrupts {
     JOYPAD
 }
 
-pub struct Cpu {
+pub struct Cpu<B: MemoryInterface = Bus> {
     pc: u16,
     sp: u16,
     a: u8,
@@ -75,8 +100,17 @@ pub struct Cpu {
     timer: Timer,
     interrupt_enabled: bool,
     halted: bool,
-    bus: Bus,
+    bus: B,
     dirty_battery_ram: bool,
+    total_cycles: u64,
+    double_speed: bool,
+    speed_switch: bool,
+    half_cycle: u8,
+    dma_stall: u16,
+    model: Model,
+    rewind: Option<Rewind>,
+    // Only present while any are set, to keep memory accesses fast
+    access_breaks: Option<Box<AccessBreaks>>,
 }
 
 impl Default for Cpu {
@@ -87,25 +121,33 @@ impl Default for Cpu {
 
 impl Cpu {
     pub fn new() -> Cpu {
+        Cpu::with_model(Model::DMG)
+    }
+
+    /// ```
+    /// With model
+    ///
+    /// Creates a CPU in the state the given hardware model's boot ROM leaves it
+    ///
+    /// Input:
+    ///     Hardware model to emulate (Model)
+    ///
+    /// Output:
+    ///     Initialized CPU (Cpu)
+    /// ```
+    pub fn with_model(model: Model) -> Cpu {
         // Magic values from pandocs
-        let mut new_cpu = Cpu {
-            pc: 0x100,
-            sp: 0xFFFE,
-            a: 0x01,
-            b: 0x00,
-            c: 0x13,
-            d: 0x00,
-            e: 0xD8,
-            f: 0xB0,
-            h: 0x01,
-            l: 0x4D,
-            clock: Clock::new(),
-            timer: Timer::new(),
-            interrupt_enabled: false,
-            halted: false,
-            bus: Bus::new(),
-            dirty_battery_ram: false,
-        };
+        let mut new_cpu = Cpu::with_bus(Bus::new());
+        new_cpu.model = model;
+        new_cpu.pc = 0x100;
+        new_cpu.sp = 0xFFFE;
+
+        let [af, bc, de, hl] = model.get_regs();
+        new_cpu.set_reg_16(Regs16::AF, af);
+        new_cpu.set_reg_16(Regs16::BC, bc);
+        new_cpu.set_reg_16(Regs16::DE, de);
+        new_cpu.set_reg_16(Regs16::HL, hl);
+        new_cpu.timer.set_div_counter(model.get_div_counter());
 
         // Magic values for RAM initialization
         new_cpu.write_ram(0xFF05, 0x00);
@@ -128,7 +170,7 @@ impl Cpu {
         new_cpu.write_ram(0xFF23, 0xBF);
         new_cpu.write_ram(0xFF24, 0x77);
         new_cpu.write_ram(0xFF25, 0xF3);
-        new_cpu.write_ram(0xFF26, 0xF1); // $F0 for SGB
+        new_cpu.write_ram(0xFF26, if model.is_sgb() { 0xF0 } else { 0xF1 });
         new_cpu.write_ram(0xFF40, 0x91);
         new_cpu.write_ram(0xFF42, 0x00);
         new_cpu.write_ram(0xFF43, 0x00);
@@ -158,8 +200,21 @@ impl Cpu {
         if let Some(inter_type) = self.interrupt_check() {
             self.trigger_interrupt(inter_type);
         }
-        // If halted, simply continue counting without executing opcodes
-        let cycles = if self.halted { 1 } else { opcodes::execute(self) };
+        // If halted, or waiting for VRAM DMA, simply continue counting without executing opcodes
+        let cycles = if self.dma_stall > 0 {
+            self.dma_stall -= 1;
+            1
+        } else if self.halted {
+            1
+        } else {
+            self.run_instruction()
+        };
+        self.add_dma_stall();
+
+        // Timer runs at the CPU's speed, everything else only sees normal speed cycles
+        let timer_cycles = cycles;
+        let cycles = self.scale_cycles(cycles);
+        self.total_cycles += cycles as u64;
 
         let clock_result = self.clock.clock_step(cycles);
         let lcd_interrupt = self.bus.set_scanline(self.clock.get_scanline());
@@ -169,11 +224,19 @@ impl Cpu {
         self.bus.set_status_reg(self.clock.get_mode());
 
         // Tick timer
-        let timer_interrupt = self.timer.tick(cycles);
+        let timer_interrupt = self.timer.tick(timer_cycles);
         if timer_interrupt {
             self.enable_interrupt(Interrupts::TIMER);
         }
 
+        // Serial clock also runs at the CPU's speed
+        if self.bus.tick_serial(timer_cycles) {
+            self.enable_interrupt(Interrupts::SERIAL);
+        }
+
+        // Tick audio
+        self.bus.tick_apu(cycles);
+
         match clock_result {
             ClockResults::RenderFrame => {
                 // Render the final scanline before rendering frame
@@ -181,9 +244,12 @@ impl Cpu {
                 // If time to render frame, then VBLANK interrupt is toggled
                 self.enable_interrupt(Interrupts::VBLANK);
                 draw_time = true;
+                self.record_rewind();
             },
             ClockResults::RenderScanline => {
                 self.bus.render_scanline();
+                self.bus.hblank_dma();
+                self.add_dma_stall();
             },
             _ => {
                 // Do nothing
@@ -226,21 +292,6 @@ impl Cpu {
         self.bus.get_title()
     }
 
-    /// ```
-    /// Fetch
-    ///
-    /// Fetches the byte specified by the PC, increments PC by one
-    ///
-    /// Output:
-    ///     Byte at the current PC (u8)
-    /// ```
-    pub fn fetch(&mut self) -> u8 {
-        let pc = self.get_pc();
-        let val = self.read_ram(pc);
-        self.pc += 1;
-        val
-    }
-
     /// ```
     /// Get external RAM
     ///
@@ -280,13 +331,213 @@ impl Cpu {
     /// ```
     /// Load game
     ///
-    /// Wrapper for the load game functionality
+    /// Wrapper for the load game functionality. Rewind history from any previous game is discarded.
     ///
     /// Input:
     ///     Game data (&[u8])
     /// ```
     pub fn load_game(&mut self, rom: &[u8]) {
-        self.bus.load_game(rom);
+        let cgb = self.model.is_cgb();
+        // Loading a game can't fail, so there's nothing to report
+        let _ = self.replace_state(|gb| {
+            gb.bus.load_game(rom, cgb);
+            Ok(())
+        });
+    }
+
+    /// ```
+    /// Load boot ROM
+    ///
+    /// Runs the given boot ROM at power on, rather than skipping straight to the game.
+    /// The boot ROM sets up its own state, so registers start cleared.
+    /// Must be called before loading the game.
+    ///
+    /// Input:
+    ///     DMG or CGB boot ROM data (&[u8])
+    /// ```
+    pub fn load_boot_rom(&mut self, boot_rom: &[u8]) {
+        self.bus.load_boot_rom(boot_rom);
+
+        self.pc = 0x0000;
+        self.sp = 0x0000;
+        self.set_reg_16(Regs16::AF, 0x0000);
+        self.set_reg_16(Regs16::BC, 0x0000);
+        self.set_reg_16(Regs16::DE, 0x0000);
+        self.set_reg_16(Regs16::HL, 0x0000);
+        self.timer.set_div_counter(0);
+        // The boot ROM turns on the screen and sound itself
+        self.write_ram(0xFF40, 0x00);
+        self.write_ram(0xFF26, 0x00);
+    }
+
+    /// ```
+    /// Save state
+    ///
+    /// Takes a snapshot of the entire machine
+    ///
+    /// Output:
+    ///     Save state data (Vec<u8>)
+    /// ```
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new(self.bus.get_rom_crc());
+
+        state.write_u16(self.pc);
+        state.write_u16(self.sp);
+        for reg in [self.a, self.b, self.c, self.d, self.e, self.f, self.h, self.l].iter() {
+            state.write_u8(*reg);
+        }
+        state.write_bool(self.interrupt_enabled);
+        state.write_bool(self.halted);
+        state.write_bool(self.double_speed);
+        state.write_bool(self.speed_switch);
+        state.write_u8(self.half_cycle);
+        state.write_u16(self.dma_stall);
+        state.write_u8(self.model.get_id());
+
+        self.clock.save_state(&mut state);
+        self.timer.save_state(&mut state);
+        self.bus.save_state(&mut state);
+
+        state.finish()
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the entire machine from a snapshot. The same game must already be loaded.
+    /// The machine is left untouched if the snapshot can't be read.
+    /// Rewind history from before the load is discarded.
+    ///
+    /// Input:
+    ///     Save state data (&[u8])
+    ///
+    /// Output:
+    ///     Whether the state was loaded, or why not (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        self.replace_state(|gb| gb.restore_state(data))
+    }
+
+    /// ```
+    /// Connect serial
+    ///
+    /// Plugs a device, such as a link cable or printer, into the serial port.
+    /// Rewinding is turned off while a link cable is connected, as the other side can't follow.
+    ///
+    /// Input:
+    ///     Device to connect (Box<dyn SerialDevice + Send>)
+    /// ```
+    pub fn connect_serial(&mut self, device: Box<dyn SerialDevice + Send>) {
+        self.bus.connect_serial(device);
+        if self.bus.is_serial_lockstep() {
+            self.clear_rewind();
+        }
+    }
+
+    /// ```
+    /// Disconnect serial
+    ///
+    /// Unplugs the device from the serial port
+    ///
+    /// Output:
+    ///     Device which was connected, if any (Option<Box<dyn SerialDevice + Send>>)
+    /// ```
+    pub fn disconnect_serial(&mut self) -> Option<Box<dyn SerialDevice + Send>> {
+        self.bus.disconnect_serial()
+    }
+
+    /// ```
+    /// Set serial capture
+    ///
+    /// Sets whether to keep every byte the game sends over the serial port, such as test ROM results
+    ///
+    /// Input:
+    ///     Whether to capture sent bytes (bool)
+    /// ```
+    pub fn set_serial_capture(&mut self, enabled: bool) {
+        self.bus.set_serial_capture(enabled);
+    }
+
+    /// ```
+    /// Take serial output
+    ///
+    /// Removes the bytes captured from the serial port so far, once capture is enabled
+    ///
+    /// Output:
+    ///     Bytes sent since last taken (Vec<u8>)
+    /// ```
+    pub fn take_serial_output(&mut self) -> Vec<u8> {
+        self.bus.take_serial_output()
+    }
+
+    /// ```
+    /// Enable rewind
+    ///
+    /// Starts keeping snapshots of recent play, so it can be stepped backwards
+    ///
+    /// Inputs:
+    ///     Maximum memory to use for snapshots, in bytes (usize)
+    ///     Number of frames between snapshots (u32)
+    /// ```
+    pub fn enable_rewind(&mut self, budget: usize, interval: u32) {
+        self.rewind = Some(Rewind::new(budget, interval));
+    }
+
+    /// ```
+    /// Rewind
+    ///
+    /// Steps back to the most recent snapshot, removing it from the rewind buffer.
+    /// Does nothing while a link cable is connected.
+    ///
+    /// Output:
+    ///     Whether there was a snapshot to go back to (bool)
+    /// ```
+    pub fn rewind(&mut self) -> bool {
+        if self.bus.is_serial_lockstep() {
+            return false;
+        }
+
+        let state = match self.rewind.as_mut().and_then(|r| r.pop()) {
+            Some(state) => { state },
+            None => { return false; }
+        };
+        self.restore_state(&state).is_ok()
+    }
+
+    /// ```
+    /// Get model
+    ///
+    /// Returns which hardware model is being emulated
+    ///
+    /// Output:
+    ///     Hardware model (Model)
+    /// ```
+    pub fn get_model(&self) -> Model {
+        self.model
+    }
+
+    /// ```
+    /// Get ROM bank
+    ///
+    /// Returns which ROM bank is mapped to $4000-$7FFF, for debugging
+    ///
+    /// Output:
+    ///     ROM bank number (u16)
+    /// ```
+    pub fn get_rom_bank(&self) -> u16 {
+        self.bus.get_rom_bank()
+    }
+
+    /// ```
+    /// Get RAM bank
+    ///
+    /// Returns which external RAM bank is mapped to $A000-$BFFF, for debugging
+    ///
+    /// Output:
+    ///     RAM bank number (u8)
+    /// ```
+    pub fn get_ram_bank(&self) -> u8 {
+        self.bus.get_ram_bank()
     }
 
     pub fn set_sys_pal(&mut self, pal: Palettes) {
@@ -319,6 +570,267 @@ impl Cpu {
         self.bus.write_ext_ram(data);
     }
 
+    /// ```
+    /// Set sample rate
+    ///
+    /// Sets the rate audio samples are generated at, to match the host's audio device
+    ///
+    /// Input:
+    ///     Sample rate, in Hz (u32)
+    /// ```
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.bus.set_sample_rate(rate);
+    }
+
+    /// ```
+    /// Set audio enabled
+    ///
+    /// Turns generation of audio samples on or off. Hosts which never drain
+    /// audio, such as test runners, should turn it off to save time.
+    ///
+    /// Input:
+    ///     Whether to generate samples (bool)
+    /// ```
+    pub fn set_audio_enabled(&mut self, enabled: bool) {
+        self.bus.set_audio_enabled(enabled);
+    }
+
+    /// ```
+    /// Drain audio
+    ///
+    /// Moves all available audio into the given buffer, as interleaved
+    /// left/right pairs. Any samples that don't fit are kept for the next call.
+    ///
+    /// Input:
+    ///     Output buffer (&mut [i16])
+    ///
+    /// Output:
+    ///     Number of values written, two per stereo frame (usize)
+    /// ```
+    pub fn drain_audio(&mut self, out: &mut [i16]) -> usize {
+        self.bus.drain_audio(out)
+    }
+
+    /// ```
+    /// Audio samples available
+    ///
+    /// Number of stereo sample frames waiting to be drained
+    ///
+    /// Output:
+    ///     Number of frames (usize)
+    /// ```
+    pub fn audio_samples_avail(&self) -> usize {
+        self.bus.audio_samples_avail()
+    }
+}
+
+impl<B: MemoryInterface> Cpu<B> {
+    /// ```
+    /// With bus
+    ///
+    /// Creates a CPU connected to the given memory, with every register cleared
+    ///
+    /// Input:
+    ///     Memory for the CPU to use (B)
+    ///
+    /// Output:
+    ///     CPU (Cpu<B>)
+    /// ```
+    pub fn with_bus(bus: B) -> Cpu<B> {
+        Cpu {
+            pc: 0x0000,
+            sp: 0x0000,
+            a: 0x00,
+            b: 0x00,
+            c: 0x00,
+            d: 0x00,
+            e: 0x00,
+            f: 0x00,
+            h: 0x00,
+            l: 0x00,
+            clock: Clock::new(),
+            timer: Timer::new(),
+            interrupt_enabled: false,
+            halted: false,
+            bus,
+            dirty_battery_ram: false,
+            total_cycles: 0,
+            double_speed: false,
+            speed_switch: false,
+            half_cycle: 0,
+            dma_stall: 0,
+            model: Model::DMG,
+            rewind: None,
+            access_breaks: None,
+        }
+    }
+
+    /// ```
+    /// Get bus
+    ///
+    /// Returns the memory the CPU is connected to
+    ///
+    /// Output:
+    ///     Memory (&B)
+    /// ```
+    pub fn get_bus(&self) -> &B {
+        &self.bus
+    }
+
+    /// ```
+    /// Get bus mutably
+    ///
+    /// Returns the memory the CPU is connected to, for changing it directly
+    ///
+    /// Output:
+    ///     Memory (&mut B)
+    /// ```
+    pub fn get_bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    /// ```
+    /// Get cycles
+    ///
+    /// Returns the total number of M-cycles emulated since power on
+    ///
+    /// Output:
+    ///     Cycle count (u64)
+    /// ```
+    pub fn get_cycles(&self) -> u64 {
+        self.total_cycles
+    }
+
+    /// ```
+    /// Is double speed
+    ///
+    /// Returns whether the CGB is running in double speed mode
+    ///
+    /// Output:
+    ///     Whether in double speed mode (bool)
+    /// ```
+    pub fn is_double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    /// ```
+    /// Is halted
+    ///
+    /// Returns whether the CPU is halted, waiting for an interrupt
+    ///
+    /// Output:
+    ///     Whether halted (bool)
+    /// ```
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// ```
+    /// Get IME
+    ///
+    /// Returns whether the interrupt master enable flag is set
+    ///
+    /// Output:
+    ///     Whether interrupts are enabled (bool)
+    /// ```
+    pub fn get_ime(&self) -> bool {
+        self.interrupt_enabled
+    }
+
+    /// ```
+    /// Set IME
+    ///
+    /// Sets the interrupt master enable flag, as EI and DI do
+    ///
+    /// Input:
+    ///     Whether interrupts are enabled (bool)
+    /// ```
+    pub fn set_ime(&mut self, enabled: bool) {
+        self.interrupt_enabled = enabled
+    }
+
+    /// ```
+    /// Step
+    ///
+    /// Services any pending interrupt, then executes one instruction.
+    /// Unlike tick, nothing else in the system is clocked, which suits memory other than the Game Boy's bus.
+    ///
+    /// Output:
+    ///     M-cycles taken, 1 if halted (u8)
+    /// ```
+    pub fn step(&mut self) -> u8 {
+        if let Some(inter_type) = self.interrupt_check() {
+            self.trigger_interrupt(inter_type);
+        }
+
+        let cycles = if self.halted {
+            1
+        } else {
+            self.run_instruction()
+        };
+        self.total_cycles += cycles as u64;
+        cycles
+    }
+
+    /// ```
+    /// Set access breakpoints
+    ///
+    /// Replaces the memory access breakpoints checked as instructions run
+    ///
+    /// Input:
+    ///     Access breakpoints, empty to stop checking (Vec<AccessBreak>)
+    /// ```
+    pub fn set_access_breaks(&mut self, breaks: Vec<AccessBreak>) {
+        self.access_breaks = if breaks.is_empty() {
+            None
+        } else {
+            Some(Box::new(AccessBreaks::new(breaks)))
+        };
+    }
+
+    /// ```
+    /// Take access hits
+    ///
+    /// Returns every access breakpoint hit since the last call, oldest first
+    ///
+    /// Output:
+    ///     Breakpoint hits (Vec<AccessHit>)
+    /// ```
+    pub fn take_access_hits(&self) -> Vec<AccessHit> {
+        self.access_breaks.as_ref().map(|b| b.take_hits()).unwrap_or_default()
+    }
+
+    /// ```
+    /// Stop
+    ///
+    /// Executes the STOP instruction, switching CPU speed if one was requested via KEY1
+    /// ```
+    pub fn stop(&mut self) {
+        if self.bus.is_cgb() && self.speed_switch {
+            self.double_speed = !self.double_speed;
+            self.speed_switch = false;
+            self.half_cycle = 0;
+            // STOP resets the divider
+            self.timer.write_timer(DIV, 0);
+        }
+    }
+
+    /// ```
+    /// Fetch
+    ///
+    /// Fetches the byte specified by the PC, increments PC by one
+    ///
+    /// Output:
+    ///     Byte at the current PC (u8)
+    /// ```
+    pub fn fetch(&mut self) -> u8 {
+        let pc = self.get_pc();
+        // Fetching instructions isn't a read for access breakpoints
+        let val = self.read_mem(pc);
+        self.pc += 1;
+        val
+    }
+
     /// ```
     /// ADD A d8
     ///
@@ -726,10 +1238,13 @@ impl Cpu {
     ///     Byte at specified address (u8)
     /// ```
     pub fn read_ram(&self, addr: u16) -> u8 {
-        match addr {
-            DIV..=TAC => { self.timer.read_timer(addr) },
-            _ => { self.bus.read_ram(addr) }
+        let val = self.read_mem(addr);
+        if let Some(breaks) = &self.access_breaks {
+            if breaks.is_checking(AccessType::Read, addr) {
+                breaks.record(AccessType::Read, addr, val, val, val);
+            }
         }
+        val
     }
 
     /// ```
@@ -1151,13 +1666,17 @@ impl Cpu {
     ///     Byte to write (u8)
     /// ```
     pub fn write_ram(&mut self, addr: u16, val: u8) {
-        match addr {
-            DIV..=TAC => {
-                self.timer.write_timer(addr, val);
-            },
-            _ => {
-                self.dirty_battery_ram |= self.bus.write_ram(addr, val);
-            }
+        let checking = self.access_breaks.as_ref().is_some_and(|b| b.is_checking(AccessType::Write, addr));
+        if !checking {
+            self.write_mem(addr, val);
+            return;
+        }
+
+        let old = self.read_mem(addr);
+        self.write_mem(addr, val);
+        let new = self.read_mem(addr);
+        if let Some(breaks) = &self.access_breaks {
+            breaks.record(AccessType::Write, addr, val, old, new);
         }
     }
 
@@ -1183,6 +1702,103 @@ impl Cpu {
     // = Private methods =
     // ===================
 
+    /// ```
+    /// Scale cycles
+    ///
+    /// Converts CPU cycles into normal speed cycles, for the PPU and APU.
+    /// In double speed mode, odd cycles are carried over to the next instruction.
+    ///
+    /// Input:
+    ///     Cycles taken by the CPU (u8)
+    ///
+    /// Output:
+    ///     Normal speed cycles (u8)
+    /// ```
+    fn scale_cycles(&mut self, cycles: u8) -> u8 {
+        if !self.double_speed {
+            return cycles;
+        }
+
+        let total = cycles + self.half_cycle;
+        self.half_cycle = total & 1;
+        total / 2
+    }
+
+    /// ```
+    /// Read memory
+    ///
+    /// Reads a byte without checking access breakpoints
+    ///
+    /// Input:
+    ///     Address in RAM (u16)
+    ///
+    /// Output:
+    ///     Byte at specified address (u8)
+    /// ```
+    fn read_mem(&self, addr: u16) -> u8 {
+        if !B::SYSTEM_IO {
+            return self.bus.read_ram(addr);
+        }
+
+        match addr {
+            DIV..=TAC => { self.timer.read_timer(addr) },
+            KEY1 if self.bus.is_cgb() => {
+                let mut val: u8 = 0x7E;
+                val.write_bit(KEY1_SPEED_BIT, self.double_speed);
+                val.write_bit(KEY1_PREPARE_BIT, self.speed_switch);
+                val
+            },
+            _ => { self.bus.read_ram(addr) }
+        }
+    }
+
+    /// ```
+    /// Write memory
+    ///
+    /// Writes a byte without checking access breakpoints
+    ///
+    /// Inputs:
+    ///     Address in RAM (u16)
+    ///     Byte to write (u8)
+    /// ```
+    fn write_mem(&mut self, addr: u16, val: u8) {
+        if !B::SYSTEM_IO {
+            self.dirty_battery_ram |= self.bus.write_ram(addr, val);
+            return;
+        }
+
+        match addr {
+            DIV..=TAC => {
+                self.timer.write_timer(addr, val);
+            },
+            KEY1 if self.bus.is_cgb() => {
+                self.speed_switch = val.get_bit(KEY1_PREPARE_BIT);
+            },
+            _ => {
+                self.dirty_battery_ram |= self.bus.write_ram(addr, val);
+            }
+        }
+    }
+
+    /// ```
+    /// Run instruction
+    ///
+    /// Executes the instruction at the PC, checking access breakpoints while it runs
+    ///
+    /// Output:
+    ///     M-cycles taken (u8)
+    /// ```
+    fn run_instruction(&mut self) -> u8 {
+        if let Some(breaks) = &self.access_breaks {
+            breaks.begin_instruction(self.pc, self.read_mem(self.pc));
+        }
+        let cycles = opcodes::execute(self);
+        if let Some(breaks) = &self.access_breaks {
+            breaks.end_instruction();
+        }
+        cycles
+    }
+
     /// ```
     /// Interrupt Check
     ///
@@ -1291,3 +1907,124 @@ impl Cpu {
         self.write_ram(IF, if_reg);
     }
 }
+
+// Private methods which need the whole system, rather than just memory
+impl Cpu {
+    /// ```
+    /// Add DMA stall
+    ///
+    /// Halts the CPU while any VRAM DMA blocks that were just copied are transferred.
+    /// Transfers take the same real time in double speed, so twice as many CPU cycles.
+    /// ```
+    fn add_dma_stall(&mut self) {
+        let blocks = self.bus.take_dma_blocks();
+        let cycles = if self.double_speed { 2 * HDMA_BLOCK_CYCLES } else { HDMA_BLOCK_CYCLES };
+        self.dma_stall += blocks * cycles;
+    }
+
+    /// ```
+    /// Replace state
+    ///
+    /// Puts the machine into a state it didn't reach by running, such as by loading one.
+    /// Rewind snapshots only lead back along the current timeline, so they're discarded if it succeeds.
+    /// Anything which replaces the machine's state from outside should go through here.
+    ///
+    /// Input:
+    ///     Function which replaces the state (FnOnce(&mut Cpu) -> Result<(), StateError>)
+    ///
+    /// Output:
+    ///     Whether the state was replaced, or why not (Result<(), StateError>)
+    /// ```
+    fn replace_state<F: FnOnce(&mut Cpu) -> Result<(), StateError>>(&mut self, replace: F) -> Result<(), StateError> {
+        replace(self)?;
+        self.clear_rewind();
+        Ok(())
+    }
+
+    /// ```
+    /// Restore state
+    ///
+    /// Loads a snapshot into the machine, leaving it untouched if the snapshot can't be read
+    ///
+    /// Input:
+    ///     Save state data (&[u8])
+    ///
+    /// Output:
+    ///     Whether the state was loaded, or why not (Result<(), StateError>)
+    /// ```
+    fn restore_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut state = StateReader::new(data, self.bus.get_rom_crc())?;
+
+        let pc = state.read_u16()?;
+        let sp = state.read_u16()?;
+        let mut regs = [0; 8];
+        for reg in regs.iter_mut() {
+            *reg = state.read_u8()?;
+        }
+        let interrupt_enabled = state.read_bool()?;
+        let halted = state.read_bool()?;
+        let double_speed = state.read_bool()?;
+        let speed_switch = state.read_bool()?;
+        let half_cycle = state.read_u8()?;
+        let dma_stall = state.read_u16()?;
+        let model = Model::from_id(state.read_u8()?).ok_or(StateError::Invalid)?;
+
+        let mut clock = self.clock.clone();
+        clock.load_state(&mut state)?;
+        let mut timer = self.timer.clone();
+        timer.load_state(&mut state)?;
+        // Read last, and only changes the bus once the whole state has been read
+        self.bus.load_state(&mut state)?;
+
+        self.pc = pc;
+        self.sp = sp;
+        let [a, b, c, d, e, f, h, l] = regs;
+        self.a = a;
+        self.b = b;
+        self.c = c;
+        self.d = d;
+        self.e = e;
+        self.f = f;
+        self.h = h;
+        self.l = l;
+        self.interrupt_enabled = interrupt_enabled;
+        self.halted = halted;
+        self.double_speed = double_speed;
+        self.speed_switch = speed_switch;
+        self.half_cycle = half_cycle;
+        self.dma_stall = dma_stall;
+        self.model = model;
+        self.clock = clock;
+        self.timer = timer;
+        Ok(())
+    }
+
+    /// ```
+    /// Clear rewind
+    ///
+    /// Throws away all rewind snapshots, such as when they no longer lead back to the current game
+    /// ```
+    fn clear_rewind(&mut self) {
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+    }
+
+    /// ```
+    /// Record rewind
+    ///
+    /// Counts a finished frame, adding a snapshot to the rewind buffer when one is due
+    /// ```
+    fn record_rewind(&mut self) {
+        if self.bus.is_serial_lockstep() {
+            return;
+        }
+
+        if let Some(mut rewind) = self.rewind.take() {
+            if rewind.next_frame() {
+                rewind.push(&self.save_state());
+            }
+            self.rewind = Some(rewind);
+        }
+    }
+}
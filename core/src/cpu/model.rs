@@ -0,0 +1,164 @@
+/*
+ * Hardware models
+ *
+ * Each model's boot ROM leaves the system in a slightly different state
+ * when it hands control to the cartridge at $0100. Values are from the
+ * Mooneye test suite's boot_regs, boot_hwio and boot_div tests.
+ *
+ * Model | AF    | BC    | DE    | HL    | DIV counter
+ * ------+-------+-------+-------+-------+------------
+ * DMG0  | $0100 | $FF13 | $00C1 | $8403 | $182C
+ * DMG   | $01B0 | $0013 | $00D8 | $014D | $ABCC
+ * MGB   | $FFB0 | $0013 | $00D8 | $014D | $ABCC
+ * SGB   | $0100 | $0014 | $0000 | $C060 | Varies
+ * SGB2  | $FF00 | $0014 | $0000 | $C060 | Varies
+ * CGB   | $1180 | $0000 | $FF56 | $000D | $1EA0
+ * AGB   | $1100 | $0100 | $FF56 | $000D | $1EA0
+ *
+ * The SGB boot ROM's length depends on communication with the SNES,
+ * so its DIV counter isn't fixed.
+ */
+
+// =============
+// = Constants =
+// =============
+const CGB_FLAG_ADDR: usize = 0x0143;
+const DMG_CGB_FLAG: u8  = 0x80;
+const CGB_ONLY_FLAG: u8 = 0xC0;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Model {
+    DMG0,
+    DMG,
+    MGB,
+    SGB,
+    SGB2,
+    CGB,
+    AGB,
+}
+
+impl Model {
+    /// ```
+    /// Detect
+    ///
+    /// Picks the most suitable model for a game, CGB if it supports color, otherwise DMG
+    ///
+    /// Input:
+    ///     ROM data (&[u8])
+    ///
+    /// Output:
+    ///     Suggested model (Model)
+    /// ```
+    pub fn detect(rom: &[u8]) -> Model {
+        match rom.get(CGB_FLAG_ADDR) {
+            Some(&DMG_CGB_FLAG) | Some(&CGB_ONLY_FLAG) => { Model::CGB },
+            _ => { Model::DMG }
+        }
+    }
+
+    /// ```
+    /// Is CGB
+    ///
+    /// Whether this model has Game Boy Color hardware
+    ///
+    /// Output:
+    ///     If model supports CGB features (bool)
+    /// ```
+    pub fn is_cgb(&self) -> bool {
+        matches!(self, Model::CGB | Model::AGB)
+    }
+
+    /// ```
+    /// Is SGB
+    ///
+    /// Whether this model is a Super Game Boy
+    ///
+    /// Output:
+    ///     If model is an SGB (bool)
+    /// ```
+    pub fn is_sgb(&self) -> bool {
+        matches!(self, Model::SGB | Model::SGB2)
+    }
+
+    /// ```
+    /// Get registers
+    ///
+    /// Register values left behind by this model's boot ROM
+    ///
+    /// Output:
+    ///     AF, BC, DE and HL values ([u16])
+    /// ```
+    pub fn get_regs(&self) -> [u16; 4] {
+        match self {
+            Model::DMG0 => { [0x0100, 0xFF13, 0x00C1, 0x8403] },
+            Model::DMG =>  { [0x01B0, 0x0013, 0x00D8, 0x014D] },
+            Model::MGB =>  { [0xFFB0, 0x0013, 0x00D8, 0x014D] },
+            Model::SGB =>  { [0x0100, 0x0014, 0x0000, 0xC060] },
+            Model::SGB2 => { [0xFF00, 0x0014, 0x0000, 0xC060] },
+            Model::CGB =>  { [0x1180, 0x0000, 0xFF56, 0x000D] },
+            Model::AGB =>  { [0x1100, 0x0100, 0xFF56, 0x000D] },
+        }
+    }
+
+    /// ```
+    /// Get DIV counter
+    ///
+    /// Value of the internal 16-bit divider counter when the boot ROM finishes.
+    /// DIV is the upper byte of this counter.
+    ///
+    /// Output:
+    ///     Divider counter, in T-cycles (u16)
+    /// ```
+    pub fn get_div_counter(&self) -> u16 {
+        match self {
+            Model::DMG0 =>              { 0x182C },
+            Model::DMG | Model::MGB =>  { 0xABCC },
+            Model::SGB | Model::SGB2 => { 0x0000 },
+            Model::CGB | Model::AGB =>  { 0x1EA0 },
+        }
+    }
+
+    /// ```
+    /// Get ID
+    ///
+    /// Numbers each model, for storing in save states
+    ///
+    /// Output:
+    ///     Model number (u8)
+    /// ```
+    pub fn get_id(&self) -> u8 {
+        match self {
+            Model::DMG0 => { 0 },
+            Model::DMG =>  { 1 },
+            Model::MGB =>  { 2 },
+            Model::SGB =>  { 3 },
+            Model::SGB2 => { 4 },
+            Model::CGB =>  { 5 },
+            Model::AGB =>  { 6 },
+        }
+    }
+
+    /// ```
+    /// From ID
+    ///
+    /// Gets the model with the given number, the reverse of get_id
+    ///
+    /// Input:
+    ///     Model number (u8)
+    ///
+    /// Output:
+    ///     Model, if valid (Option<Model>)
+    /// ```
+    pub fn from_id(id: u8) -> Option<Model> {
+        match id {
+            0 => { Some(Model::DMG0) },
+            1 => { Some(Model::DMG) },
+            2 => { Some(Model::MGB) },
+            3 => { Some(Model::SGB) },
+            4 => { Some(Model::SGB2) },
+            5 => { Some(Model::CGB) },
+            6 => { Some(Model::AGB) },
+            _ => { None }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::utils::ModifyBits;
+use crate::state::{StateError, StateReader, StateWriter};
 
 pub const DIV: u16 = 0xFF04; // Divider register
 pub const TIMA: u16 = 0xFF05; // Counter register
@@ -9,6 +10,7 @@ pub const TAC: u16 = 0xFF07; // Control register
 const DIV_SPEED_IN_CYCLES: u16 = 64;
 const TIMA_SPEED_IN_CYCLES: [u16; 4] = [256, 4, 16, 64];
 
+#[derive(Clone)]
 pub struct Timer {
     running: bool,
     div_cycles: u16,
@@ -38,6 +40,32 @@ impl Timer {
         }
     }
 
+    /// ```
+    /// Set DIV counter
+    ///
+    /// Sets the internal divider counter, used to match the state left by the boot ROM
+    ///
+    /// Input:
+    ///     Divider counter, in T-cycles (u16)
+    /// ```
+    pub fn set_div_counter(&mut self, counter: u16) {
+        self.div = (counter >> 8) as u8;
+        // Sub-counter is kept in M-cycles
+        self.div_cycles = (counter & 0xFF) / 4;
+    }
+
+    /// ```
+    /// Set TIMA
+    ///
+    /// Sets the timer counter directly, used when restoring a save state
+    ///
+    /// Input:
+    ///     New counter value (u8)
+    /// ```
+    pub fn set_tima(&mut self, val: u8) {
+        self.tima = val;
+    }
+
     pub fn tick(&mut self, cycles: u8) -> bool {
         let mut interrupt = false;
 
@@ -99,4 +127,47 @@ impl Timer {
             }
         };
     }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes the timer registers and their counters to a save state
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.running);
+        state.write_u16(self.div_cycles);
+        state.write_u16(self.tima_cycles);
+        state.write_u8(self.tima_index as u8);
+        state.write_u8(self.div);
+        state.write_u8(self.tima);
+        state.write_u8(self.tma);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the timer from a save state
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.running = state.read_bool()?;
+        self.div_cycles = state.read_u16()?;
+        self.tima_cycles = state.read_u16()?;
+        self.tima_index = state.read_u8()? as usize;
+        if self.tima_index >= TIMA_SPEED_IN_CYCLES.len() {
+            return Err(StateError::Invalid);
+        }
+        self.div = state.read_u8()?;
+        self.tima = state.read_u8()?;
+        self.tma = state.read_u8()?;
+        Ok(())
+    }
 }
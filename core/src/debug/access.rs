@@ -0,0 +1,184 @@
+// Memory access breakpoints
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+
+/*
+ * Memory access breakpoints
+ *
+ * Checked by the CPU as each instruction accesses memory, so they see every
+ * access, even writes which don't change anything. Accesses made outside of
+ * an instruction, such as by the debugger itself or interrupt handling, are
+ * ignored, as are instructions fetching their own bytes.
+ *
+ * Type    | Fires when                                  | Reported
+ * --------+---------------------------------------------+----------------------------------
+ * Read    | An instruction reads from the range         | PC, address, value read
+ * Write   | An instruction writes to the range          | PC, address, value, old/new contents
+ * Execute | An instruction in the range starts running  | PC, opcode
+ *
+ * The CPU only holds these while any are set, so otherwise there's nothing to check.
+ */
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccessType {
+    Read,
+    Write,
+    Execute,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AccessBreak {
+    pub kind: AccessType,
+    pub start: u16,
+    pub end: u16,
+}
+
+impl AccessBreak {
+    /// ```
+    /// New
+    ///
+    /// Creates a breakpoint over an address range, including both ends
+    ///
+    /// Inputs:
+    ///     Type of access (AccessType)
+    ///     First address (u16)
+    ///     Last address (u16)
+    ///
+    /// Output:
+    ///     Access breakpoint (AccessBreak)
+    /// ```
+    pub fn new(kind: AccessType, start: u16, end: u16) -> AccessBreak {
+        AccessBreak {
+            kind,
+            start: start.min(end),
+            end: start.max(end),
+        }
+    }
+
+    pub fn contains(&self, addr: u16) -> bool {
+        (self.start..=self.end).contains(&addr)
+    }
+}
+
+impl fmt::Display for AccessBreak {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            AccessType::Read =>     { "read" },
+            AccessType::Write =>    { "write" },
+            AccessType::Execute =>  { "execute" },
+        };
+        if self.start == self.end {
+            write!(f, "{} ${:04x}", kind, self.start)
+        } else {
+            write!(f, "{} ${:04x}-${:04x}", kind, self.start, self.end)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AccessHit {
+    pub kind: AccessType,
+    // Start of the instruction which made the access
+    pub pc: u16,
+    pub addr: u16,
+    // Byte read or written, or the opcode when executing
+    pub val: u8,
+    // Contents before and after a write, which can differ from what was written for registers and ROM
+    pub old: u8,
+    pub new: u8,
+}
+
+impl fmt::Display for AccessHit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            AccessType::Read => {
+                write!(f, "${:04x}: read ${:02x} from ${:04x}", self.pc, self.val, self.addr)
+            },
+            AccessType::Write => {
+                write!(f, "${:04x}: wrote ${:02x} to ${:04x} (${:02x} -> ${:02x})", self.pc, self.val, self.addr, self.old, self.new)
+            },
+            AccessType::Execute => {
+                write!(f, "${:04x}: executing opcode ${:02x}", self.pc, self.val)
+            }
+        }
+    }
+}
+
+pub(crate) struct AccessBreaks {
+    breaks: Vec<AccessBreak>,
+    // Set while an instruction is running, holding its address
+    current_pc: Cell<Option<u16>>,
+    hits: RefCell<Vec<AccessHit>>,
+}
+
+impl AccessBreaks {
+    pub fn new(breaks: Vec<AccessBreak>) -> AccessBreaks {
+        AccessBreaks {
+            breaks,
+            current_pc: Cell::new(None),
+            hits: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// ```
+    /// Begin instruction
+    ///
+    /// Starts checking accesses, on behalf of the instruction at the given address
+    ///
+    /// Inputs:
+    ///     Address of the instruction (u16)
+    ///     Its opcode (u8)
+    /// ```
+    pub fn begin_instruction(&self, pc: u16, opcode: u8) {
+        if self.is_watching(AccessType::Execute, pc) {
+            self.record(AccessType::Execute, pc, opcode, opcode, opcode);
+        }
+        self.current_pc.set(Some(pc));
+    }
+
+    pub fn end_instruction(&self) {
+        self.current_pc.set(None);
+    }
+
+    /// ```
+    /// Is checking
+    ///
+    /// Whether an access of this type to this address should be recorded right now
+    ///
+    /// Inputs:
+    ///     Type of access (AccessType)
+    ///     Address (u16)
+    ///
+    /// Output:
+    ///     Whether to record it (bool)
+    /// ```
+    pub fn is_checking(&self, kind: AccessType, addr: u16) -> bool {
+        self.current_pc.get().is_some() && self.is_watching(kind, addr)
+    }
+
+    /// ```
+    /// Record
+    ///
+    /// Notes an access made by the current instruction
+    ///
+    /// Inputs:
+    ///     Type of access (AccessType)
+    ///     Address (u16)
+    ///     Byte accessed (u8)
+    ///     Contents before (u8)
+    ///     Contents after (u8)
+    /// ```
+    pub fn record(&self, kind: AccessType, addr: u16, val: u8, old: u8, new: u8) {
+        let pc = self.current_pc.get().unwrap_or(addr);
+        self.hits.borrow_mut().push(AccessHit { kind, pc, addr, val, old, new });
+    }
+
+    pub fn take_hits(&self) -> Vec<AccessHit> {
+        self.hits.take()
+    }
+
+    fn is_watching(&self, kind: AccessType, addr: u16) -> bool {
+        self.breaks.iter().any(|b| b.kind == kind && b.contains(addr))
+    }
+}
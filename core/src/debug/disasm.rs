@@ -0,0 +1,411 @@
+// Disassembler
+
+use crate::cartridge::ROM_BANK_SIZE;
+use super::symbols::Symbols;
+use std::fmt;
+
+/*
+ * Disassembler
+ *
+ * Operands are written into the opcode names as placeholders, which are
+ * replaced by the decoded value when an instruction is printed.
+ *
+ * Placeholder | Bytes | Meaning                              | Operand
+ * ------------+-------+--------------------------------------+--------------------------------
+ * d8          | 1     | Immediate byte                       | Imm8
+ * d16         | 2     | Immediate word                       | Imm16
+ * a8          | 1     | Address in $FF00-$FFFF               | Addr
+ * a16         | 2     | Address                              | Target for JP/CALL, else Addr
+ * r8          | 1     | Signed offset                        | Target for JR, else Offset
+ *
+ * ROM banks are laid out as the CPU sees them. Bank 0 is at $0000-$3FFF,
+ * and every other bank is at $4000-$7FFF.
+ *
+ * The listing from export_rom has one line per instruction, as bank:address,
+ * the raw bytes, then the instruction, with labels on their own line before.
+ * Data is disassembled as if it were code.
+ */
+
+// =============
+// = Constants =
+// =============
+const OPCODE_NAMES: [&str; 0x100] = [
+    "NOP",          "LD BC, d16",   "LD (BC), A",   "INC BC",       "INC B",        "DEC B",        "LD B, d8",     "RLCA",         // $00
+    "LD (a16), SP", "ADD HL, BC",   "LD A, (BC)",   "DEC BC",       "INC C",        "DEC C",        "LD C, d8",     "RRCA",         // $08
+    "STOP",         "LD DE, d16",   "LD (DE), A",   "INC DE",       "INC D",        "DEC D",        "LD D, d8",     "RLA",          // $10
+    "JR r8",        "ADD HL, DE",   "LD A, (DE)",   "DEC DE",       "INC E",        "DEC E",        "LD E, d8",     "RRA",          // $18
+    "JR NZ, r8",    "LD HL, d16",   "LD (HL+), A",  "INC HL",       "INC H",        "DEC H",        "LD H, d8",     "DAA",          // $20
+    "JR Z, r8",     "ADD HL, HL",   "LD A, (HL+)",  "DEC HL",       "INC L",        "DEC L",        "LD L, d8",     "CPL",          // $28
+    "JR NC, r8",    "LD SP, d16",   "LD (HL-), A",  "INC SP",       "INC (HL)",     "DEC (HL)",     "LD (HL), d8",  "SCF",          // $30
+    "JR C, r8",     "ADD HL, SP",   "LD A, (HL-)",  "DEC SP",       "INC A",        "DEC A",        "LD A, d8",     "CCF",          // $38
+    "LD B, B",      "LD B, C",      "LD B, D",      "LD B, E",      "LD B, H",      "LD B, L",      "LD B, (HL)",   "LD B, A",      // $40
+    "LD C, B",      "LD C, C",      "LD C, D",      "LD C, E",      "LD C, H",      "LD C, L",      "LD C, (HL)",   "LD C, A",      // $48
+    "LD D, B",      "LD D, C",      "LD D, D",      "LD D, E",      "LD D, H",      "LD D, L",      "LD D, (HL)",   "LD D, A",      // $50
+    "LD E, B",      "LD E, C",      "LD E, D",      "LD E, E",      "LD E, H",      "LD E, L",      "LD E, (HL)",   "LD E, A",      // $58
+    "LD H, B",      "LD H, C",      "LD H, D",      "LD H, E",      "LD H, H",      "LD H, L",      "LD H, (HL)",   "LD H, A",      // $60
+    "LD L, B",      "LD L, C",      "LD L, D",      "LD L, E",      "LD L, H",      "LD L, L",      "LD L, (HL)",   "LD L, A",      // $68
+    "LD (HL), B",   "LD (HL), C",   "LD (HL), D",   "LD (HL), E",   "LD (HL), H",   "LD (HL), L",   "HALT",         "LD (HL), A",   // $70
+    "LD A, B",      "LD A, C",      "LD A, D",      "LD A, E",      "LD A, H",      "LD A, L",      "LD A, (HL)",   "LD A, A",      // $78
+    "ADD A, B",     "ADD A, C",     "ADD A, D",     "ADD A, E",     "ADD A, H",     "ADD A, L",     "ADD A, (HL)",  "ADD A, A",     // $80
+    "ADC A, B",     "ADC A, C",     "ADC A, D",     "ADC A, E",     "ADC A, H",     "ADC A, L",     "ADC A, (HL)",  "ADC A, A",     // $88
+    "SUB B",        "SUB C",        "SUB D",        "SUB E",        "SUB H",        "SUB L",        "SUB (HL)",     "SUB A",        // $90
+    "SBC B",        "SBC C",        "SBC D",        "SBC E",        "SBC H",        "SBC L",        "SBC (HL)",     "SBC A",        // $98
+    "AND B",        "AND C",        "AND D",        "AND E",        "AND H",        "AND L",        "AND (HL)",     "AND A",        // $A0
+    "XOR B",        "XOR C",        "XOR D",        "XOR E",        "XOR H",        "XOR L",        "XOR (HL)",     "XOR A",        // $A8
+    "OR B",         "OR C",         "OR D",         "OR E",         "OR H",         "OR L",         "OR (HL)",      "OR A",         // $B0
+    "CP B",         "CP C",         "CP D",         "CP E",         "CP H",         "CP L",         "CP (HL)",      "CP A",         // $B8
+    "RET NZ",       "POP BC",       "JP NZ, a16",   "JP a16",       "CALL NZ, a16", "PUSH BC",      "ADD A, d8",    "RST $00",      // $C0
+    "RET Z",        "RET",          "JP Z, a16",    "PREFIX CB",    "CALL Z, a16",  "CALL a16",     "ADC A, d8",    "RST $08",      // $C8
+    "RET NC",       "POP DE",       "JP NC, a16",   "INVALID",      "CALL NC, a16", "PUSH DE",      "SUB d8",       "RST $10",      // $D0
+    "RET C",        "RETI",         "JP C, a16",    "INVALID",      "CALL C, a16",  "INVALID",      "SBC A, d8",    "RST $18",      // $D8
+    "LDH (a8), A",  "POP HL",       "LD (C), A",    "INVALID",      "INVALID",      "PUSH HL",      "AND d8",       "RST $20",      // $E0
+    "ADD SP, r8",   "JP (HL)",      "LD (a16), A",  "INVALID",      "INVALID",      "INVALID",      "XOR d8",       "RST $28",      // $E8
+    "LDH A, (a8)",  "POP AF",       "LD A, (C)",    "DI",           "INVALID",      "PUSH AF",      "OR d8",        "RST $30",      // $F0
+    "LD HL, SP+r8", "LD SP, HL",    "LD A, (a16)",  "EI",           "INVALID",      "INVALID",      "CP d8",        "RST $38"       // $F8
+];
+const CB_OPS: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+const CB_BIT_OPS: [&str; 3] = ["BIT", "RES", "SET"];
+const CB_REGS: [&str; 8] = ["B", "C", "D", "E", "H", "L", "(HL)", "A"];
+
+const PLACEHOLDERS: [(&str, usize); 5] = [("d16", 2), ("a16", 2), ("d8", 1), ("a8", 1), ("r8", 1)];
+
+const CB_PREFIX: u8 = 0xCB;
+const STOP_OPCODE: u8 = 0x10;
+const HIGH_RAM_PAGE: u16 = 0xFF00;
+const SWITCH_BANK_START: u16 = 0x4000;
+const BYTES_WIDTH: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operand {
+    Imm8(u8),
+    Imm16(u16),
+    Addr(u16),
+    Target(u16),
+    Offset(i8),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Instruction {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    pub mnemonic: String,
+    pub operand: Option<Operand>,
+}
+
+impl Instruction {
+    /// ```
+    /// Get length
+    ///
+    /// Number of bytes the instruction takes up
+    ///
+    /// Output:
+    ///     Length in bytes (usize)
+    /// ```
+    pub fn get_length(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// ```
+    /// Get next address
+    ///
+    /// Address of the instruction following this one
+    ///
+    /// Output:
+    ///     Next address (u16)
+    /// ```
+    pub fn get_next_addr(&self) -> u16 {
+        self.addr.wrapping_add(self.bytes.len() as u16)
+    }
+
+    /// ```
+    /// Get target
+    ///
+    /// Where the instruction can jump or call to, if that's known without running it
+    ///
+    /// Output:
+    ///     Jump target (Option<u16>)
+    /// ```
+    pub fn get_target(&self) -> Option<u16> {
+        match self.operand {
+            Some(Operand::Target(target)) => { Some(target) },
+            // RST's target is held in bits 3-5 of the opcode
+            _ if self.mnemonic.starts_with("RST") => { Some((self.bytes[0] & 0x38) as u16) },
+            _ => { None }
+        }
+    }
+
+    /// ```
+    /// Format with labels
+    ///
+    /// Writes out the instruction, naming any address which has a label
+    ///
+    /// Input:
+    ///     Function finding the label for an address (Fn(u16) -> Option<String>)
+    ///
+    /// Output:
+    ///     Instruction text (String)
+    /// ```
+    pub fn format_with<F: Fn(u16) -> Option<String>>(&self, label: F) -> String {
+        let name_addr = |addr: u16| label(addr).unwrap_or_else(|| format!("${:04X}", addr));
+        match self.operand {
+            Some(Operand::Imm8(val)) =>     { self.mnemonic.replace("d8", &format!("${:02X}", val)) },
+            Some(Operand::Imm16(val)) =>    { self.mnemonic.replace("d16", &format!("${:04X}", val)) },
+            Some(Operand::Addr(addr)) =>    { replace_placeholder(&self.mnemonic, &name_addr(addr)) },
+            Some(Operand::Target(addr)) =>  { replace_placeholder(&self.mnemonic, &name_addr(addr)) },
+            Some(Operand::Offset(val)) =>   {
+                // SP+r8 reads better as SP-3 than SP+-3
+                if self.mnemonic.contains("+r8") {
+                    self.mnemonic.replace("+r8", &format!("{:+}", val))
+                } else {
+                    self.mnemonic.replace("r8", &val.to_string())
+                }
+            },
+            None => {
+                // RST's target isn't an operand, but is still worth naming
+                match self.get_target() {
+                    Some(target) if self.mnemonic.starts_with("RST") => {
+                        label(target).map_or_else(|| self.mnemonic.clone(), |name| format!("RST {}", name))
+                    },
+                    _ => { self.mnemonic.clone() }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format_with(|_| None))
+    }
+}
+
+/// ```
+/// Decode
+///
+/// Decodes the instruction at the start of the given bytes
+///
+/// Inputs:
+///     Bytes, starting with the opcode (&[u8])
+///     Address of the opcode (u16)
+///
+/// Output:
+///     Instruction, or None if the bytes run out first (Option<Instruction>)
+/// ```
+pub fn decode(bytes: &[u8], addr: u16) -> Option<Instruction> {
+    let opcode = *bytes.first()?;
+    if opcode == CB_PREFIX {
+        let cb_op = *bytes.get(1)?;
+        return Some(Instruction {
+            addr,
+            bytes: bytes[..2].to_vec(),
+            mnemonic: cb_name(cb_op),
+            operand: None,
+        });
+    }
+
+    let mnemonic = OPCODE_NAMES[opcode as usize];
+    let placeholder = PLACEHOLDERS.iter().find(|(p, _)| mnemonic.contains(p));
+    // STOP skips over the byte after it, which is normally $00
+    let extra = if opcode == STOP_OPCODE { 1 } else { placeholder.map_or(0, |(_, size)| *size) };
+    let length = 1 + extra;
+    if bytes.len() < length {
+        return None;
+    }
+
+    let bytes = bytes[..length].to_vec();
+    let next_addr = addr.wrapping_add(length as u16);
+    let is_jump = mnemonic.starts_with("JP") || mnemonic.starts_with("CALL") || mnemonic.starts_with("JR");
+    let operand = placeholder.map(|(p, _)| {
+        let word = if length == 3 { (bytes[2] as u16) << 8 | bytes[1] as u16 } else { 0 };
+        match *p {
+            "d8" =>     { Operand::Imm8(bytes[1]) },
+            "d16" =>    { Operand::Imm16(word) },
+            "a8" =>     { Operand::Addr(HIGH_RAM_PAGE | bytes[1] as u16) },
+            "a16" =>    { if is_jump { Operand::Target(word) } else { Operand::Addr(word) } },
+            _ =>        {
+                // Relative jumps are from the end of the instruction
+                let offset = bytes[1] as i8;
+                if is_jump { Operand::Target(next_addr.wrapping_add(offset as u16)) } else { Operand::Offset(offset) }
+            }
+        }
+    });
+
+    Some(Instruction {
+        addr,
+        bytes,
+        mnemonic: mnemonic.to_string(),
+        operand,
+    })
+}
+
+/// ```
+/// Disassemble
+///
+/// Decodes instructions from anything readable, such as the live memory map
+///
+/// Inputs:
+///     Function reading a byte from an address (Fn(u16) -> u8)
+///     Address to start from (u16)
+///     Number of instructions (usize)
+///
+/// Output:
+///     Decoded instructions (Vec<Instruction>)
+/// ```
+pub fn disassemble<F: Fn(u16) -> u8>(read: F, addr: u16, count: usize) -> Vec<Instruction> {
+    let mut insts = Vec::with_capacity(count);
+    let mut pc = addr;
+    for _ in 0..count {
+        let bytes = [read(pc), read(pc.wrapping_add(1)), read(pc.wrapping_add(2))];
+        // Three bytes is enough for any instruction
+        let inst = decode(&bytes, pc).unwrap();
+        pc = inst.get_next_addr();
+        insts.push(inst);
+    }
+    insts
+}
+
+/// ```
+/// Disassemble bank
+///
+/// Decodes instructions from a ROM bank, regardless of which is currently mapped in
+///
+/// Inputs:
+///     Full ROM data (&[u8])
+///     ROM bank number (usize)
+///     Address to start from, as the CPU would see it (u16)
+///     Most instructions to decode (usize)
+///
+/// Output:
+///     Decoded instructions, stopping at the end of the bank (Vec<Instruction>)
+/// ```
+pub fn disassemble_bank(rom: &[u8], bank: usize, addr: u16, count: usize) -> Vec<Instruction> {
+    let data = get_bank(rom, bank);
+    let base = get_bank_base(bank);
+    let mut insts = Vec::new();
+    if addr < base {
+        return insts;
+    }
+
+    let mut offset = (addr - base) as usize;
+    while insts.len() < count && offset < data.len() {
+        match decode(&data[offset..], base + offset as u16) {
+            Some(inst) => {
+                offset += inst.get_length();
+                insts.push(inst);
+            },
+            None => { break; }
+        }
+    }
+    insts
+}
+
+/// ```
+/// Export ROM
+///
+/// Disassembles every bank of a ROM into a text listing, with labels if any are given
+///
+/// Inputs:
+///     Full ROM data (&[u8])
+///     Symbol table (Option<&Symbols>)
+///
+/// Output:
+///     Listing (String)
+/// ```
+pub fn export_rom(rom: &[u8], symbols: Option<&Symbols>) -> String {
+    let mut listing = String::new();
+    let num_banks = rom.len().div_ceil(ROM_BANK_SIZE);
+
+    for bank in 0..num_banks {
+        let data = get_bank(rom, bank);
+        let base = get_bank_base(bank);
+        listing.push_str(&format!("; ROM bank ${:02X}\n", bank));
+
+        let label = |addr: u16| symbols.and_then(|s| s.get_label(addr, bank as u16)).map(|l| l.to_string());
+        let mut offset = 0;
+        while offset < data.len() {
+            let addr = base + offset as u16;
+            if let Some(name) = label(addr) {
+                listing.push_str(&format!("{}:\n", name));
+            }
+            match decode(&data[offset..], addr) {
+                Some(inst) => {
+                    let text = inst.format_with(label);
+                    listing.push_str(&format!("{:02X}:{:04X}  {:w$}  {}\n", bank, addr, format_bytes(&inst.bytes), text, w = BYTES_WIDTH));
+                    offset += inst.get_length();
+                },
+                None => {
+                    // An instruction cut off by the end of the bank
+                    let rest = &data[offset..];
+                    let vals: Vec<String> = rest.iter().map(|b| format!("${:02X}", b)).collect();
+                    listing.push_str(&format!("{:02X}:{:04X}  {:w$}  DB {}\n", bank, addr, format_bytes(rest), vals.join(", "), w = BYTES_WIDTH));
+                    break;
+                }
+            }
+        }
+        listing.push('\n');
+    }
+    listing
+}
+
+/// ```
+/// CB name
+///
+/// Gets the name of a $CB prefixed instruction
+///
+/// Input:
+///     Byte following the prefix (u8)
+///
+/// Output:
+///     Instruction name (String)
+/// ```
+fn cb_name(op: u8) -> String {
+    let reg = CB_REGS[(op & 0x07) as usize];
+    let bit = (op >> 3) & 0x07;
+    match op >> 6 {
+        0 => { format!("{} {}", CB_OPS[bit as usize], reg) },
+        n => { format!("{} {}, {}", CB_BIT_OPS[(n - 1) as usize], bit, reg) }
+    }
+}
+
+/// ```
+/// Replace placeholder
+///
+/// Swaps whichever address placeholder the name has for the given text
+///
+/// Inputs:
+///     Instruction name (&str)
+///     Operand text (&str)
+///
+/// Output:
+///     Name with operand filled in (String)
+/// ```
+fn replace_placeholder(mnemonic: &str, text: &str) -> String {
+    ["a16", "a8", "r8"].iter().fold(mnemonic.to_string(), |name, p| name.replace(p, text))
+}
+
+/// ```
+/// Get bank
+///
+/// Gets the data for a ROM bank, which may be short or empty past the end of the ROM
+///
+/// Inputs:
+///     Full ROM data (&[u8])
+///     ROM bank number (usize)
+///
+/// Output:
+///     Bank data (&[u8])
+/// ```
+fn get_bank(rom: &[u8], bank: usize) -> &[u8] {
+    let start = (bank * ROM_BANK_SIZE).min(rom.len());
+    let end = (start + ROM_BANK_SIZE).min(rom.len());
+    &rom[start..end]
+}
+
+fn get_bank_base(bank: usize) -> u16 {
+    if bank == 0 { 0x0000 } else { SWITCH_BANK_START }
+}
+
+fn format_bytes(bytes: &[u8]) -> String {
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    hex.join(" ")
+}
@@ -1,68 +1,106 @@
 // The songbird debugger module
+pub mod access;
+pub mod disasm;
+pub mod symbols;
+
 use crate::cpu::*;
+use access::{AccessBreak, AccessType};
+use disasm::disassemble;
+use symbols::Symbols;
 use std::cmp::min;
 use std::collections::HashMap;
+use std::fmt;
+
+// =============
+// = Constants =
+// =============
+const CALL_OPCODES: [u8; 5] = [0xCD, 0xC4, 0xCC, 0xD4, 0xDC];
+const CALL_LENGTH: u16 = 3;
+// RST is $C7 with the target in bits 3-5
+const RST_MASK: u8 = 0xC7;
+const RST_LENGTH: u16 = 1;
+const STACK_TOP: u16 = 0xFFFE;
+const STACK_SCAN_WORDS: u16 = 64;
+const BACKTRACE_DEPTH: usize = 16;
+
+/*
+ * Break/watchpoints can be given as bank:address, in hex, to only match while
+ * that bank is mapped in. Without a bank, they match whichever bank is mapped.
+ *
+ * Region      | Bank
+ * ------------+----------------------------
+ * $4000-$7FFF | Cartridge ROM bank
+ * $A000-$BFFF | Cartridge RAM bank
+ * Elsewhere   | Not banked, bank is ignored
+ */
+const ROM_BANK_START: u16 = 0x4000;
+const ROM_BANK_STOP: u16 = 0x7FFF;
+const RAM_BANK_START: u16 = 0xA000;
+const RAM_BANK_STOP: u16 = 0xBFFF;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Point {
+    pub bank: Option<u16>,
+    pub addr: u16,
+}
 
-const OPCODE_NAMES: [&str; 0x100] = [
-    "NOP",          "LD BC, d16",   "LD (BC), A",   "INC BC",       "INC B",        "DEC B",        "LD B, d8",     "RLCA",         // $00
-    "LD (a16), SP", "ADD HL, BC",   "LD A, (BC)",   "DEC BC",       "INC C",        "DEC C",        "LD C, d8",     "RRCA",         // $08
-    "STOP",         "LD DE, d16",   "LD (DE), A",   "INC DE",       "INC D",        "DEC D",        "LD D, d8",     "RLA",          // $10
-    "JR r8",        "ADD HL, DE",   "LD A, (DE)",   "DEC DE",       "INC E",        "DEC E",        "LD E, d8",     "RRA",          // $18
-    "JR NZ, r8",    "LD HL, d16",   "LD (HL+), A",  "INC HL",       "INC H",        "DEC H",        "LD H, d8",     "DAA",          // $20
-    "JR Z, r8",     "ADD HL, HL",   "LD A, (HL+)",  "DEC HL",       "INC L",        "DEC L",        "LD L, d8",     "CPL",          // $28
-    "JR NC, r8",    "LD SP, d16",   "LD (HL-), A",  "INC SP",       "INC (HL)",     "DEC (HL)",     "LD (HL), d8",  "SCF",          // $30
-    "JR C, r8",     "ADD HL, SP",   "LD A, (HL-)",  "DEC SP",       "INC A",        "DEC A",        "LD A, d8",     "CCF",          // $38
-    "LD B, B",      "LD B, C",      "LD B, D",      "LD B, E",      "LD B, H",      "LD B, L",      "LD B, (HL)",   "LD B, A",      // $40
-    "LD C, B",      "LD C, C",      "LD C, D",      "LD C, E",      "LD C, H",      "LD C, L",      "LD C, (HL)",   "LD C, A",      // $48
-    "LD D, B",      "LD D, C",      "LD D, D",      "LD D, E",      "LD D, H",      "LD D, L",      "LD D, (HL)",   "LD D, A",      // $50
-    "LD E, B",      "LD E, C",      "LD E, D",      "LD E, E",      "LD E, H",      "LD E, L",      "LD E, (HL)",   "LD E, A",      // $58
-    "LD H, B",      "LD H, C",      "LD H, D",      "LD H, E",      "LD H, H",      "LD H, L",      "LD H, (HL)",   "LD H, A",      // $60
-    "LD L, B",      "LD L, C",      "LD L, D",      "LD L, E",      "LD L, H",      "LD L, L",      "LD L, (HL)",   "LD L, A",      // $68
-    "LD (HL), B",   "LD (HL), C",   "LD (HL), D",   "LD (HL), E",   "LD (HL), H",   "LD (HL), L",   "HALT",         "LD (HL), A",   // $70
-    "LD A, B",      "LD A, C",      "LD A, D",      "LD A, E",      "LD A, H",      "LD A, L",      "LD A, (HL)",   "LD A, A",      // $78
-    "ADD A, B",     "ADD A, C",     "ADD A, D",     "ADD A, E",     "ADD A, H",     "ADD A, L",     "ADD A, (HL)",  "ADD A, A",     // $80
-    "ADC A, B",     "ADC A, C",     "ADC A, D",     "ADC A, E",     "ADC A, H",     "ADC A, L",     "ADC A, (HL)",  "ADC A, A",     // $88
-    "SUB B",        "SUB C",        "SUB D",        "SUB E",        "SUB H",        "SUB L",        "SUB (HL)",     "SUB A",        // $90
-    "SBC B",        "SBC C",        "SBC D",        "SBC E",        "SBC H",        "SBC L",        "SBC (HL)",     "SBC A",        // $98
-    "AND B",        "AND C",        "AND D",        "AND E",        "AND H",        "AND L",        "AND (HL)",     "AND A",        // $A0
-    "XOR B",        "XOR C",        "XOR D",        "XOR E",        "XOR H",        "XOR L",        "XOR (HL)",     "XOR A",        // $A8
-    "OR B",         "OR C",         "OR D",         "OR E",         "OR H",         "OR L",         "OR (HL)",      "OR A",         // $B0
-    "CP B",         "CP C",         "CP D",         "CP E",         "CP H",         "CP L",         "CP (HL)",      "CP A",         // $B8
-    "RET NZ",       "POP BC",       "JP NZ, a16",   "JP a16",       "CALL NZ, a16", "PUSH BC",      "AND A, d8",    "RST 00",       // $C0
-    "RET Z",        "RET",          "JP Z, a16",    "PREFIX CB",    "CALL Z, a16",  "CALL a16",     "ADC A, d8",    "RST 08",       // $C8
-    "RET NC",       "POP DE",       "JP NC, a16",   "INVALID",      "CALL NC, a16", "PUSH DE",      "SUB d8",       "RST 10",       // $D0
-    "RET C",        "RETI",         "JP C, a16",    "INVALID",      "CALL C, a16",  "INVALID",      "SBC A, d8",    "RST 18",       // $D8
-    "LDH (a8), A",  "POP HL",       "LD (C), A",    "INVALID",      "INVALID",      "PUSH HL",      "AND d8",       "RST 20",       // $E0
-    "ADD SP, r8",   "JP (HL)",      "LD (a16), A",  "INVALID",      "INVALID",      "INVALID",      "XOR d8",       "RST 28",       // $E8
-    "LDH A, (a8)",  "POP AF",       "LD A, (C)",    "DI",           "INVALID",      "PUSH AF",      "OR d8",        "RST 30",       // $F0
-    "LD HL, SP+r8", "LD SP, HL",    "LD A, (a16)",  "EI",           "INVALID",      "INVALID",      "CP d8",        "RST 38"        // $F8
-];
-
-const OPCODE_LENGTH: [u8; 0x100] = [
-    0, 2, 0, 0, 0, 0, 1, 0, 2, 0, 0, 0, 0, 0, 1, 0,
-    1, 2, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 1, 0,
-    1, 2, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 1, 0,
-    1, 2, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 1, 0,
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    0, 0, 2, 2, 2, 0, 1, 0, 0, 0, 2, 0, 2, 2, 1, 0,
-    0, 0, 2, 0, 2, 0, 1, 0, 0, 0, 2, 0, 2, 0, 1, 0,
-    1, 0, 1, 0, 0, 0, 1, 0, 1, 0, 2, 0, 0, 0, 1, 0,
-    1, 0, 1, 0, 0, 0, 1, 0, 1, 0, 2, 0, 0, 0, 1, 0
-];
+impl Point {
+    /// ```
+    /// New
+    ///
+    /// Creates a break/watchpoint, dropping the bank if the address isn't banked
+    ///
+    /// Inputs:
+    ///     Bank, or None for any bank (Option<u16>)
+    ///     Address (u16)
+    ///
+    /// Output:
+    ///     Break/watchpoint (Point)
+    /// ```
+    pub fn new(bank: Option<u16>, addr: u16) -> Point {
+        let banked = (ROM_BANK_START..=ROM_BANK_STOP).contains(&addr) || (RAM_BANK_START..=RAM_BANK_STOP).contains(&addr);
+        Point {
+            bank: if banked { bank } else { None },
+            addr,
+        }
+    }
+
+    /// ```
+    /// Is mapped
+    ///
+    /// Whether the point's bank is the one currently mapped in
+    ///
+    /// Input:
+    ///     Reference to CPU (&Cpu)
+    ///
+    /// Output:
+    ///     Whether mapped (bool)
+    /// ```
+    pub fn is_mapped(&self, gb: &Cpu) -> bool {
+        match self.bank {
+            Some(bank) if self.addr >= RAM_BANK_START => { bank == gb.get_ram_bank() as u16 },
+            Some(bank) => { bank == gb.get_rom_bank() },
+            None => { true }
+        }
+    }
+}
 
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.bank {
+            Some(bank) => { write!(f, "{:02x}:{:04x}", bank, self.addr) },
+            None => { write!(f, "${:04x}", self.addr) }
+        }
+    }
+}
 
 // Tell Rust to STFU about camel cases
 #[allow(non_camel_case_types)]
 pub struct debugger {
-    breakpoints: Vec<u16>,
-    watchpoints: Vec<u16>
+    breakpoints: Vec<Point>,
+    watchpoints: Vec<Point>,
+    access_breaks: Vec<AccessBreak>,
+    symbols: Symbols
 }
 
 impl Default for debugger {
@@ -75,24 +113,105 @@ impl debugger {
     pub fn new() -> debugger {
         debugger {
             breakpoints: Vec::new(),
-            watchpoints: Vec::new()
+            watchpoints: Vec::new(),
+            access_breaks: Vec::new(),
+            symbols: Symbols::new()
+        }
+    }
+
+    /// ```
+    /// Set symbols
+    ///
+    /// Uses the given labels in disassembly and stack traces, and for setting breakpoints
+    ///
+    /// Input:
+    ///     Symbol table (Symbols)
+    /// ```
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    /// ```
+    /// Parse address
+    ///
+    /// Reads an address typed by the user, either a label or hex with an optional '$' or '0x'.
+    /// Labels are checked first, as names like "Add" are also valid hex.
+    ///
+    /// Input:
+    ///     Text to parse (&str)
+    ///
+    /// Output:
+    ///     Address, if valid (Option<u16>)
+    /// ```
+    pub fn parse_addr(&self, text: &str) -> Option<u16> {
+        if let Some((_, addr)) = self.symbols.get_addr(text) {
+            return Some(addr);
+        }
+
+        let digits = text.trim_start_matches('$').trim_start_matches("0x");
+        u16::from_str_radix(digits, 16).ok()
+    }
+
+    /// ```
+    /// Parse point
+    ///
+    /// Reads a break/watchpoint typed by the user, as a label, bank:address or plain address.
+    /// Labels keep the bank from the symbol file.
+    ///
+    /// Input:
+    ///     Text to parse (&str)
+    ///
+    /// Output:
+    ///     Break/watchpoint, if valid (Option<Point>)
+    /// ```
+    pub fn parse_point(&self, text: &str) -> Option<Point> {
+        if let Some((bank, addr)) = self.symbols.get_addr(text) {
+            return Some(Point::new(Some(bank), addr));
+        }
+
+        match text.split_once(':') {
+            Some((bank, addr)) => {
+                let bank = u16::from_str_radix(bank.trim_start_matches('$').trim_start_matches("0x"), 16).ok()?;
+                let addr = self.parse_addr(addr)?;
+                Some(Point::new(Some(bank), addr))
+            },
+            None => { self.parse_addr(text).map(|addr| Point::new(None, addr)) }
+        }
+    }
+
+    /// ```
+    /// Parse range
+    ///
+    /// Reads an address range typed by the user, as start-end, or a single address or label
+    ///
+    /// Input:
+    ///     Text to parse (&str)
+    ///
+    /// Output:
+    ///     First and last address, if valid (Option<(u16, u16)>)
+    /// ```
+    pub fn parse_range(&self, text: &str) -> Option<(u16, u16)> {
+        match text.split_once('-') {
+            Some((start, end)) => { Some((self.parse_addr(start)?, self.parse_addr(end)?)) },
+            None => { self.parse_addr(text).map(|addr| (addr, addr)) }
         }
     }
 
     /// ```
     /// Check breakpoints
     ///
-    /// Checks if any of the breakpoints have been hit
+    /// Checks if any of the breakpoints have been hit, in the currently mapped bank
     ///
     /// Input:
-    ///     Program counter (u16)
+    ///     Reference to CPU (&Cpu)
     ///
     /// Outputs:
     ///     Whether or not to break (bool)
     /// ```
-    pub fn check_break(&self, pc: u16) -> bool {
+    pub fn check_break(&self, gb: &Cpu) -> bool {
+        let pc = gb.get_pc();
         for bp in &self.breakpoints {
-            if *bp == pc {
+            if bp.addr == pc && bp.is_mapped(gb) {
                 return true;
             }
         }
@@ -117,17 +236,21 @@ impl debugger {
     /// Prints the debugger help message
     /// ```
     pub fn print_help(&self) {
-        println!("'b #' to break at that address");
+        println!("'b #' to break at that address or label, as bank:address for a single bank");
+        println!("'bt' to print a stack trace");
         println!("'c' to continue execution");
-        println!("'del #' to delete breakpoint at that address");
+        println!("'del #' to delete breakpoint at that address or label, or access breakpoints over that range");
         println!("'disass' to show disassembly of next 5 instructions");
         println!("'help' to print this message");
         println!("'info' to list break/watchpoints");
         println!("'n' to run to next instruction");
         println!("'p' to print 16 bytes at given RAM address (in hex)");
         println!("'q' to quit program");
+        println!("'rb #[-#]' to break on reads from that address range");
         println!("'reg' to list register contents");
-        println!("'watch #' to add (write) watchpoint at that address");
+        println!("'watch #' to add (write) watchpoint at that address, as bank:address for a single bank");
+        println!("'wb #[-#]' to break on writes to that address range, even of the same value");
+        println!("'xb #[-#]' to break on executing from that address range");
         println!();
     }
 
@@ -159,7 +282,7 @@ impl debugger {
         if !self.breakpoints.is_empty() {
             let mut breakstring = "Breakpoints:".to_string();
             for bp in &self.breakpoints {
-                breakstring = format!("{} ${:04x}", breakstring, bp);
+                breakstring = format!("{} {}", breakstring, bp);
             }
             println!("{}", breakstring);
         } else {
@@ -169,13 +292,20 @@ impl debugger {
         if !self.watchpoints.is_empty() {
             let mut watchstring = "Watchpoints:".to_string();
             for wp in &self.watchpoints {
-                watchstring = format!("{} ${:04x}", watchstring, wp);
+                watchstring = format!("{} {}", watchstring, wp);
             }
             println!("{}", watchstring);
         } else {
             println!("You have no watchpoints set");
         }
 
+        if !self.access_breaks.is_empty() {
+            println!("Access breakpoints:");
+            for ab in &self.access_breaks {
+                println!("  {}", ab);
+            }
+        }
+
         println!();
     }
 
@@ -186,10 +316,10 @@ impl debugger {
     /// Note: Doesn't check if breakpoint is already in list
     ///
     /// Input:
-    ///     Address to break (u16)
+    ///     Address to break, and its bank (Point)
     /// ```
-    pub fn add_break(&mut self, addr: u16) {
-        self.breakpoints.push(addr);
+    pub fn add_break(&mut self, point: Point) {
+        self.breakpoints.push(point);
     }
 
     /// ```
@@ -199,10 +329,40 @@ impl debugger {
     /// Note: Doesn't check if watchpoint is already in list
     ///
     /// Input:
-    ///     Address to watch (u16)
+    ///     Address to watch, and its bank (Point)
     /// ```
-    pub fn add_watch(&mut self, addr: u16) {
-        self.watchpoints.push(addr);
+    pub fn add_watch(&mut self, point: Point) {
+        self.watchpoints.push(point);
+    }
+
+    /// ```
+    /// Add access breakpoint
+    ///
+    /// Breaks when an instruction accesses memory in the given way, anywhere within a range
+    ///
+    /// Inputs:
+    ///     Reference to CPU, which checks the accesses (&mut Cpu)
+    ///     Type of access (AccessType)
+    ///     First and last address (u16, u16)
+    /// ```
+    pub fn add_access_break(&mut self, gb: &mut Cpu, kind: AccessType, range: (u16, u16)) {
+        self.access_breaks.push(AccessBreak::new(kind, range.0, range.1));
+        gb.set_access_breaks(self.access_breaks.clone());
+    }
+
+    /// ```
+    /// Delete access breakpoints
+    ///
+    /// Removes access breakpoints of any type over exactly the given range
+    ///
+    /// Inputs:
+    ///     Reference to CPU, which checks the accesses (&mut Cpu)
+    ///     First and last address (u16, u16)
+    /// ```
+    pub fn del_access_break(&mut self, gb: &mut Cpu, range: (u16, u16)) {
+        let (start, end) = (range.0.min(range.1), range.0.max(range.1));
+        self.access_breaks.retain(|ab| ab.start != start || ab.end != end);
+        gb.set_access_breaks(self.access_breaks.clone());
     }
 
     /// ```
@@ -216,9 +376,10 @@ impl debugger {
     /// ```
     pub fn print_ram(&self, addr: u16, gb: &Cpu) {
         // Print up to addr + 16, unless we go off the end
-        let end_addr = min(addr + 16, 0xFFFF);
+        let end_addr = min(addr as u32 + 16, 0x10000);
         let mut valstring = String::new();
-        for i in addr..end_addr {
+        for i in (addr as u32)..end_addr {
+            let i = i as u16;
             let val = gb.read_ram(i);
             valstring = format!("{} {:02x}", valstring, val);
         }
@@ -226,9 +387,9 @@ impl debugger {
         println!("${:04x}: {}", addr, valstring);
     }
 
-    pub fn del_break(&mut self, addr: u16) {
+    pub fn del_break(&mut self, point: Point) {
         for i in 0..self.breakpoints.len() {
-            if self.breakpoints[i] == addr {
+            if self.breakpoints[i] == point {
                 self.breakpoints.remove(i);
                 break;
             }
@@ -239,36 +400,94 @@ impl debugger {
     /// Disassemble
     ///
     /// Prints out next five instructions as GBz80 assembly
-    /// NOTE: This is still rough, only prints out instructions and not parameters
     ///
     /// Input:
-    ///     Refernce to CPU (&Cpu)
+    ///     Reference to CPU (&Cpu)
     /// ```
     pub fn disassemble(&self, gb: &Cpu) {
-        let mut pc = gb.get_pc();
-
-        // Print next 5 instructions
-        for _ in 0..5 {
-            let op = gb.read_ram(pc);
-            let op_name = OPCODE_NAMES[op as usize];
-            println!("${:04x} | {}", pc, op_name);
-            pc += OPCODE_LENGTH[op as usize] as u16 + 1;
+        let bank = gb.get_rom_bank();
+        let label = |addr: u16| self.symbols.get_label(addr, bank).map(|l| l.to_string());
+        for inst in disassemble(|addr| gb.read_ram(addr), gb.get_pc(), 5) {
+            if let Some(name) = label(inst.addr) {
+                println!("{}:", name);
+            }
+            println!("${:04x} | {}", inst.addr, inst.format_with(label));
+        }
+    }
+
+    /// ```
+    /// Print backtrace
+    ///
+    /// Prints the current location, then each call which led to it.
+    /// Calls are found by looking for return addresses on the stack which follow a CALL or RST,
+    /// so other data pushed there can occasionally look like a call.
+    ///
+    /// Input:
+    ///     Reference to CPU (&Cpu)
+    /// ```
+    pub fn print_backtrace(&self, gb: &Cpu) {
+        let bank = gb.get_rom_bank();
+        println!("#0  {}", self.describe_addr(gb.get_pc(), bank));
+
+        let mut depth = 1;
+        let mut sp = gb.get_sp();
+        for _ in 0..STACK_SCAN_WORDS {
+            if sp >= STACK_TOP || depth > BACKTRACE_DEPTH {
+                break;
+            }
+            let ret = (gb.read_ram(sp.wrapping_add(1)) as u16) << 8 | gb.read_ram(sp) as u16;
+            sp = sp.wrapping_add(2);
+
+            let call_site = if CALL_OPCODES.contains(&gb.read_ram(ret.wrapping_sub(CALL_LENGTH))) {
+                ret.wrapping_sub(CALL_LENGTH)
+            } else if gb.read_ram(ret.wrapping_sub(RST_LENGTH)) & RST_MASK == RST_MASK {
+                ret.wrapping_sub(RST_LENGTH)
+            } else {
+                continue;
+            };
+            println!("#{:<2} {}", depth, self.describe_addr(call_site, bank));
+            depth += 1;
         }
     }
 
-    pub fn get_watch_vals(&self, gb: &Cpu) -> HashMap<u16, u8> {
+    /// ```
+    /// Get watch values
+    ///
+    /// Records the current value of each watchpoint whose bank is mapped in
+    ///
+    /// Input:
+    ///     Reference to CPU (&Cpu)
+    ///
+    /// Output:
+    ///     Value at each watchpoint (HashMap<Point, u8>)
+    /// ```
+    pub fn get_watch_vals(&self, gb: &Cpu) -> HashMap<Point, u8> {
         let mut vals = HashMap::new();
         for wp in &self.watchpoints {
-            vals.insert(*wp, gb.read_ram(*wp));
+            if wp.is_mapped(gb) {
+                vals.insert(*wp, gb.read_ram(wp.addr));
+            }
         }
 
         vals
     }
 
-    pub fn check_watch(&self, gb: &Cpu, prev_map: HashMap<u16, u8>) -> bool {
+    /// ```
+    /// Check watchpoints
+    ///
+    /// Checks if a watched value has changed. Points in a bank which has since been switched out are skipped.
+    ///
+    /// Inputs:
+    ///     Reference to CPU (&Cpu)
+    ///     Values from before (HashMap<Point, u8>)
+    ///
+    /// Output:
+    ///     Whether or not to break (bool)
+    /// ```
+    pub fn check_watch(&self, gb: &Cpu, prev_map: HashMap<Point, u8>) -> bool {
         for wp in &self.watchpoints {
             if let Some(old) = prev_map.get(wp) {
-                if *old != gb.read_ram(*wp) {
+                if wp.is_mapped(gb) && *old != gb.read_ram(wp.addr) {
                     return true;
                 }
             }
@@ -276,4 +495,28 @@ impl debugger {
 
         false
     }
+
+    // ===================
+    // = Private methods =
+    // ===================
+
+    /// ```
+    /// Describe address
+    ///
+    /// Formats an address along with the nearest label before it, if any
+    ///
+    /// Inputs:
+    ///     Address (u16)
+    ///     Currently mapped ROM bank (u16)
+    ///
+    /// Output:
+    ///     Description, such as "$0155 Main.loop+$2" (String)
+    /// ```
+    fn describe_addr(&self, addr: u16, bank: u16) -> String {
+        match self.symbols.get_nearest_label(addr, bank) {
+            Some((name, 0)) =>      { format!("${:04x} {}", addr, name) },
+            Some((name, offset)) => { format!("${:04x} {}+${:x}", addr, name, offset) },
+            None =>                 { format!("${:04x}", addr) }
+        }
+    }
 }
@@ -0,0 +1,160 @@
+// RGBDS symbol files
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/*
+ * RGBDS symbol files
+ *
+ * rgblink's -n option writes a .sym file listing every label, one per line
+ * as bank:address, in hex, then the name. Comments start with ';'.
+ *
+ * ; File generated by rgblink
+ * 00:0150 Main
+ * 00:0153 Main.loop
+ * 01:4000 LoadTiles
+ *
+ * Only $4000-$7FFF is looked up by the current ROM bank. Elsewhere, a label
+ * in bank 0 is preferred, otherwise whichever bank has a label there.
+ */
+
+// =============
+// = Constants =
+// =============
+const COMMENT: char = ';';
+const SWITCH_BANK_START: u16 = 0x4000;
+const SWITCH_BANK_STOP: u16 = 0x7FFF;
+// ROM0, ROMX, VRAM, SRAM, WRAM, echo RAM, OAM, IO/HRAM
+const REGION_STARTS: [u16; 8] = [0x0000, 0x4000, 0x8000, 0xA000, 0xC000, 0xE000, 0xFE00, 0xFF00];
+
+#[derive(Default)]
+pub struct Symbols {
+    // Keyed by (bank, address), so labels are kept in address order within each bank
+    labels: BTreeMap<(u16, u16), String>,
+    addrs: BTreeMap<String, (u16, u16)>,
+    // The same locations keyed by (address, bank), to find which banks have a label at an address
+    banks: BTreeSet<(u16, u16)>,
+}
+
+impl Symbols {
+    pub fn new() -> Symbols {
+        Symbols::default()
+    }
+
+    /// ```
+    /// Parse
+    ///
+    /// Reads the contents of an RGBDS .sym file, skipping any lines it doesn't understand
+    ///
+    /// Input:
+    ///     Symbol file text (&str)
+    ///
+    /// Output:
+    ///     Symbol table (Symbols)
+    /// ```
+    pub fn parse(text: &str) -> Symbols {
+        let mut symbols = Symbols::new();
+        for line in text.lines() {
+            let line = line.split(COMMENT).next().unwrap_or("");
+            let mut words = line.split_whitespace();
+            let (location, name) = match (words.next(), words.next()) {
+                (Some(location), Some(name)) => { (location, name) },
+                _ => { continue; }
+            };
+
+            if let Some((bank, addr)) = location.split_once(':') {
+                let bank = u16::from_str_radix(bank, 16);
+                let addr = u16::from_str_radix(addr, 16);
+                if let (Ok(bank), Ok(addr)) = (bank, addr) {
+                    symbols.add(bank, addr, name);
+                }
+            }
+        }
+        symbols
+    }
+
+    /// ```
+    /// Add
+    ///
+    /// Adds a label. A second label at the same location replaces the first when looking up by address.
+    ///
+    /// Inputs:
+    ///     Bank number (u16)
+    ///     Address (u16)
+    ///     Label name (&str)
+    /// ```
+    pub fn add(&mut self, bank: u16, addr: u16, name: &str) {
+        self.labels.insert((bank, addr), name.to_string());
+        self.addrs.insert(name.to_string(), (bank, addr));
+        self.banks.insert((addr, bank));
+    }
+
+    pub fn len(&self) -> usize {
+        self.addrs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addrs.is_empty()
+    }
+
+    /// ```
+    /// Get address
+    ///
+    /// Finds where a label is
+    ///
+    /// Input:
+    ///     Label name (&str)
+    ///
+    /// Output:
+    ///     Bank and address, if the label exists (Option<(u16, u16)>)
+    /// ```
+    pub fn get_addr(&self, name: &str) -> Option<(u16, u16)> {
+        self.addrs.get(name).copied()
+    }
+
+    /// ```
+    /// Get label
+    ///
+    /// Finds the label at an address, as the CPU currently sees it
+    ///
+    /// Inputs:
+    ///     Address (u16)
+    ///     Currently mapped ROM bank (u16)
+    ///
+    /// Output:
+    ///     Label name, if there is one (Option<&str>)
+    /// ```
+    pub fn get_label(&self, addr: u16, rom_bank: u16) -> Option<&str> {
+        if is_switch_bank(addr) {
+            return self.labels.get(&(rom_bank, addr)).map(|s| s.as_str());
+        }
+
+        // Bank 0 sorts first, so is preferred
+        let (_, bank) = self.banks.range((addr, 0)..=(addr, u16::MAX)).next()?;
+        self.labels.get(&(*bank, addr)).map(|s| s.as_str())
+    }
+
+    /// ```
+    /// Get nearest label
+    ///
+    /// Finds the closest label at or before an address, within the same bank and memory region
+    ///
+    /// Inputs:
+    ///     Address (u16)
+    ///     Currently mapped ROM bank (u16)
+    ///
+    /// Output:
+    ///     Label name and how far past it the address is (Option<(&str, u16)>)
+    /// ```
+    pub fn get_nearest_label(&self, addr: u16, rom_bank: u16) -> Option<(&str, u16)> {
+        let bank = if is_switch_bank(addr) { rom_bank } else { 0 };
+        // A label in ROM shouldn't be used to describe RAM, and so on
+        let region_start = REGION_STARTS.iter().rev().find(|&&start| start <= addr).copied().unwrap_or(0);
+        self.labels.range((bank, region_start)..=(bank, addr)).next_back().map(|((_, a), name)| {
+            (name.as_str(), addr - a)
+        })
+    }
+}
+
+fn is_switch_bank(addr: u16) -> bool {
+    (SWITCH_BANK_START..=SWITCH_BANK_STOP).contains(&addr)
+}
@@ -1,5 +1,7 @@
 // Input/Output functions
 
+use crate::state::{StateError, StateReader, StateWriter};
+
 /*
  * Game Boy joypad layout
  * Address $FF00
@@ -47,6 +49,7 @@ impl Buttons {
     }
 }
 
+#[derive(Clone)]
 pub struct IO {
     btns: [bool; 8],
     get_btn_keys: bool,
@@ -115,6 +118,37 @@ impl IO {
         }
     }
 
+    /// ```
+    /// Save state
+    ///
+    /// Writes which button group is being polled to a save state.
+    /// Pressed buttons come from the player, so they aren't saved.
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bool(self.get_btn_keys);
+        state.write_bool(self.get_dir_keys);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores which button group is being polled from a save state
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.get_btn_keys = state.read_bool()?;
+        self.get_dir_keys = state.read_bool()?;
+        Ok(())
+    }
+
     /// ```
     /// Pack buttons
     ///
@@ -1,7 +1,17 @@
+pub mod apu;
 pub mod bus;
 pub mod cartridge;
 pub mod cpu;
 pub mod debug;
 pub mod io;
+pub mod link;
+pub mod memory;
+pub mod pacer;
+pub mod png;
 pub mod ppu;
+pub mod printer;
+pub mod rewind;
+pub mod runner;
+pub mod serial;
+pub mod state;
 pub mod utils;
@@ -0,0 +1,433 @@
+// Link cable between two emulators
+
+use crate::cpu::Cpu;
+use crate::serial::SerialDevice;
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/*
+ * Link cable
+ *
+ * Connects the serial ports of two emulators over any stream, such as a
+ * TCP or Unix socket. To keep transfers deterministic, both sides run in
+ * lockstep: every quantum of CPU cycles, each side sends the other a sync
+ * message and waits for the other's before continuing. Neither side can
+ * ever get more than one quantum ahead.
+ *
+ * Handshake, sent by both sides on connection
+ * Offset | Size | Info
+ * -------+------+----------------------------------
+ * 0      | 4    | Magic, "SBLK"
+ * 4      | 1    | Protocol version
+ * 5      | 4    | Quantum, in CPU cycles
+ *
+ * Sync message
+ * Offset | Size | Info
+ * -------+------+----------------------------------
+ * 0      | 1    | SB at the end of the quantum
+ * 1      | 1    | Flags, see below
+ * 2      | 1    | Byte sent by the transfer, if any
+ *
+ * Sync flags
+ * Bit | Info
+ * ----+-----------------------------------------------
+ * 0   | A transfer was clocked during the quantum
+ * 1   | The serial port was used, by a transfer or by waiting for one
+ *
+ * The side driving a transfer receives the other's SB from the previous
+ * sync, and the other side receives the byte at the start of the next
+ * quantum. Both happen at the same point on both sides, no matter how
+ * fast either host runs. The quantum should be no longer than a transfer
+ * (1024 cycles), so bytes sent back to back aren't merged.
+ *
+ * Each sync is a network round trip, so syncing less often is what keeps
+ * a link running at full speed. Once neither side has used the serial
+ * port for IDLE_QUANTA syncs in a row, both switch to quanta IDLE_QUANTA
+ * times as long, and back as soon as either side uses it again. Both see
+ * the same flags, so they always agree on when the next sync is.
+ *
+ * If the other side stops answering for too long, such as when it's paused
+ * or has vanished without closing the connection, the link is dropped.
+ */
+
+// =============
+// = Constants =
+// =============
+// One transfer at the normal clock, so about 1000 round trips a second while the
+// link is in use. Shorter quanta pass SB changes across sooner, but a LAN round
+// trip of even half a millisecond would then keep the game from reaching full speed.
+pub const DEFAULT_QUANTUM: u32 = 1024;
+// While the link is unused, the quantum grows to about a frame, so about 60 round trips a second
+const IDLE_QUANTA: u32 = 16;
+const LINK_MAGIC: &[u8; 4] = b"SBLK";
+const LINK_VERSION: u8 = 2;
+const HANDSHAKE_SIZE: usize = 9;
+const SYNC_SIZE: usize = 3;
+const SYNC_TRANSFER: u8 = 0b01;
+const SYNC_BUSY: u8 = 0b10;
+const DISCONNECTED: u8 = 0xFF;
+// Longest to wait for the other side before giving up on the link
+const LINK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Link<S: Read + Write> {
+    stream: S,
+    quantum: u32,
+    cycles: u32,
+    peer_sb: u8,
+    outgoing: Option<u8>,
+    incoming: Option<u8>,
+    // Whether this side has waited for a transfer since the last sync
+    polled: bool,
+    // Syncs in a row where neither side used the serial port
+    idle_syncs: u32,
+    connected: bool,
+}
+
+impl<S: Read + Write> Link<S> {
+    /// ```
+    /// New
+    ///
+    /// Starts a link over an already open connection, checking the other side agrees on the protocol
+    ///
+    /// Inputs:
+    ///     Connection to the other emulator (S)
+    ///     Cycles between syncs, which both sides must use (u32)
+    ///
+    /// Output:
+    ///     Link, or why the handshake failed (io::Result<Link>)
+    /// ```
+    pub fn new(mut stream: S, quantum: u32) -> io::Result<Link<S>> {
+        let mut handshake = Vec::with_capacity(HANDSHAKE_SIZE);
+        handshake.extend_from_slice(LINK_MAGIC);
+        handshake.push(LINK_VERSION);
+        handshake.extend_from_slice(&quantum.to_le_bytes());
+        stream.write_all(&handshake)?;
+        stream.flush()?;
+
+        let mut peer = [0; HANDSHAKE_SIZE];
+        stream.read_exact(&mut peer)?;
+        if peer[..] != handshake[..] {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Other side uses a different link protocol or quantum"));
+        }
+
+        Ok(Link {
+            stream,
+            quantum,
+            cycles: 0,
+            peer_sb: DISCONNECTED,
+            outgoing: None,
+            incoming: None,
+            polled: false,
+            idle_syncs: 0,
+            connected: true,
+        })
+    }
+
+    /// ```
+    /// Is connected
+    ///
+    /// Whether the other side is still there. Once lost, the link acts like an unplugged cable.
+    ///
+    /// Output:
+    ///     If link is connected (bool)
+    /// ```
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+}
+
+impl Link<TcpStream> {
+    /// ```
+    /// Connect
+    ///
+    /// Connects to another emulator listening for a link
+    ///
+    /// Input:
+    ///     Address of the other emulator, such as "localhost:8765" (A)
+    ///
+    /// Output:
+    ///     Link, or why it couldn't connect (io::Result<Link>)
+    /// ```
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<Link<TcpStream>> {
+        let stream = TcpStream::connect(addr)?;
+        Link::from_tcp(stream)
+    }
+
+    /// ```
+    /// Listen
+    ///
+    /// Waits for another emulator to connect on the given port
+    ///
+    /// Input:
+    ///     Port to listen on (u16)
+    ///
+    /// Output:
+    ///     Link, or why it couldn't connect (io::Result<Link>)
+    /// ```
+    pub fn listen(port: u16) -> io::Result<Link<TcpStream>> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let (stream, _) = listener.accept()?;
+        Link::from_tcp(stream)
+    }
+
+    /// ```
+    /// From TCP
+    ///
+    /// Sets up a newly opened connection for syncing, then starts the link over it
+    ///
+    /// Input:
+    ///     Connection to the other emulator (TcpStream)
+    ///
+    /// Output:
+    ///     Link, or why it couldn't connect (io::Result<Link>)
+    /// ```
+    fn from_tcp(stream: TcpStream) -> io::Result<Link<TcpStream>> {
+        // Sync messages are tiny, and waiting to batch them would stall both sides
+        stream.set_nodelay(true)?;
+        // Without a timeout, a peer which stops answering would freeze this side too
+        stream.set_read_timeout(Some(LINK_TIMEOUT))?;
+        stream.set_write_timeout(Some(LINK_TIMEOUT))?;
+        Link::new(stream, DEFAULT_QUANTUM)
+    }
+}
+
+impl<S: Read + Write> SerialDevice for Link<S> {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        if !self.connected {
+            return DISCONNECTED;
+        }
+
+        self.outgoing = Some(byte);
+        self.peer_sb
+    }
+
+    fn poll_external(&mut self, _byte: u8) -> Option<u8> {
+        self.polled = true;
+        self.incoming.take()
+    }
+
+    fn is_lockstep(&self) -> bool {
+        self.connected
+    }
+
+    fn tick(&mut self, cycles: u8, sb: u8) {
+        if !self.connected {
+            return;
+        }
+
+        self.cycles += cycles as u32;
+        let quantum = if self.idle_syncs >= IDLE_QUANTA { self.quantum * IDLE_QUANTA } else { self.quantum };
+        if self.cycles >= quantum {
+            self.cycles -= quantum;
+            if self.sync(sb).is_err() {
+                self.connected = false;
+                self.peer_sb = DISCONNECTED;
+                self.incoming = None;
+            }
+        }
+    }
+}
+
+// Private functions
+impl<S: Read + Write> Link<S> {
+    /// ```
+    /// Sync
+    ///
+    /// Swaps sync messages with the other side, waiting for it to reach the same point
+    ///
+    /// Input:
+    ///     Current value of SB (u8)
+    ///
+    /// Output:
+    ///     Whether the other side could be reached (io::Result<()>)
+    /// ```
+    fn sync(&mut self, sb: u8) -> io::Result<()> {
+        let mut flags = 0;
+        if self.outgoing.is_some() {
+            flags |= SYNC_TRANSFER | SYNC_BUSY;
+        }
+        if self.polled {
+            flags |= SYNC_BUSY;
+        }
+        let msg = [sb, flags, self.outgoing.take().unwrap_or(0)];
+        self.polled = false;
+        self.stream.write_all(&msg)?;
+        self.stream.flush()?;
+
+        let mut peer = [0; SYNC_SIZE];
+        self.stream.read_exact(&mut peer)?;
+        self.peer_sb = peer[0];
+        // A byte that wasn't waited for is lost, as on hardware
+        self.incoming = if (peer[1] & SYNC_TRANSFER) != 0 { Some(peer[2]) } else { None };
+
+        // Both sides see the same flags, so switch quantum at the same sync
+        if ((flags | peer[1]) & SYNC_BUSY) != 0 {
+            self.idle_syncs = 0;
+        } else {
+            self.idle_syncs = self.idle_syncs.saturating_add(1);
+        }
+        Ok(())
+    }
+}
+
+/*
+ * Linked pair
+ *
+ * Two emulators in the same process, with their serial ports wired
+ * directly together. Whichever is behind is always stepped next, so they
+ * never drift more than one instruction apart, and runs are perfectly
+ * repeatable. Bytes are swapped the moment a transfer starts, and a byte
+ * the other side isn't waiting for by its next step is lost, as on hardware.
+ */
+
+// The wire between the two ports, holding each side's SB and any byte sent to it
+struct Wire {
+    sb: [u8; 2],
+    incoming: [Option<u8>; 2],
+    // Whether each side has already had a chance to take its incoming byte
+    offered: [bool; 2],
+}
+
+// One end of the wire, plugged into a serial port
+struct WireEnd {
+    // Shared through a mutex, so either emulator can still be moved to another thread
+    wire: Arc<Mutex<Wire>>,
+    side: usize,
+}
+
+impl SerialDevice for WireEnd {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        let mut wire = self.wire.lock().unwrap();
+        let other = 1 - self.side;
+        wire.incoming[other] = Some(byte);
+        wire.offered[other] = false;
+        wire.sb[other]
+    }
+
+    fn poll_external(&mut self, _byte: u8) -> Option<u8> {
+        self.wire.lock().unwrap().incoming[self.side].take()
+    }
+
+    fn tick(&mut self, _cycles: u8, sb: u8) {
+        let mut wire = self.wire.lock().unwrap();
+        wire.sb[self.side] = sb;
+        // Polled straight after this tick, so a byte still here next tick wasn't waited for
+        if wire.offered[self.side] {
+            wire.incoming[self.side] = None;
+        }
+        wire.offered[self.side] = wire.incoming[self.side].is_some();
+    }
+
+    fn is_lockstep(&self) -> bool {
+        true
+    }
+}
+
+pub struct LinkedPair {
+    // Boxed, as two whole systems are too large to comfortably keep on the stack
+    cpus: [Box<Cpu>; 2],
+}
+
+impl LinkedPair {
+    /// ```
+    /// New
+    ///
+    /// Connects two emulators' serial ports together, replacing any devices already plugged in
+    ///
+    /// Inputs:
+    ///     First emulator (Cpu)
+    ///     Second emulator (Cpu)
+    ///
+    /// Output:
+    ///     Linked emulators (LinkedPair)
+    /// ```
+    pub fn new(mut first: Cpu, mut second: Cpu) -> LinkedPair {
+        let wire = Arc::new(Mutex::new(Wire {
+            sb: [DISCONNECTED; 2],
+            incoming: [None; 2],
+            offered: [false; 2],
+        }));
+        first.connect_serial(Box::new(WireEnd { wire: wire.clone(), side: 0 }));
+        second.connect_serial(Box::new(WireEnd { wire, side: 1 }));
+
+        LinkedPair { cpus: [Box::new(first), Box::new(second)] }
+    }
+
+    /// ```
+    /// Tick
+    ///
+    /// Performs one CPU operation on whichever emulator is behind
+    ///
+    /// Output:
+    ///     Whether each emulator has a frame to render ([bool; 2])
+    /// ```
+    pub fn tick(&mut self) -> [bool; 2] {
+        let mut draw = [false; 2];
+        let side = if self.cpus[0].get_cycles() <= self.cpus[1].get_cycles() { 0 } else { 1 };
+        draw[side] = self.cpus[side].tick();
+        draw
+    }
+
+    /// ```
+    /// Run frame
+    ///
+    /// Runs both emulators until each has finished a frame
+    /// ```
+    pub fn run_frame(&mut self) {
+        let mut drawn = [false; 2];
+        while !(drawn[0] && drawn[1]) {
+            let draw = self.tick();
+            drawn[0] |= draw[0];
+            drawn[1] |= draw[1];
+        }
+    }
+
+    /// ```
+    /// Run cycles
+    ///
+    /// Runs both emulators for at least the given number of M-cycles
+    ///
+    /// Input:
+    ///     Number of M-cycles to run (u64)
+    /// ```
+    pub fn run_cycles(&mut self, cycles: u64) {
+        let end = self.cpus[0].get_cycles().max(self.cpus[1].get_cycles()) + cycles;
+        while self.cpus[0].get_cycles() < end || self.cpus[1].get_cycles() < end {
+            self.tick();
+        }
+    }
+
+    pub fn first(&self) -> &Cpu {
+        &self.cpus[0]
+    }
+
+    pub fn first_mut(&mut self) -> &mut Cpu {
+        &mut self.cpus[0]
+    }
+
+    pub fn second(&self) -> &Cpu {
+        &self.cpus[1]
+    }
+
+    pub fn second_mut(&mut self) -> &mut Cpu {
+        &mut self.cpus[1]
+    }
+
+    /// ```
+    /// Into inner
+    ///
+    /// Unplugs the link, handing back both emulators
+    ///
+    /// Output:
+    ///     First and second emulators ((Box<Cpu>, Box<Cpu>))
+    /// ```
+    pub fn into_inner(self) -> (Box<Cpu>, Box<Cpu>) {
+        let [mut first, mut second] = self.cpus;
+        first.disconnect_serial();
+        second.disconnect_serial();
+        (first, second)
+    }
+}
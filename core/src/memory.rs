@@ -0,0 +1,218 @@
+// Memory interface
+
+use std::cell::RefCell;
+
+/// ```
+/// Memory interface
+///
+/// Anything the CPU can read and write through, such as the full Game Boy system bus,
+/// or a plain 64 KiB array for testing instructions on their own
+/// ```
+pub trait MemoryInterface {
+    /// ```
+    /// System IO
+    ///
+    /// Whether this is a Game Boy memory map, so the timer and CGB speed switch
+    /// registers are handled by the CPU rather than passed through
+    /// ```
+    const SYSTEM_IO: bool = false;
+
+    /// ```
+    /// Read RAM
+    ///
+    /// Returns the byte at the given address
+    ///
+    /// Input:
+    ///     Address to read (u16)
+    ///
+    /// Output:
+    ///     Byte at address (u8)
+    /// ```
+    fn read_ram(&self, addr: u16) -> u8;
+
+    /// ```
+    /// Write RAM
+    ///
+    /// Writes a byte to the given address
+    ///
+    /// Inputs:
+    ///     Address to write to (u16)
+    ///     Byte to write (u8)
+    ///
+    /// Output:
+    ///     Whether battery backed memory was changed (bool)
+    /// ```
+    fn write_ram(&mut self, addr: u16, val: u8) -> bool;
+
+    /// ```
+    /// Is CGB
+    ///
+    /// Whether Game Boy Color features, such as the speed switch, are available
+    ///
+    /// Output:
+    ///     Whether CGB features are enabled (bool)
+    /// ```
+    fn is_cgb(&self) -> bool {
+        false
+    }
+}
+
+/*
+ * Memory for running the CPU outside of a Game Boy
+ *
+ * Type          | Use
+ * --------------+----------------------------------------------------------------
+ * FlatMemory    | 64 KiB of plain RAM, with no memory mapped registers or banking
+ * RecordingBus  | Wraps any other memory, logging every read and write in order
+ */
+
+// =============
+// = Constants =
+// =============
+const FLAT_MEMORY_SIZE: usize = 0x10000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Access {
+    pub addr: u16,
+    pub val: u8,
+    pub kind: AccessKind,
+}
+
+pub struct FlatMemory {
+    ram: Vec<u8>,
+}
+
+impl Default for FlatMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FlatMemory {
+    /// ```
+    /// New
+    ///
+    /// Creates 64 KiB of memory, cleared to zero
+    ///
+    /// Output:
+    ///     Memory (FlatMemory)
+    /// ```
+    pub fn new() -> FlatMemory {
+        FlatMemory {
+            ram: vec![0; FLAT_MEMORY_SIZE],
+        }
+    }
+
+    /// ```
+    /// Load
+    ///
+    /// Copies data into memory, wrapping around at the end of the address space
+    ///
+    /// Inputs:
+    ///     Address to start at (u16)
+    ///     Data to copy (&[u8])
+    /// ```
+    pub fn load(&mut self, addr: u16, data: &[u8]) {
+        for (i, &byte) in data.iter().enumerate() {
+            let addr = addr.wrapping_add(i as u16);
+            self.ram[addr as usize] = byte;
+        }
+    }
+}
+
+impl MemoryInterface for FlatMemory {
+    fn read_ram(&self, addr: u16) -> u8 {
+        self.ram[addr as usize]
+    }
+
+    fn write_ram(&mut self, addr: u16, val: u8) -> bool {
+        self.ram[addr as usize] = val;
+        false
+    }
+}
+
+pub struct RecordingBus<B: MemoryInterface> {
+    inner: B,
+    accesses: RefCell<Vec<Access>>,
+}
+
+impl<B: MemoryInterface> RecordingBus<B> {
+    /// ```
+    /// New
+    ///
+    /// Wraps memory so every access to it is recorded
+    ///
+    /// Input:
+    ///     Memory to wrap (B)
+    ///
+    /// Output:
+    ///     Recording bus (RecordingBus<B>)
+    /// ```
+    pub fn new(inner: B) -> RecordingBus<B> {
+        RecordingBus {
+            inner,
+            accesses: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// ```
+    /// Get inner
+    ///
+    /// Returns the wrapped memory. Accesses made through it directly aren't recorded.
+    ///
+    /// Output:
+    ///     Wrapped memory (&B)
+    /// ```
+    pub fn get_inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// ```
+    /// Get inner mutably
+    ///
+    /// Returns the wrapped memory, for changing it without recording
+    ///
+    /// Output:
+    ///     Wrapped memory (&mut B)
+    /// ```
+    pub fn get_inner_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    /// ```
+    /// Take accesses
+    ///
+    /// Returns every access made since the last call, oldest first
+    ///
+    /// Output:
+    ///     Recorded accesses (Vec<Access>)
+    /// ```
+    pub fn take_accesses(&self) -> Vec<Access> {
+        self.accesses.take()
+    }
+}
+
+impl<B: MemoryInterface> MemoryInterface for RecordingBus<B> {
+    const SYSTEM_IO: bool = B::SYSTEM_IO;
+
+    fn read_ram(&self, addr: u16) -> u8 {
+        let val = self.inner.read_ram(addr);
+        self.accesses.borrow_mut().push(Access { addr, val, kind: AccessKind::Read });
+        val
+    }
+
+    fn write_ram(&mut self, addr: u16, val: u8) -> bool {
+        self.accesses.borrow_mut().push(Access { addr, val, kind: AccessKind::Write });
+        self.inner.write_ram(addr, val)
+    }
+
+    fn is_cgb(&self) -> bool {
+        self.inner.is_cgb()
+    }
+}
@@ -0,0 +1,124 @@
+use std::time::Duration;
+use wasm_timer::Instant;
+
+/*
+ * Frame pacing
+ *
+ * The Game Boy runs at 1 MiHz (in M-cycles), which works out to a frame rate
+ * of ~59.73 Hz, rather than the host's usual 60 Hz. The pacer tells the
+ * front end how long to wait before emulating more, using one of:
+ *
+ * Mode        | Paced by
+ * ------------+-----------------------------------------------------
+ * Audio       | How much audio is still waiting to be played
+ * WallClock   | Emulated cycles compared to elapsed real time
+ * Unthrottled | Nothing, runs as fast as possible
+ */
+
+// =============
+// = Constants =
+// =============
+pub const CPU_FREQ: u64 = 1_048_576; // In M-cycles
+// Amount of audio to keep queued in Audio mode, in milliseconds
+const AUDIO_LATENCY_MS: u64 = 50;
+// How far behind real time we can fall before giving up on catching up
+const MAX_LAG_MS: u64 = 250;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PaceMode {
+    Audio,
+    WallClock,
+    Unthrottled,
+}
+
+pub struct Pacer {
+    mode: PaceMode,
+    start_time: Instant,
+    start_cycles: u64,
+}
+
+impl Pacer {
+    pub fn new(mode: PaceMode) -> Pacer {
+        Pacer {
+            mode,
+            start_time: Instant::now(),
+            start_cycles: 0,
+        }
+    }
+
+    pub fn get_mode(&self) -> PaceMode {
+        self.mode
+    }
+
+    /// ```
+    /// Set mode
+    ///
+    /// Switches pacing mode, restarting the wall clock from the current cycle count
+    ///
+    /// Inputs:
+    ///     New pacing mode (PaceMode)
+    ///     Total emulated cycles so far (u64)
+    /// ```
+    pub fn set_mode(&mut self, mode: PaceMode, cycles: u64) {
+        self.mode = mode;
+        self.reset(cycles);
+    }
+
+    /// ```
+    /// Reset
+    ///
+    /// Restarts timing from now, such as after loading a game or unpausing
+    ///
+    /// Input:
+    ///     Total emulated cycles so far (u64)
+    /// ```
+    pub fn reset(&mut self, cycles: u64) {
+        self.start_time = Instant::now();
+        self.start_cycles = cycles;
+    }
+
+    /// ```
+    /// Get delay
+    ///
+    /// Calculates how long the front end should wait before emulating the next frame
+    ///
+    /// Inputs:
+    ///     Total emulated cycles so far (u64)
+    ///     Number of audio sample frames queued but not yet played (usize)
+    ///     Audio sample rate, in Hz (u32)
+    ///
+    /// Output:
+    ///     Time to wait (Duration)
+    /// ```
+    pub fn get_delay(&mut self, cycles: u64, queued_samples: usize, sample_rate: u32) -> Duration {
+        match self.mode {
+            PaceMode::Audio => {
+                if sample_rate == 0 {
+                    return Duration::from_secs(0);
+                }
+
+                let queued = Duration::from_micros(queued_samples as u64 * 1_000_000 / sample_rate as u64);
+                let latency = Duration::from_millis(AUDIO_LATENCY_MS);
+                queued.checked_sub(latency).unwrap_or_else(|| Duration::from_secs(0))
+            },
+            PaceMode::WallClock => {
+                let emulated_cycles = cycles.saturating_sub(self.start_cycles);
+                let emulated = Duration::from_micros(emulated_cycles * 1_000_000 / CPU_FREQ);
+                let elapsed = self.start_time.elapsed();
+
+                if emulated > elapsed {
+                    emulated - elapsed
+                } else {
+                    // If we've fallen too far behind (or were paused), don't try to catch up
+                    if elapsed - emulated > Duration::from_millis(MAX_LAG_MS) {
+                        self.reset(cycles);
+                    }
+                    Duration::from_secs(0)
+                }
+            },
+            PaceMode::Unthrottled => {
+                Duration::from_secs(0)
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+// PNG image encoding
+
+use crate::utils::crc32;
+
+/*
+ * PNG encoder
+ *
+ * Writes images without compression, which keeps the encoder tiny at the
+ * cost of larger files. The image data is still wrapped in a zlib stream,
+ * but made only of "stored" deflate blocks.
+ *
+ * File layout
+ * Chunk | Info
+ * ------+------------------------------------------
+ * -     | Signature
+ * IHDR  | Width, height and pixel format
+ * IDAT  | zlib stream of scanlines, each starting with a filter type (0: None)
+ * IEND  | End of image
+ *
+ * Each chunk is its length, four character type, data, and the CRC32 of
+ * the type and data.
+ */
+
+// =============
+// = Constants =
+// =============
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const BIT_DEPTH: u8 = 8;
+const ZLIB_HEADER: [u8; 2] = [0x78, 0x01];
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+const ADLER_MOD: u32 = 65521;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ColorType {
+    Gray,
+    Rgba,
+}
+
+impl ColorType {
+    fn get_id(&self) -> u8 {
+        match self {
+            ColorType::Gray => { 0 },
+            ColorType::Rgba => { 6 },
+        }
+    }
+
+    fn get_channels(&self) -> usize {
+        match self {
+            ColorType::Gray => { 1 },
+            ColorType::Rgba => { 4 },
+        }
+    }
+}
+
+/// ```
+/// Encode PNG
+///
+/// Converts raw pixels into a PNG file
+///
+/// Inputs:
+///     Image width, in pixels (usize)
+///     Image height, in pixels (usize)
+///     Pixel format (ColorType)
+///     Pixel data, row by row, 8 bits per channel (&[u8])
+///
+/// Output:
+///     PNG file data (Vec<u8>)
+/// ```
+pub fn encode_png(width: usize, height: usize, color: ColorType, pixels: &[u8]) -> Vec<u8> {
+    let mut png = PNG_SIGNATURE.to_vec();
+
+    let mut ihdr = Vec::new();
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    // Bit depth, color type, compression, filter, interlace
+    ihdr.extend_from_slice(&[BIT_DEPTH, color.get_id(), 0, 0, 0]);
+    write_chunk(&mut png, b"IHDR", &ihdr);
+
+    let stride = width * color.get_channels();
+    let mut scanlines = Vec::with_capacity((stride + 1) * height);
+    for row in pixels.chunks(stride).take(height) {
+        scanlines.push(0);
+        scanlines.extend_from_slice(row);
+    }
+    write_chunk(&mut png, b"IDAT", &zlib_store(&scanlines));
+    write_chunk(&mut png, b"IEND", &[]);
+
+    png
+}
+
+/// ```
+/// Write chunk
+///
+/// Appends a PNG chunk, with its length and checksum
+///
+/// Inputs:
+///     PNG file data (&mut Vec<u8>)
+///     Chunk type (&[u8; 4])
+///     Chunk contents (&[u8])
+/// ```
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// ```
+/// zlib store
+///
+/// Wraps data in a zlib stream without compressing it
+///
+/// Input:
+///     Data to wrap (&[u8])
+///
+/// Output:
+///     zlib stream (Vec<u8>)
+/// ```
+fn zlib_store(data: &[u8]) -> Vec<u8> {
+    let mut out = ZLIB_HEADER.to_vec();
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+
+    // Even empty data needs one final block
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// ```
+/// Adler-32
+///
+/// Calculates the checksum used by zlib streams
+///
+/// Input:
+///     Data to checksum (&[u8])
+///
+/// Output:
+///     Checksum (u32)
+/// ```
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+
+    for byte in data {
+        a = (a + *byte as u32) % ADLER_MOD;
+        b = (b + a) % ADLER_MOD;
+    }
+
+    (b << 16) | a
+}
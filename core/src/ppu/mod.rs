@@ -6,6 +6,7 @@ use palette::*;
 use sprite::{OAM_BYTE_SIZE, Sprite};
 use tile::{Tile, TILE_BYTES};
 use crate::cpu::clock::ModeTypes;
+use crate::state::{StateError, StateReader, StateWriter};
 use crate::utils::*;
 use std::ops::Range;
 
@@ -16,7 +17,8 @@ const MAP_SIZE: usize = 32; // In tiles
 const MAP_PIXELS: usize = MAP_SIZE * TILESIZE; // In pixels
 const VRAM_SIZE: usize = 0x8000;
 const VRAM_OFFSET: usize = 0x8000;
-const TILE_NUM: usize = 384;
+const VRAM_BANK_SIZE: usize = 0x2000;
+const TILE_NUM: usize = 384; // Per VRAM bank
 const OAM_SPR_NUM: usize = 40;
 const SPR_PER_LINE: usize = 10;
 
@@ -33,6 +35,11 @@ const OBP0: usize                    = 0xFF48 - VRAM_OFFSET;
 const OBP1: usize                    = 0xFF49 - VRAM_OFFSET;
 const WY: usize                      = 0xFF4A - VRAM_OFFSET;
 const WX: usize                      = 0xFF4B - VRAM_OFFSET;
+const VBK: usize                     = 0xFF4F - VRAM_OFFSET;
+const BCPS: usize                    = 0xFF68 - VRAM_OFFSET;
+const BCPD: usize                    = 0xFF69 - VRAM_OFFSET;
+const OCPS: usize                    = 0xFF6A - VRAM_OFFSET;
+const OCPD: usize                    = 0xFF6B - VRAM_OFFSET;
 
 // VRAM ranges
 const DISPLAY_RAM_RANGE: Range<usize> = (0x8000 - VRAM_OFFSET)..(0xA000 - VRAM_OFFSET);
@@ -60,13 +67,66 @@ const LYC_LY_FLAG_BIT: u8 =         2;
 // const OAM_INTERRUPT_BIT: u8 =       5;
 const LYC_LY_INTERRUPT_BIT: u8 =    6;
 
+/*
+ * CGB BG Map Attributes
+ *
+ * Stored in VRAM bank 1, at the same position as the tile number in bank 0
+ *
+ * Bit | Info
+ * ----+---------------------------------------
+ * 7   | BG-to-OAM priority
+ * 6   | Vertical flip
+ * 5   | Horizontal flip
+ * 4   | Unused
+ * 3   | Tile VRAM bank
+ * 2-0 | Background palette number
+ */
+const ATTR_PAL_MASK: u8     = 0b111;
+const ATTR_BANK_BIT: u8     = 3;
+const ATTR_X_FLIP_BIT: u8   = 5;
+const ATTR_Y_FLIP_BIT: u8   = 6;
+const ATTR_PRIORITY_BIT: u8 = 7;
+
+/*
+ * CGB Palette RAM
+ *
+ * Eight palettes of four colors each, for both background and objects.
+ * Each color is two bytes, little endian:
+ *
+ * Bit   | Info
+ * ------+------
+ * 14-10 | Blue
+ * 9-5   | Green
+ * 4-0   | Red
+ *
+ * BCPS/OCPS select the byte that BCPD/OCPD access. If bit 7 is set,
+ * the index increments after each write.
+ */
+const PAL_RAM_SIZE: usize = 64;
+const PAL_INDEX_MASK: u8  = 0x3F;
+const PAL_AUTO_INC_BIT: u8 = 7;
+const CGB_WHITE: u16      = 0x7FFF;
+
+// Marks background pixels which are drawn above sprites, in the line's background info
+const BG_PRIORITY_FLAG: u8 = 0x80;
+const BG_COLOR_MASK: u8    = 0b11;
+
+#[derive(Clone)]
 pub struct PPU {
     vram: [u8; VRAM_SIZE],
-    screen_buffer: [u8; SCREEN_HEIGHT * SCREEN_WIDTH],
-    tiles: [Tile; TILE_NUM],
+    vram_bank_1: [u8; VRAM_BANK_SIZE],
+    vram_bank: u8,
+    screen_buffer: [u16; SCREEN_HEIGHT * SCREEN_WIDTH],
+    tiles: [Tile; 2 * TILE_NUM],
     oam: [Sprite; OAM_SPR_NUM],
     last_wndw_line: Option<u8>,
     sys_pal: Palettes,
+    cgb: bool,
+    dmg_compat: bool,
+    bg_pal_ram: [u8; PAL_RAM_SIZE],
+    obj_pal_ram: [u8; PAL_RAM_SIZE],
+    bcps: u8,
+    ocps: u8,
 }
 
 impl Default for PPU {
@@ -82,11 +142,19 @@ impl PPU {
     pub fn new() -> PPU {
         PPU {
             vram: [0; VRAM_SIZE],
+            vram_bank_1: [0; VRAM_BANK_SIZE],
+            vram_bank: 0,
             screen_buffer: [0; SCREEN_HEIGHT * SCREEN_WIDTH],
-            tiles: [Tile::new(); TILE_NUM],
+            tiles: [Tile::new(); 2 * TILE_NUM],
             oam: [Sprite::new(); OAM_SPR_NUM],
             last_wndw_line: None,
             sys_pal: Palettes::GRAYSCALE,
+            cgb: false,
+            dmg_compat: false,
+            bg_pal_ram: [0xFF; PAL_RAM_SIZE],
+            obj_pal_ram: [0; PAL_RAM_SIZE],
+            bcps: 0,
+            ocps: 0,
         }
     }
 
@@ -102,21 +170,49 @@ impl PPU {
     pub fn write_vram(&mut self, raw_addr: u16, val: u8) {
         let addr = raw_addr - VRAM_OFFSET as u16;
 
-        if self.is_valid_status(raw_addr) {
-            // Update OAM objects if needed
-            if is_in_oam(addr) {
-                let relative_addr = addr - OAM_MEM;
-                let spr_num = relative_addr / OAM_BYTE_SIZE;
-                let byte_num = relative_addr % OAM_BYTE_SIZE;
-                self.oam[spr_num as usize].update_byte(byte_num, val);
-            } else if is_in_tile_set(addr) {
-                let offset = addr - TILE_SET;
-                let tile_num = offset / TILE_BYTES;
-                let byte_num = offset % TILE_BYTES;
-                self.tiles[tile_num as usize].update_byte(byte_num, val);
-            }
+        if !self.is_valid_status(raw_addr) {
+            return;
+        }
 
-            self.vram[addr as usize] = val;
+        match addr as usize {
+            VBK | BCPS | BCPD | OCPS | OCPD if !self.cgb => {
+                // CGB registers aren't present on DMG
+            },
+            VBK => {
+                self.vram_bank = val & 0b1;
+            },
+            BCPS => {
+                self.bcps = val;
+            },
+            BCPD => {
+                write_pal_ram(&mut self.bg_pal_ram, &mut self.bcps, val);
+            },
+            OCPS => {
+                self.ocps = val;
+            },
+            OCPD => {
+                write_pal_ram(&mut self.obj_pal_ram, &mut self.ocps, val);
+            },
+            _ => {
+                // Update OAM objects if needed
+                if is_in_oam(addr) {
+                    let relative_addr = addr - OAM_MEM;
+                    let spr_num = relative_addr / OAM_BYTE_SIZE;
+                    let byte_num = relative_addr % OAM_BYTE_SIZE;
+                    self.oam[spr_num as usize].update_byte(byte_num, val);
+                } else if is_in_tile_set(addr) {
+                    let offset = addr - TILE_SET;
+                    let tile_num = (offset / TILE_BYTES) as usize + (self.vram_bank as usize * TILE_NUM);
+                    let byte_num = offset % TILE_BYTES;
+                    self.tiles[tile_num].update_byte(byte_num, val);
+                }
+
+                if self.is_in_bank_1(addr) {
+                    self.vram_bank_1[addr as usize] = val;
+                } else {
+                    self.vram[addr as usize] = val;
+                }
+            }
         }
     }
 
@@ -133,7 +229,170 @@ impl PPU {
     /// ```
     pub fn read_vram(&self, raw_addr: u16) -> u8 {
         let addr = raw_addr - VRAM_OFFSET as u16;
-        self.vram[addr as usize]
+
+        match addr as usize {
+            VBK | BCPS | BCPD | OCPS | OCPD if !self.cgb => { 0xFF },
+            VBK =>  { 0xFE | self.vram_bank },
+            BCPS => { self.bcps | 0x40 },
+            BCPD => { self.bg_pal_ram[(self.bcps & PAL_INDEX_MASK) as usize] },
+            OCPS => { self.ocps | 0x40 },
+            OCPD => { self.obj_pal_ram[(self.ocps & PAL_INDEX_MASK) as usize] },
+            _ => {
+                if self.is_in_bank_1(addr) {
+                    self.vram_bank_1[addr as usize]
+                } else {
+                    self.vram[addr as usize]
+                }
+            }
+        }
+    }
+
+    /// ```
+    /// Set CGB
+    ///
+    /// Sets whether the PPU runs with Game Boy Color hardware
+    ///
+    /// Input:
+    ///     Whether to enable CGB features (bool)
+    /// ```
+    pub fn set_cgb(&mut self, cgb: bool) {
+        self.cgb = cgb;
+    }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes video memory, palettes and the current screen to a save state
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.vram);
+        state.write_bytes(&self.vram_bank_1);
+        state.write_u8(self.vram_bank);
+        for pixel in self.screen_buffer.iter() {
+            state.write_u16(*pixel);
+        }
+        state.write_bool(self.last_wndw_line.is_some());
+        state.write_u8(self.last_wndw_line.unwrap_or(0));
+        state.write_bool(self.cgb);
+        state.write_bool(self.dmg_compat);
+        state.write_bytes(&self.bg_pal_ram);
+        state.write_bytes(&self.obj_pal_ram);
+        state.write_u8(self.bcps);
+        state.write_u8(self.ocps);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Restores the PPU from a save state, then rebuilds the tile and sprite caches
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state could be read (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.read_bytes(&mut self.vram)?;
+        state.read_bytes(&mut self.vram_bank_1)?;
+        self.vram_bank = state.read_u8()?;
+        for pixel in self.screen_buffer.iter_mut() {
+            *pixel = state.read_u16()?;
+        }
+        let has_wndw_line = state.read_bool()?;
+        let wndw_line = state.read_u8()?;
+        self.last_wndw_line = if has_wndw_line { Some(wndw_line) } else { None };
+        self.cgb = state.read_bool()?;
+        self.dmg_compat = state.read_bool()?;
+        state.read_bytes(&mut self.bg_pal_ram)?;
+        state.read_bytes(&mut self.obj_pal_ram)?;
+        self.bcps = state.read_u8()?;
+        self.ocps = state.read_u8()?;
+
+        self.rebuild_caches();
+        Ok(())
+    }
+
+    /// ```
+    /// Get VRAM bank
+    ///
+    /// Returns the contents of one of the video RAM banks
+    ///
+    /// Input:
+    ///     Bank number, 0 or 1 (usize)
+    ///
+    /// Output:
+    ///     VRAM bank contents (&[u8])
+    /// ```
+    pub fn get_vram_bank(&self, bank: usize) -> &[u8] {
+        if bank == 0 {
+            &self.vram[DISPLAY_RAM_RANGE]
+        } else {
+            &self.vram_bank_1
+        }
+    }
+
+    /// ```
+    /// Set VRAM bank
+    ///
+    /// Replaces the contents of one of the video RAM banks, bypassing any access restrictions
+    ///
+    /// Inputs:
+    ///     Bank number, 0 or 1 (usize)
+    ///     New bank contents, up to 8 KiB (&[u8])
+    /// ```
+    pub fn set_vram_bank(&mut self, bank: usize, data: &[u8]) {
+        let len = data.len().min(VRAM_BANK_SIZE);
+        if bank == 0 {
+            self.vram[DISPLAY_RAM_RANGE.start..(DISPLAY_RAM_RANGE.start + len)].copy_from_slice(&data[..len]);
+        } else {
+            self.vram_bank_1[..len].copy_from_slice(&data[..len]);
+        }
+        self.rebuild_caches();
+    }
+
+    /// ```
+    /// Get palette RAM
+    ///
+    /// Returns the CGB background and object palette RAM
+    ///
+    /// Output:
+    ///     Background and object palette data (&[u8], &[u8])
+    /// ```
+    pub fn get_pal_ram(&self) -> (&[u8], &[u8]) {
+        (&self.bg_pal_ram, &self.obj_pal_ram)
+    }
+
+    /// ```
+    /// Set palette RAM
+    ///
+    /// Replaces the CGB background and object palette RAM
+    ///
+    /// Inputs:
+    ///     Background palette data, up to 64 bytes (&[u8])
+    ///     Object palette data, up to 64 bytes (&[u8])
+    /// ```
+    pub fn set_pal_ram(&mut self, bg: &[u8], obj: &[u8]) {
+        let bg_len = bg.len().min(PAL_RAM_SIZE);
+        let obj_len = obj.len().min(PAL_RAM_SIZE);
+        self.bg_pal_ram[..bg_len].copy_from_slice(&bg[..bg_len]);
+        self.obj_pal_ram[..obj_len].copy_from_slice(&obj[..obj_len]);
+    }
+
+    /// ```
+    /// Set DMG compatibility
+    ///
+    /// Sets whether a CGB is running a DMG game, coloring it with the CGB palettes
+    /// chosen by the boot ROM rather than the system palette
+    ///
+    /// Input:
+    ///     Whether to enable DMG compatibility mode (bool)
+    /// ```
+    pub fn set_dmg_compat(&mut self, compat: bool) {
+        self.dmg_compat = compat;
     }
 
     /// ```
@@ -177,18 +436,22 @@ impl PPU {
     pub fn render_scanline(&mut self) {
         // Render current scanline
         let line = self.vram[LY];
-        let mut pixel_row = [0; SCREEN_WIDTH];
+        let blank = if self.is_color() { CGB_WHITE } else { 0 };
+        let mut pixel_row = [blank; SCREEN_WIDTH];
+        // Color index and priority of the background pixels, used when drawing sprites
+        let mut bg_row = [0; SCREEN_WIDTH];
 
-        if self.is_bkgd_dspl() {
-            self.render_background_line(&mut pixel_row, line);
-        }
+        // On CGB, the background can't be disabled. Instead, it loses its priority over sprites
+        if self.is_bkgd_dspl() || self.cgb {
+            self.render_background_line(&mut pixel_row, &mut bg_row, line);
 
-        if self.is_wndw_dspl() {
-            self.render_wndw_line(&mut pixel_row, line);
+            if self.is_wndw_dspl() {
+                self.render_wndw_line(&mut pixel_row, &mut bg_row, line);
+            }
         }
 
         if self.is_sprt_dspl() {
-            self.render_sprite_line(&mut pixel_row, line);
+            self.render_sprite_line(&mut pixel_row, &bg_row, line);
         }
 
         // Copy this line of pixels into overall screen buffer
@@ -219,7 +482,8 @@ impl PPU {
     ///     Array of pixels to draw ([u8])
     /// ```
     pub fn render_screen(&self) -> [u8; DISP_SIZE] {
-        let mut map_array = [0; SCREEN_HEIGHT * SCREEN_WIDTH];
+        let blank = if self.is_color() { CGB_WHITE } else { 0 };
+        let mut map_array = [blank; SCREEN_HEIGHT * SCREEN_WIDTH];
         if self.is_lcd_dspl() {
             map_array.copy_from_slice(&self.screen_buffer);
         }
@@ -240,34 +504,22 @@ impl PPU {
     /// Renders the given scanline of the background layer
     ///
     /// Inputs:
-    ///     Array to load pixel data into (&[u8])
+    ///     Array to load pixel data into (&[u16])
+    ///     Array to load background color index and priority into (&[u8])
     ///     Scanline to render (u8)
     /// ```
-    fn render_background_line(&self, pixel_row: &mut [u8], line: u8) {
-        let tile_map = self.get_bkgd_tile_map();
-        let palette = self.get_bkgd_palette();
+    fn render_background_line(&self, pixel_row: &mut [u16], bg_row: &mut [u8], line: u8) {
+        let map_range = self.get_bkgd_tile_map();
         let screen_coords = self.get_scroll_coords();
 
         // Get the row of tiles containing our scanline
         let y = ((screen_coords.y as usize) + (line as usize)) % MAP_PIXELS;
-        let row = y % TILESIZE;
         let start_x = screen_coords.x as usize;
         for x in 0..SCREEN_WIDTH {
-            // Get coords for current tile
-            let map_x = ((start_x + x) % MAP_PIXELS) / TILESIZE;
-            let map_y = y / TILESIZE;
-            let index = map_y * MAP_SIZE + map_x;
-            // The tile indexes in the second tile pattern table ($8800-97ff) are signed
-            let tile_index = if self.get_bkgd_wndw_tile_set_index() == 0 {
-                (256 + (tile_map[index] as i8 as isize)) as usize
-            } else {
-                tile_map[index] as usize
-            };
-            let tile = &self.tiles[tile_index];
-            let col = (start_x + x) % TILESIZE;
-            let pixel = tile.get_row(row)[col];
-            let corrected_pixel = palette[pixel as usize];
-            pixel_row[x] = corrected_pixel;
+            let map_x = (start_x + x) % MAP_PIXELS;
+            let (color, info) = self.get_map_pixel(map_range.clone(), map_x, y);
+            pixel_row[x] = color;
+            bg_row[x] = info;
         }
     }
 
@@ -277,10 +529,11 @@ impl PPU {
     /// Renders the given scanline of the window layer
     ///
     /// Inputs:
-    ///     Array to load pixel data into (&[u8])
+    ///     Array to load pixel data into (&[u16])
+    ///     Array to load background color index and priority into (&[u8])
     ///     Scanline to render (u8)
     /// ```
-    fn render_wndw_line(&mut self, pixel_row: &mut [u8], line: u8) {
+    fn render_wndw_line(&mut self, pixel_row: &mut [u16], bg_row: &mut [u8], line: u8) {
         let wndw_coords = self.get_wndw_coords();
         // See below for why this is needed
         let line = if self.last_wndw_line.is_none() { line } else { self.last_wndw_line.unwrap() + 1 };
@@ -290,29 +543,16 @@ impl PPU {
             return;
         }
 
-        let wndw_map = self.get_wndw_tile_map();
-        let palette = self.get_bkgd_palette();
+        let map_range = self.get_wndw_tile_map();
 
         // Get the row of tiles containing our scanline
         let y = (line - wndw_coords.y) as usize;
-        let row = y % TILESIZE;
-        let map_y = y / TILESIZE;
         let start_x = wndw_coords.x as usize;
         for x in start_x..SCREEN_WIDTH {
-            // Get coords for current tile
-            let map_x = ((x - start_x) % MAP_PIXELS) / TILESIZE;
-            let index = map_y * MAP_SIZE + map_x;
-            // The tile indexes in the second tile pattern table ($8800-97ff) are signed
-            let tile_index = if self.get_bkgd_wndw_tile_set_index() == 0 {
-                (256 + (wndw_map[index] as i8 as isize)) as usize
-            } else {
-                wndw_map[index] as usize
-            };
-            let tile = &self.tiles[tile_index];
-            let col = (x - start_x) % TILESIZE;
-            let pixel = tile.get_row(row)[col];
-            let corrected_pixel = palette[pixel as usize];
-            pixel_row[x] = corrected_pixel;
+            let map_x = (x - start_x) % MAP_PIXELS;
+            let (color, info) = self.get_map_pixel(map_range.clone(), map_x, y);
+            pixel_row[x] = color;
+            bg_row[x] = info;
         }
 
         // The window layer has an odd edge case
@@ -322,19 +562,70 @@ impl PPU {
         self.last_wndw_line = Some(line);
     }
 
+    /// ```
+    /// Get map pixel
+    ///
+    /// Gets a pixel from the background or window tile map
+    ///
+    /// Inputs:
+    ///     VRAM range of the tile map (Range<usize>)
+    ///     X coordinate within the map, in pixels (usize)
+    ///     Y coordinate within the map, in pixels (usize)
+    ///
+    /// Output:
+    ///     Pixel color, and its color index and priority flag (u16, u8)
+    /// ```
+    fn get_map_pixel(&self, map_range: Range<usize>, map_x: usize, map_y: usize) -> (u16, u8) {
+        let index = map_range.start + (map_y / TILESIZE) * MAP_SIZE + (map_x / TILESIZE);
+        let tile_byte = self.vram[index];
+        // The tile indexes in the second tile pattern table ($8800-97ff) are signed
+        let tile_index = if self.get_bkgd_wndw_tile_set_index() == 0 {
+            (256 + (tile_byte as i8 as isize)) as usize
+        } else {
+            tile_byte as usize
+        };
+        let mut col = map_x % TILESIZE;
+        let mut row = map_y % TILESIZE;
+
+        if !self.cgb {
+            let pixel = self.tiles[tile_index].get_row(row)[col];
+            let shade = self.get_bkgd_palette()[pixel as usize];
+            let color = if self.dmg_compat { get_cgb_color(&self.bg_pal_ram, 0, shade) } else { shade as u16 };
+            return (color, pixel);
+        }
+
+        // CGB attributes are stored in the same place in the second VRAM bank
+        let attr = self.vram_bank_1[index];
+        if attr.get_bit(ATTR_X_FLIP_BIT) {
+            col = TILESIZE - col - 1;
+        }
+        if attr.get_bit(ATTR_Y_FLIP_BIT) {
+            row = TILESIZE - row - 1;
+        }
+        let bank_offset = if attr.get_bit(ATTR_BANK_BIT) { TILE_NUM } else { 0 };
+
+        let pixel = self.tiles[tile_index + bank_offset].get_row(row)[col];
+        let color = get_cgb_color(&self.bg_pal_ram, attr & ATTR_PAL_MASK, pixel);
+        let priority = if attr.get_bit(ATTR_PRIORITY_BIT) { BG_PRIORITY_FLAG } else { 0 };
+        (color, pixel | priority)
+    }
+
     /// ```
     /// Render Sprite Line
     ///
     /// Renders the given scanline of the sprite layer
     ///
     /// Inputs:
-    ///     Array to load pixel data into (&[u8])
+    ///     Array to load pixel data into (&[u16])
+    ///     Background color index and priority of each pixel (&[u8])
     ///     Scanline to render (u8)
     /// ```
-    fn render_sprite_line(&self, pixel_row: &mut [u8], line: u8) {
+    fn render_sprite_line(&self, pixel_row: &mut [u16], bg_row: &[u8], line: u8) {
         // Iterate through every sprite
         let sorted_sprites = self.sort_sprites();
         let is_8x16 = self.spr_are_8x16();
+        // On CGB, clearing LCDC bit 0 puts sprites above everything
+        let bg_has_priority = !self.cgb || self.is_bkgd_dspl();
         let mut sprites_drawn = 10;
         for spr in sorted_sprites {
             if !spr.contains_scanline(line, is_8x16) || !spr.is_onscreen() {
@@ -379,7 +670,8 @@ impl PPU {
                 spr.get_tile_num()
             };
 
-            let tile = &self.tiles[spr_num as usize];
+            let bank_offset = if self.cgb && spr.get_vram_bank() == 1 { TILE_NUM } else { 0 };
+            let tile = &self.tiles[spr_num as usize + bank_offset];
             let pixels = tile.get_row(row % TILESIZE);
             let spr_x = top_x as usize;
             for col in 0..TILESIZE {
@@ -391,19 +683,28 @@ impl PPU {
                 };
 
                 let pixel_x = spr_x.wrapping_add(x_offset);
-                // Move on if pixel is going to be drawn off-screen
-                if pixel_x >= SCREEN_WIDTH {
+                // Move on if pixel is going to be drawn off-screen, or is transparent
+                if pixel_x >= SCREEN_WIDTH || pixel == 0 {
                     continue;
                 }
 
-                let corrected_pixel = palette[pixel as usize];
-
                 // Only draw pixel if
-                // - Sprite is above background, and the pixel being drawn isn't transparent
-                // - Sprite is below background, and background has transparent color here
-                let should_draw = (above_bg && pixel != 0) || (!above_bg && pixel_row[pixel_x] == 0);
+                // - Sprite is above background, and the background tile doesn't have priority
+                // - Background has transparent color here
+                let bg_info = bg_row[pixel_x];
+                let bg_transparent = (bg_info & BG_COLOR_MASK) == 0;
+                let bg_above = (bg_info & BG_PRIORITY_FLAG) != 0;
+                let should_draw = !bg_has_priority || bg_transparent || (above_bg && !bg_above);
                 if should_draw {
-                    pixel_row[pixel_x] = corrected_pixel;
+                    pixel_row[pixel_x] = if self.cgb {
+                        get_cgb_color(&self.obj_pal_ram, spr.get_cgb_pal(), pixel)
+                    } else if self.dmg_compat {
+                        // OBP0 and OBP1 use the first two object palettes
+                        let pal_num = if spr.is_pal_0() { 0 } else { 1 };
+                        get_cgb_color(&self.obj_pal_ram, pal_num, palette[pixel as usize])
+                    } else {
+                        palette[pixel as usize] as u16
+                    };
                 }
             }
         }
@@ -415,12 +716,12 @@ impl PPU {
     /// Gets the pixel values for the pixels currently on screen
     ///
     /// Input:
-    ///     160x144 screen pixel array (&[u8])
+    ///     160x144 screen pixel array, either shades or 15-bit CGB colors (&[u16])
     ///
     /// Output:
     ///     RGB values for on-screen pixels ([u8])
     /// ```
-    fn get_color(&self, pixel_array: &[u8]) -> [u8; DISP_SIZE] {
+    fn get_color(&self, pixel_array: &[u16]) -> [u8; DISP_SIZE] {
         let mut rgb_screen = [0; DISP_SIZE];
         let pal = get_sys_pal(self.sys_pal);
         // Iterate through every visible pixel
@@ -430,7 +731,7 @@ impl PPU {
                 let pixel = pixel_array[index];
 
                 let view_index = index * COLOR_CHANNELS;
-                let color = pal[pixel as usize];
+                let color = if self.is_color() { rgb555_to_rgba(pixel) } else { pal[pixel as usize] };
                 rgb_screen[view_index..(color.len() + view_index)].clone_from_slice(&color[..]);
             }
         }
@@ -441,36 +742,36 @@ impl PPU {
     /// ```
     /// Get background tile map
     ///
-    /// Gets the pixel data for the background tiles
+    /// Gets the VRAM range of the background tile map
     ///
     /// Output:
-    ///     Slice of tilemap values (&[u8])
+    ///     Tilemap address range (Range<usize>)
     /// ```
-    fn get_bkgd_tile_map(&self) -> &[u8] {
+    fn get_bkgd_tile_map(&self) -> Range<usize> {
         // $00 for $9800-$9BFF
         // $01 for $9C00-$9FFF
         if self.get_bkgd_tile_map_index() == 0 {
-            &self.vram[TILE_MAP_0_RANGE]
+            TILE_MAP_0_RANGE
         } else {
-            &self.vram[TILE_MAP_1_RANGE]
+            TILE_MAP_1_RANGE
         }
     }
 
     /// ```
     /// Get window tile map
     ///
-    /// Gets the pixel data for the window tiles
+    /// Gets the VRAM range of the window tile map
     ///
     /// Output:
-    ///     Slice of tilemap values (&[u8])
+    ///     Tilemap address range (Range<usize>)
     /// ```
-    fn get_wndw_tile_map(&self) -> &[u8] {
+    fn get_wndw_tile_map(&self) -> Range<usize> {
         // $00 for $9800-$9BFF
         // $01 for $9C00-$9FFF
         if self.get_wndw_tile_map_index() == 0 {
-            &self.vram[TILE_MAP_0_RANGE]
+            TILE_MAP_0_RANGE
         } else {
-            &self.vram[TILE_MAP_1_RANGE]
+            TILE_MAP_1_RANGE
         }
     }
 
@@ -517,10 +818,13 @@ impl PPU {
         // In event of overlap, sprites are drawn
         // (on DMG) with the lowest x-coordinate on top.
         // If tie, lowest sprite number goes on top
+        // On CGB, only the sprite number matters
         let mut sprites = self.oam.to_vec();
         // Reverse the vector so that lower sprite number is earlier in a tie
         sprites.reverse();
-        sprites.sort_by(|a, b| b.get_coords().0.cmp(&a.get_coords().0));
+        if !self.cgb {
+            sprites.sort_by(|a, b| b.get_coords().0.cmp(&a.get_coords().0));
+        }
         sprites
     }
 
@@ -677,6 +981,55 @@ impl PPU {
         }
     }
 
+    /// ```
+    /// Rebuild caches
+    ///
+    /// Decodes every tile and sprite again from VRAM and OAM
+    /// ```
+    fn rebuild_caches(&mut self) {
+        let tile_set_len = TILE_NUM * TILE_BYTES as usize;
+        for offset in 0..tile_set_len {
+            let tile_num = offset / TILE_BYTES as usize;
+            let byte_num = (offset % TILE_BYTES as usize) as u16;
+            self.tiles[tile_num].update_byte(byte_num, self.vram[TILE_SET as usize + offset]);
+            self.tiles[tile_num + TILE_NUM].update_byte(byte_num, self.vram_bank_1[TILE_SET as usize + offset]);
+        }
+
+        let oam_len = OAM_SPR_NUM * OAM_BYTE_SIZE as usize;
+        for offset in 0..oam_len {
+            let spr_num = offset / OAM_BYTE_SIZE as usize;
+            let byte_num = (offset % OAM_BYTE_SIZE as usize) as u16;
+            self.oam[spr_num].update_byte(byte_num, self.vram[OAM_MEM as usize + offset]);
+        }
+    }
+
+    /// ```
+    /// Is color
+    ///
+    /// Whether the screen buffer holds CGB colors, rather than DMG shades
+    ///
+    /// Output:
+    ///     If screen is in color (bool)
+    /// ```
+    fn is_color(&self) -> bool {
+        self.cgb || self.dmg_compat
+    }
+
+    /// ```
+    /// Is in bank 1
+    ///
+    /// Whether the given address accesses the second VRAM bank
+    ///
+    /// Input:
+    ///     Address, relative to start of VRAM (u16)
+    ///
+    /// Output:
+    ///     If access goes to VRAM bank 1 (bool)
+    /// ```
+    fn is_in_bank_1(&self, addr: u16) -> bool {
+        self.cgb && self.vram_bank == 1 && DISPLAY_RAM_RANGE.contains(&(addr as usize))
+    }
+
     /// ```
     /// Is valid status
     ///
@@ -735,3 +1088,63 @@ fn is_in_oam(addr: u16) -> bool {
 fn is_in_tile_set(addr: u16) -> bool {
     addr <= TILE_SET_END
 }
+
+/// ```
+/// Write palette RAM
+///
+/// Writes to CGB palette RAM through BCPD/OCPD, incrementing the index if needed
+///
+/// Inputs:
+///     Palette RAM (&mut [u8])
+///     Index register, BCPS or OCPS (&mut u8)
+///     Value to write (u8)
+/// ```
+fn write_pal_ram(ram: &mut [u8], spec: &mut u8, val: u8) {
+    let index = *spec & PAL_INDEX_MASK;
+    ram[index as usize] = val;
+
+    if spec.get_bit(PAL_AUTO_INC_BIT) {
+        let next = (index + 1) & PAL_INDEX_MASK;
+        *spec = (*spec & !PAL_INDEX_MASK) | next;
+    }
+}
+
+/// ```
+/// Get CGB color
+///
+/// Looks up a color from CGB palette RAM
+///
+/// Inputs:
+///     Palette RAM (&[u8])
+///     Palette number, 0-7 (u8)
+///     Color index within palette, 0-3 (u8)
+///
+/// Output:
+///     15-bit color (u16)
+/// ```
+fn get_cgb_color(ram: &[u8], pal: u8, pixel: u8) -> u16 {
+    let index = 2 * (pal as usize * PAL_SIZE + pixel as usize);
+    let low = ram[index] as u16;
+    let high = ram[index + 1] as u16;
+    ((high << 8) | low) & CGB_WHITE
+}
+
+/// ```
+/// RGB555 to RGBA
+///
+/// Converts a 15-bit CGB color into 8 bits per channel
+///
+/// Input:
+///     15-bit color (u16)
+///
+/// Output:
+///     RGBA color ([u8])
+/// ```
+fn rgb555_to_rgba(color: u16) -> [u8; COLOR_CHANNELS] {
+    let scale = |c: u16| -> u8 {
+        let c = (c & 0x1F) as u8;
+        (c << 3) | (c >> 2)
+    };
+
+    [scale(color), scale(color >> 5), scale(color >> 10), 255]
+}
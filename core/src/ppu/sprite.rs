@@ -35,6 +35,8 @@ const PAL_NUM_BIT: u8 = 4;
 const X_FLIP_BIT: u8 = 5;
 const Y_FLIP_BIT: u8 = 6;
 const BG_PRIORITY_BIT: u8 = 7;
+const VRAM_BANK_BIT: u8 = 3;
+const CGB_PAL_MASK: u8 = 0b111;
 
 #[derive(Copy, Clone)]
 pub struct Sprite {
@@ -44,7 +46,9 @@ pub struct Sprite {
     above_bkgd: bool,
     x_flip: bool,
     y_flip: bool,
-    palette_0: bool
+    palette_0: bool,
+    vram_bank: u8,
+    cgb_pal: u8
 }
 
 impl Sprite {
@@ -56,7 +60,9 @@ impl Sprite {
             above_bkgd: true,
             x_flip: false,
             y_flip: false,
-            palette_0: true
+            palette_0: true,
+            vram_bank: 0,
+            cgb_pal: 0
         }
     }
 
@@ -171,6 +177,30 @@ impl Sprite {
     pub fn is_above_bkgd(&self) -> bool {
         self.above_bkgd
     }
+
+    /// ```
+    /// Get VRAM bank
+    ///
+    /// Which VRAM bank the sprite's tile is stored in (CGB only)
+    ///
+    /// Output:
+    ///     VRAM bank, 0 or 1 (u8)
+    /// ```
+    pub fn get_vram_bank(&self) -> u8 {
+        self.vram_bank
+    }
+
+    /// ```
+    /// Get CGB palette
+    ///
+    /// Which of the eight object palettes the sprite uses (CGB only)
+    ///
+    /// Output:
+    ///     Palette number, 0-7 (u8)
+    /// ```
+    pub fn get_cgb_pal(&self) -> u8 {
+        self.cgb_pal
+    }
 }
 
 impl Sprite {
@@ -213,7 +243,7 @@ impl Sprite {
     /// ```
     /// Parse OAM byte 4
     ///
-    /// Parses byte corresponding to X/Y flip, palette choice, and draw priority
+    /// Parses byte corresponding to X/Y flip, palette choice, draw priority, and VRAM bank
     ///
     /// Input:
     ///     Value to parse (u8)
@@ -223,5 +253,7 @@ impl Sprite {
         self.y_flip = val.get_bit(Y_FLIP_BIT);
         self.x_flip = val.get_bit(X_FLIP_BIT);
         self.palette_0 = !val.get_bit(PAL_NUM_BIT);
+        self.vram_bank = if val.get_bit(VRAM_BANK_BIT) { 1 } else { 0 };
+        self.cgb_pal = val & CGB_PAL_MASK;
     }
 }
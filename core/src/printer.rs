@@ -0,0 +1,464 @@
+// Game Boy Printer
+
+use crate::png::{ColorType, encode_png};
+use crate::serial::SerialDevice;
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/*
+ * Game Boy Printer
+ *
+ * The Game Boy always drives the clock, sending the printer packets:
+ *
+ * Offset | Size | Info
+ * -------+------+----------------------------------
+ * 0      | 2    | Magic, $88 $33
+ * 2      | 1    | Command
+ * 3      | 1    | Compression (0: None, 1: Run-length)
+ * 4      | 2    | Data length
+ * 6      | -    | Data
+ * -      | 2    | Checksum, the sum of everything from command to data
+ * -      | 1    | Printer replies $81, to show it's connected
+ * -      | 1    | Printer replies with its status
+ *
+ * Command | Info
+ * --------+------------------------------------------------
+ * $01     | Init, clearing any image data
+ * $02     | Print, with sheets, margins, palette and exposure
+ * $04     | Data, two rows of tiles, or the end of the image if empty
+ * $0F     | Status, asking how the printer is doing
+ *
+ * Status
+ * Bit | Info
+ * ----+---------------------------
+ * 7   | Low battery
+ * 6   | Other error
+ * 5   | Paper jam
+ * 4   | Packet error
+ * 3   | Image data waiting to be printed
+ * 2   | Image data full
+ * 1   | Printing
+ * 0   | Checksum error
+ *
+ * Compressed data is a series of runs. A control byte with bit 7 clear is
+ * followed by (n + 1) bytes to copy. With bit 7 set, the next byte is
+ * repeated ((n & $7F) + 2) times.
+ *
+ * Image data is 2bpp tiles, 20 to a row, so each data packet adds
+ * 160x16 pixels. The print palette maps each color to a shade, in the
+ * same way as BGP.
+ *
+ * Print data
+ * Offset | Info
+ * -------+-----------------------------------------------
+ * 0      | Number of sheets, 0 to only feed paper
+ * 1      | Margins, lines fed before (high nibble) and after (low nibble)
+ * 2      | Palette
+ * 3      | Exposure, $00-$7F, from 25% lighter to 25% darker than $40
+ *
+ * Games often print long images in strips with no margin between them,
+ * so each print is added to the current printout, after its top margin.
+ * The printout is only finished once a print with a bottom margin ends it.
+ */
+
+// =============
+// = Constants =
+// =============
+const MAGIC_1: u8 = 0x88;
+const MAGIC_2: u8 = 0x33;
+const ALIVE: u8 = 0x81;
+
+const CMD_INIT: u8 = 0x01;
+const CMD_PRINT: u8 = 0x02;
+const CMD_DATA: u8 = 0x04;
+
+const STATUS_CHECKSUM: u8 = 0b0000_0001;
+const STATUS_PRINTING: u8 = 0b0000_0010;
+const STATUS_FULL: u8 = 0b0000_0100;
+const STATUS_UNPRINTED: u8 = 0b0000_1000;
+
+const COMPRESSED_RUN_FLAG: u8 = 0x80;
+const PRINT_SHEETS_INDEX: usize = 0;
+const PRINT_MARGINS_INDEX: usize = 1;
+const PRINT_PALETTE_INDEX: usize = 2;
+const PRINT_EXPOSURE_INDEX: usize = 3;
+const DEFAULT_PALETTE: u8 = 0xE4;
+const DEFAULT_EXPOSURE: u8 = 0x40;
+const EXPOSURE_MASK: u8 = 0x7F;
+
+pub const PRINT_WIDTH: usize = 160;
+const TILE_SIZE: usize = 8;
+const TILE_BYTES: usize = 16;
+const TILES_PER_ROW: usize = PRINT_WIDTH / TILE_SIZE;
+// Largest image the printer can hold, nine data packets
+const MAX_IMAGE_BYTES: usize = 9 * 0x280;
+// Status checks answered as busy after printing, so games see it working
+const PRINT_BUSY_POLLS: u8 = 4;
+const SHADES: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+const PAPER: u8 = SHADES[0];
+// Blank paper fed for each line of margin, in pixel rows
+const MARGIN_ROWS: usize = 8;
+// Longest printout kept before it's finished anyway, in pixel rows
+const MAX_PRINTOUT_ROWS: usize = 8192;
+
+#[derive(Copy, Clone, PartialEq)]
+enum PacketState {
+    Magic1,
+    Magic2,
+    Command,
+    Compression,
+    LenLow,
+    LenHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status,
+}
+
+pub struct Printer {
+    state: PacketState,
+    command: u8,
+    compressed: bool,
+    len: u16,
+    data: Vec<u8>,
+    checksum: u16,
+    sum: u16,
+    status: u8,
+    busy_polls: u8,
+    image: Vec<u8>,
+    printout: Vec<u8>,
+    output: Option<String>,
+    last_print: Option<Vec<u8>>,
+}
+
+impl Printer {
+    /// ```
+    /// New
+    ///
+    /// Creates a printer, which saves each finished printout as a PNG
+    ///
+    /// Input:
+    ///     Path prefix for printed images, such as the ROM's path, or None to not save (Option<String>)
+    ///
+    /// Output:
+    ///     Printer (Printer)
+    /// ```
+    pub fn new(output: Option<String>) -> Printer {
+        Printer {
+            state: PacketState::Magic1,
+            command: 0,
+            compressed: false,
+            len: 0,
+            data: Vec::new(),
+            checksum: 0,
+            sum: 0,
+            status: 0,
+            busy_polls: 0,
+            image: Vec::new(),
+            printout: Vec::new(),
+            output,
+            last_print: None,
+        }
+    }
+
+    /// ```
+    /// Get last print
+    ///
+    /// Returns the most recently finished printout, one grayscale byte per pixel, 160 pixels wide
+    ///
+    /// Output:
+    ///     Printed pixels, if anything has been printed (Option<&[u8]>)
+    /// ```
+    pub fn get_last_print(&self) -> Option<&[u8]> {
+        self.last_print.as_deref()
+    }
+}
+
+impl SerialDevice for Printer {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        let mut reply = 0x00;
+
+        self.state = match self.state {
+            PacketState::Magic1 => {
+                if byte == MAGIC_1 { PacketState::Magic2 } else { PacketState::Magic1 }
+            },
+            PacketState::Magic2 => {
+                if byte == MAGIC_2 { PacketState::Command } else { PacketState::Magic1 }
+            },
+            PacketState::Command => {
+                self.command = byte;
+                self.sum = byte as u16;
+                PacketState::Compression
+            },
+            PacketState::Compression => {
+                self.compressed = byte != 0;
+                self.sum = self.sum.wrapping_add(byte as u16);
+                PacketState::LenLow
+            },
+            PacketState::LenLow => {
+                self.len = byte as u16;
+                self.sum = self.sum.wrapping_add(byte as u16);
+                PacketState::LenHigh
+            },
+            PacketState::LenHigh => {
+                self.len |= (byte as u16) << 8;
+                self.sum = self.sum.wrapping_add(byte as u16);
+                self.data.clear();
+                if self.len == 0 { PacketState::ChecksumLow } else { PacketState::Data }
+            },
+            PacketState::Data => {
+                self.data.push(byte);
+                self.sum = self.sum.wrapping_add(byte as u16);
+                if self.data.len() >= self.len as usize { PacketState::ChecksumLow } else { PacketState::Data }
+            },
+            PacketState::ChecksumLow => {
+                self.checksum = byte as u16;
+                PacketState::ChecksumHigh
+            },
+            PacketState::ChecksumHigh => {
+                self.checksum |= (byte as u16) << 8;
+                PacketState::Alive
+            },
+            PacketState::Alive => {
+                reply = ALIVE;
+                PacketState::Status
+            },
+            PacketState::Status => {
+                self.run_command();
+                reply = self.get_status();
+                PacketState::Magic1
+            }
+        };
+
+        reply
+    }
+}
+
+// Private functions
+impl Printer {
+    /// ```
+    /// Run command
+    ///
+    /// Acts on a fully received packet
+    /// ```
+    fn run_command(&mut self) {
+        if self.checksum != self.sum {
+            self.status |= STATUS_CHECKSUM;
+            return;
+        }
+        self.status &= !STATUS_CHECKSUM;
+
+        match self.command {
+            CMD_INIT => {
+                self.image.clear();
+                self.status = 0;
+                self.busy_polls = 0;
+            },
+            CMD_DATA => {
+                let data = if self.compressed { decompress(&self.data) } else { self.data.clone() };
+                let room = MAX_IMAGE_BYTES - self.image.len();
+                self.image.extend(data.iter().take(room));
+                if !self.image.is_empty() {
+                    self.status |= STATUS_UNPRINTED;
+                }
+                if self.image.len() >= MAX_IMAGE_BYTES {
+                    self.status |= STATUS_FULL;
+                }
+            },
+            CMD_PRINT => {
+                let sheets = self.data.get(PRINT_SHEETS_INDEX).copied().unwrap_or(1);
+                let margins = self.data.get(PRINT_MARGINS_INDEX).copied().unwrap_or(0);
+                let palette = self.data.get(PRINT_PALETTE_INDEX).copied().unwrap_or(DEFAULT_PALETTE);
+                let exposure = self.data.get(PRINT_EXPOSURE_INDEX).copied().unwrap_or(DEFAULT_EXPOSURE);
+                self.print(sheets, margins, palette, exposure);
+                self.image.clear();
+                self.status &= !(STATUS_UNPRINTED | STATUS_FULL);
+                self.busy_polls = PRINT_BUSY_POLLS;
+            },
+            _ => {
+                // Status and anything unknown just report back
+            }
+        }
+    }
+
+    /// ```
+    /// Get status
+    ///
+    /// Gets the status byte to reply with, counting down how long to appear busy for
+    ///
+    /// Output:
+    ///     Status (u8)
+    /// ```
+    fn get_status(&mut self) -> u8 {
+        if self.busy_polls > 0 {
+            self.busy_polls -= 1;
+            self.status | STATUS_PRINTING
+        } else {
+            self.status
+        }
+    }
+
+    /// ```
+    /// Print
+    ///
+    /// Adds the received image to the printout, finishing it if there's a bottom margin
+    ///
+    /// Inputs:
+    ///     Number of copies to print (u8)
+    ///     Margins, before in the high nibble and after in the low (u8)
+    ///     Print palette, in BGP format (u8)
+    ///     Exposure, $00-$7F (u8)
+    /// ```
+    fn print(&mut self, sheets: u8, margins: u8, palette: u8, exposure: u8) {
+        let top = (margins >> 4) as usize;
+        let bottom = margins & 0x0F;
+
+        let fed = self.printout.len() + top * MARGIN_ROWS * PRINT_WIDTH;
+        self.printout.resize(fed, PAPER);
+        let strip = self.render(palette, exposure);
+        for _ in 0..sheets {
+            self.printout.extend_from_slice(&strip);
+        }
+
+        if bottom != 0 || self.printout.len() >= MAX_PRINTOUT_ROWS * PRINT_WIDTH {
+            self.finish_printout();
+        }
+    }
+
+    /// ```
+    /// Finish printout
+    ///
+    /// Saves the current printout, and starts a new one
+    /// ```
+    fn finish_printout(&mut self) {
+        let pixels = std::mem::take(&mut self.printout);
+        // Only paper was fed
+        if pixels.iter().all(|&p| p == PAPER) {
+            return;
+        }
+
+        if let Some(prefix) = &self.output {
+            save_print(prefix, pixels.len() / PRINT_WIDTH, &pixels);
+        }
+        self.last_print = Some(pixels);
+    }
+
+    /// ```
+    /// Render
+    ///
+    /// Converts the received tiles into an image
+    ///
+    /// Inputs:
+    ///     Print palette, in BGP format (u8)
+    ///     Exposure, $00-$7F (u8)
+    ///
+    /// Output:
+    ///     Grayscale pixels, 160 wide (Vec<u8>)
+    /// ```
+    fn render(&self, palette: u8, exposure: u8) -> Vec<u8> {
+        let rows = self.image.len() / (TILES_PER_ROW * TILE_BYTES);
+        let height = rows * TILE_SIZE;
+        let mut pixels = vec![0; PRINT_WIDTH * height];
+
+        // Any partial row of tiles is left off
+        for (tile_num, tile) in self.image.chunks_exact(TILE_BYTES).take(rows * TILES_PER_ROW).enumerate() {
+            let tile_x = (tile_num % TILES_PER_ROW) * TILE_SIZE;
+            let tile_y = (tile_num / TILES_PER_ROW) * TILE_SIZE;
+            for y in 0..TILE_SIZE {
+                let low = tile[2 * y];
+                let high = tile[2 * y + 1];
+                for x in 0..TILE_SIZE {
+                    let bit = 7 - x;
+                    let color = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+                    let shade = (palette >> (2 * color)) & 0b11;
+                    pixels[(tile_y + y) * PRINT_WIDTH + tile_x + x] = expose(SHADES[shade as usize], exposure);
+                }
+            }
+        }
+
+        pixels
+    }
+}
+
+/// ```
+/// Expose
+///
+/// Darkens or lightens a shade, as the print head's heat would
+///
+/// Inputs:
+///     Shade (u8)
+///     Exposure, where $40 leaves the shade as is (u8)
+///
+/// Output:
+///     Adjusted shade (u8)
+/// ```
+fn expose(shade: u8, exposure: u8) -> u8 {
+    // Scales darkness from 3/4 at $00 to just under 5/4 at $7F
+    let scale = 3 * 0x80 + 2 * (exposure & EXPOSURE_MASK) as u32;
+    let darkness = (PAPER - shade) as u32 * scale / 0x200;
+    PAPER - darkness.min(PAPER as u32) as u8
+}
+
+/// ```
+/// Decompress
+///
+/// Expands run-length encoded image data
+///
+/// Input:
+///     Compressed data (&[u8])
+///
+/// Output:
+///     Image data (Vec<u8>)
+/// ```
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let ctrl = data[i];
+        i += 1;
+        if (ctrl & COMPRESSED_RUN_FLAG) != 0 {
+            let len = (ctrl & !COMPRESSED_RUN_FLAG) as usize + 2;
+            if let Some(&byte) = data.get(i) {
+                out.extend(std::iter::repeat_n(byte, len));
+            }
+            i += 1;
+        } else {
+            let len = ctrl as usize + 1;
+            let end = (i + len).min(data.len());
+            out.extend_from_slice(&data[i..end]);
+            i = end;
+        }
+    }
+
+    out
+}
+
+/// ```
+/// Save print
+///
+/// Writes a printed image to the first free "<prefix>-print<N>.png"
+///
+/// Inputs:
+///     Path prefix (&str)
+///     Image height, in pixels (usize)
+///     Grayscale pixels (&[u8])
+/// ```
+fn save_print(prefix: &str, height: usize, pixels: &[u8]) {
+    let png = encode_png(PRINT_WIDTH, height, ColorType::Gray, pixels);
+
+    let mut num = 1;
+    let mut filename = format!("{}-print{}.png", prefix, num);
+    while Path::new(&filename).exists() {
+        num += 1;
+        filename = format!("{}-print{}.png", prefix, num);
+    }
+
+    // A failed print shouldn't stop the game
+    if let Ok(mut file) = File::create(filename) {
+        let _ = file.write_all(&png);
+    }
+}
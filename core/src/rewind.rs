@@ -0,0 +1,245 @@
+use std::collections::VecDeque;
+
+/*
+ * Rewind buffer
+ *
+ * Holds recent save states, so play can be stepped backwards. Only the
+ * newest snapshot is kept whole. Each older snapshot is stored as the
+ * difference from the one after it: the two are XORed together, leaving
+ * zeroes wherever nothing changed, which are then run-length encoded.
+ * Most of memory doesn't change between frames, so this shrinks each
+ * snapshot down to a few KiB.
+ *
+ * Delta encoding
+ * Offset | Size | Info
+ * -------+------+----------------------------------
+ * 0      | 4    | Length of the older snapshot
+ * 4      | -    | Runs, each starting with a control byte
+ *
+ * Control byte
+ * Bit | Info
+ * ----+-------------------------------------------------
+ * 7   | Run type (0: Literal bytes follow, 1: Zeroes)
+ * 6-0 | Run length, minus 1
+ *
+ * Once the memory budget is used up, the oldest snapshots are dropped.
+ */
+
+// =============
+// = Constants =
+// =============
+pub const DEFAULT_BUDGET: usize = 16 * 1024 * 1024;
+pub const DEFAULT_INTERVAL: u32 = 4;
+const ZERO_RUN_FLAG: u8 = 0x80;
+const MAX_RUN: usize = 0x80;
+const LEN_SIZE: usize = 4;
+
+pub struct Rewind {
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+    delta_size: usize,
+    budget: usize,
+    interval: u32,
+    frames: u32,
+}
+
+impl Rewind {
+    /// ```
+    /// New
+    ///
+    /// Creates an empty rewind buffer
+    ///
+    /// Inputs:
+    ///     Maximum memory to use for snapshots, in bytes (usize)
+    ///     Number of frames between snapshots (u32)
+    ///
+    /// Output:
+    ///     Rewind buffer (Rewind)
+    /// ```
+    pub fn new(budget: usize, interval: u32) -> Rewind {
+        Rewind {
+            latest: None,
+            deltas: VecDeque::new(),
+            delta_size: 0,
+            budget,
+            interval: interval.max(1),
+            frames: 0,
+        }
+    }
+
+    /// ```
+    /// Next frame
+    ///
+    /// Counts a frame, and returns whether a snapshot should be taken
+    ///
+    /// Output:
+    ///     If a snapshot is due (bool)
+    /// ```
+    pub fn next_frame(&mut self) -> bool {
+        self.frames += 1;
+        if self.frames >= self.interval {
+            self.frames = 0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// ```
+    /// Push
+    ///
+    /// Adds a new snapshot, compressing the previous one against it
+    ///
+    /// Input:
+    ///     Save state data (&[u8])
+    /// ```
+    pub fn push(&mut self, state: &[u8]) {
+        if let Some(prev) = self.latest.take() {
+            let delta = encode_delta(&prev, state);
+            self.delta_size += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.latest = Some(state.to_vec());
+
+        // Drop the oldest snapshots until we're back under budget
+        while self.get_size() > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => { self.delta_size -= delta.len(); },
+                None => { break; }
+            }
+        }
+    }
+
+    /// ```
+    /// Pop
+    ///
+    /// Removes the newest snapshot, rebuilding the one before it
+    ///
+    /// Output:
+    ///     Newest save state, if any (Option<Vec<u8>>)
+    /// ```
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let state = self.latest.take()?;
+        if let Some(delta) = self.deltas.pop_back() {
+            self.delta_size -= delta.len();
+            self.latest = Some(decode_delta(&delta, &state));
+        }
+        // Restart the count, so the next snapshot is a full interval after the restored one
+        self.frames = 0;
+        Some(state)
+    }
+
+    /// ```
+    /// Clear
+    ///
+    /// Removes all snapshots, such as when a different game is loaded
+    /// ```
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.delta_size = 0;
+        self.frames = 0;
+    }
+
+    /// ```
+    /// Length
+    ///
+    /// Number of snapshots held
+    ///
+    /// Output:
+    ///     Snapshot count (usize)
+    /// ```
+    pub fn len(&self) -> usize {
+        match self.latest {
+            Some(_) => { self.deltas.len() + 1 },
+            None => { 0 }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    /// ```
+    /// Get size
+    ///
+    /// Memory used by all snapshots, in bytes
+    ///
+    /// Output:
+    ///     Buffer size (usize)
+    /// ```
+    pub fn get_size(&self) -> usize {
+        self.delta_size + self.latest.as_ref().map_or(0, |s| s.len())
+    }
+}
+
+/// ```
+/// Encode delta
+///
+/// Compresses a snapshot against the one which came after it
+///
+/// Inputs:
+///     Older snapshot (&[u8])
+///     Newer snapshot (&[u8])
+///
+/// Output:
+///     Compressed difference (Vec<u8>)
+/// ```
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&(old.len() as u32).to_le_bytes());
+
+    let xor: Vec<u8> = old.iter().enumerate().map(|(i, b)| b ^ new.get(i).unwrap_or(&0)).collect();
+    let mut i = 0;
+    while i < xor.len() {
+        let zeroes = xor[i..].iter().take(MAX_RUN).take_while(|&&b| b == 0).count();
+        if zeroes > 0 {
+            out.push(ZERO_RUN_FLAG | (zeroes - 1) as u8);
+            i += zeroes;
+        } else {
+            // Take literal bytes until the next zero
+            let literals = xor[i..].iter().take(MAX_RUN).take_while(|&&b| b != 0).count();
+            out.push((literals - 1) as u8);
+            out.extend_from_slice(&xor[i..(i + literals)]);
+            i += literals;
+        }
+    }
+
+    out
+}
+
+/// ```
+/// Decode delta
+///
+/// Rebuilds a snapshot from the one after it and their compressed difference
+///
+/// Inputs:
+///     Compressed difference (&[u8])
+///     Newer snapshot (&[u8])
+///
+/// Output:
+///     Older snapshot (Vec<u8>)
+/// ```
+fn decode_delta(delta: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut len_bytes = [0; LEN_SIZE];
+    len_bytes.copy_from_slice(&delta[..LEN_SIZE]);
+    let len = u32::from_le_bytes(len_bytes) as usize;
+
+    let mut old: Vec<u8> = (0..len).map(|i| *new.get(i).unwrap_or(&0)).collect();
+    let mut i = 0;
+    let mut pos = LEN_SIZE;
+    while pos < delta.len() {
+        let ctrl = delta[pos];
+        let run = (ctrl & !ZERO_RUN_FLAG) as usize + 1;
+        pos += 1;
+        if (ctrl & ZERO_RUN_FLAG) == 0 {
+            for (byte, diff) in old[i..(i + run)].iter_mut().zip(&delta[pos..(pos + run)]) {
+                *byte ^= diff;
+            }
+            pos += run;
+        }
+        i += run;
+    }
+
+    old
+}
@@ -0,0 +1,297 @@
+// Test ROM runner
+
+use crate::cpu::{Cpu, Regs};
+
+/*
+ * Test ROM runner
+ *
+ * Runs a test ROM without any display until it reports a result, using
+ * whichever detectors match how that ROM reports.
+ *
+ * Detector   | Stops when                                      | Result
+ * -----------+-------------------------------------------------+---------------------------------
+ * serial     | Serial output contains the expected text        | Pass
+ * blargg_ram | Cart RAM holds Blargg's signature and a status  | Pass if the status is 0
+ * mooneye    | LD B,B runs, Mooneye's "test done" signal       | Pass if B-L hold Fibonacci numbers
+ * pc_loop    | An instruction keeps jumping to itself          | Pass, unless waiting on serial
+ * -          | The frame limit is reached                      | Fail, unless no detectors are set
+ *
+ * Blargg's cart RAM results
+ * Address     | Info
+ * ------------+-------------------------------------------
+ * $A000       | Status, $80 while running, then the result code
+ * $A001-$A003 | Signature, $DE $B0 $61
+ * $A004       | Result text, zero terminated
+ *
+ * Waiting in a loop for an interrupt looks the same as being stuck, so a
+ * loop only counts once nothing but interrupts has run for a while. Code
+ * running with less on the stack than the loop had is taken to be an
+ * interrupt handler, anything else means the game has moved on.
+ */
+
+// =============
+// = Constants =
+// =============
+pub const DEFAULT_MAX_FRAMES: u32 = 60 * 60;
+pub const BLARGG_PASS_TEXT: &str = "Passed";
+
+// Mooneye's tests all finish within a few seconds
+const MOONEYE_MAX_FRAMES: u32 = 60 * 10;
+
+const LD_B_B: u8 = 0x40;
+const MOONEYE_PASS: [u8; 6] = [3, 5, 8, 13, 21, 34];
+const MOONEYE_REGS: [Regs; 6] = [Regs::B, Regs::C, Regs::D, Regs::E, Regs::H, Regs::L];
+
+const BLARGG_STATUS_ADDR: u16 = 0xA000;
+const BLARGG_SIGNATURE_ADDR: u16 = 0xA001;
+const BLARGG_TEXT_ADDR: u16 = 0xA004;
+const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const BLARGG_RUNNING: u8 = 0x80;
+const BLARGG_TEXT_MAX: u16 = 0x400;
+
+const LOOP_FRAMES: u32 = 10;
+
+pub enum Outcome {
+    Pass(String),
+    Fail(String),
+}
+
+impl Outcome {
+    pub fn is_pass(&self) -> bool {
+        matches!(self, Outcome::Pass(_))
+    }
+
+    pub fn get_reason(&self) -> &str {
+        match self {
+            Outcome::Pass(reason) | Outcome::Fail(reason) => { reason }
+        }
+    }
+}
+
+pub struct TestReport {
+    pub outcome: Outcome,
+    pub serial: Vec<u8>,
+    pub frames: u32,
+}
+
+pub struct TestRunner {
+    pub max_frames: u32,
+    pub serial: Option<String>,
+    pub blargg_ram: bool,
+    pub mooneye: bool,
+    pub pc_loop: bool,
+}
+
+impl TestRunner {
+    /// ```
+    /// New
+    ///
+    /// Creates a runner with no detectors, which simply runs for the given number of frames
+    ///
+    /// Input:
+    ///     Frames to run before giving up (u32)
+    ///
+    /// Output:
+    ///     Test runner (TestRunner)
+    /// ```
+    pub fn new(max_frames: u32) -> TestRunner {
+        TestRunner {
+            max_frames,
+            serial: None,
+            blargg_ram: false,
+            mooneye: false,
+            pc_loop: false,
+        }
+    }
+
+    /// ```
+    /// Blargg
+    ///
+    /// Creates a runner for Blargg's tests, which report over serial or in cart RAM
+    ///
+    /// Output:
+    ///     Test runner (TestRunner)
+    /// ```
+    pub fn blargg() -> TestRunner {
+        TestRunner {
+            serial: Some(BLARGG_PASS_TEXT.to_string()),
+            blargg_ram: true,
+            pc_loop: true,
+            ..TestRunner::new(DEFAULT_MAX_FRAMES)
+        }
+    }
+
+    /// ```
+    /// Mooneye
+    ///
+    /// Creates a runner for the Mooneye test suite
+    ///
+    /// Output:
+    ///     Test runner (TestRunner)
+    /// ```
+    pub fn mooneye() -> TestRunner {
+        TestRunner {
+            mooneye: true,
+            ..TestRunner::new(MOONEYE_MAX_FRAMES)
+        }
+    }
+
+    /// ```
+    /// Run
+    ///
+    /// Runs the game until a detector fires, or the frame limit is reached. Audio is turned off, as nothing plays it
+    ///
+    /// Input:
+    ///     Emulator, with test ROM loaded (&mut Cpu)
+    ///
+    /// Output:
+    ///     Result, serial output and frames run (TestReport)
+    /// ```
+    pub fn run(&self, gb: &mut Cpu) -> TestReport {
+        let mut serial = Vec::new();
+        let mut frames = 0;
+        // Loop address, stack pointer, and frame it was first seen
+        let mut stuck: Option<(u16, u16, u32)> = None;
+        gb.set_serial_capture(true);
+        gb.set_audio_enabled(false);
+
+        while frames < self.max_frames {
+            let pc = gb.get_pc();
+            let sp = gb.get_sp();
+            let executing = !gb.is_halted();
+            let opcode = gb.read_ram(pc);
+
+            let new_frame = gb.tick();
+
+            let new_output = gb.take_serial_output();
+            if !new_output.is_empty() {
+                serial.extend_from_slice(&new_output);
+                if let Some(expected) = &self.serial {
+                    if contains(&serial, expected.as_bytes()) {
+                        let outcome = Outcome::Pass("serial output matched".to_string());
+                        return TestReport { outcome, serial, frames };
+                    }
+                }
+            }
+
+            if new_frame {
+                frames += 1;
+                if self.blargg_ram {
+                    if let Some(outcome) = check_blargg_ram(gb) {
+                        return TestReport { outcome, serial, frames };
+                    }
+                }
+            }
+
+            if !executing {
+                continue;
+            }
+
+            if self.mooneye && opcode == LD_B_B {
+                let outcome = check_mooneye_regs(gb);
+                return TestReport { outcome, serial, frames };
+            }
+
+            if self.pc_loop && gb.get_pc() == pc {
+                match stuck {
+                    Some((loop_pc, loop_sp, since)) if loop_pc == pc && loop_sp == sp => {
+                        if frames - since >= LOOP_FRAMES {
+                            let outcome = if self.serial.is_some() {
+                                Outcome::Fail(format!("stuck at ${:04X} without matching serial output", pc))
+                            } else {
+                                Outcome::Pass(format!("stuck at ${:04X}", pc))
+                            };
+                            return TestReport { outcome, serial, frames };
+                        }
+                    },
+                    _ => {
+                        stuck = Some((pc, sp, frames));
+                    }
+                }
+            } else if let Some((loop_pc, loop_sp, _)) = stuck {
+                if pc != loop_pc && sp >= loop_sp {
+                    stuck = None;
+                }
+            }
+        }
+
+        let outcome = if self.serial.is_some() || self.blargg_ram || self.mooneye || self.pc_loop {
+            Outcome::Fail(format!("timed out after {} frames", frames))
+        } else {
+            Outcome::Pass(format!("ran {} frames", frames))
+        };
+        TestReport { outcome, serial, frames }
+    }
+}
+
+/// ```
+/// Check Blargg RAM
+///
+/// Looks for a finished result in cart RAM
+///
+/// Input:
+///     Emulator (&Cpu)
+///
+/// Output:
+///     Result, if the test has finished (Option<Outcome>)
+/// ```
+fn check_blargg_ram(gb: &Cpu) -> Option<Outcome> {
+    let signature_found = BLARGG_SIGNATURE.iter().enumerate().all(|(i, &b)| {
+        gb.read_ram(BLARGG_SIGNATURE_ADDR + i as u16) == b
+    });
+    let status = gb.read_ram(BLARGG_STATUS_ADDR);
+    if !signature_found || status == BLARGG_RUNNING {
+        return None;
+    }
+
+    let mut text = Vec::new();
+    for addr in BLARGG_TEXT_ADDR..(BLARGG_TEXT_ADDR + BLARGG_TEXT_MAX) {
+        match gb.read_ram(addr) {
+            0 => { break; },
+            byte => { text.push(byte); }
+        }
+    }
+    let text = String::from_utf8_lossy(&text).trim().replace('\n', " ");
+
+    if status == 0 {
+        Some(Outcome::Pass(format!("cart RAM result: {}", text)))
+    } else {
+        Some(Outcome::Fail(format!("cart RAM result ${:02X}: {}", status, text)))
+    }
+}
+
+/// ```
+/// Check Mooneye registers
+///
+/// Checks the registers Mooneye's tests set once done
+///
+/// Input:
+///     Emulator (&Cpu)
+///
+/// Output:
+///     Pass if they hold the Fibonacci numbers (Outcome)
+/// ```
+fn check_mooneye_regs(gb: &Cpu) -> Outcome {
+    let regs: Vec<u8> = MOONEYE_REGS.iter().map(|r| gb.get_reg(*r)).collect();
+    if regs[..] == MOONEYE_PASS {
+        Outcome::Pass("Mooneye registers".to_string())
+    } else {
+        Outcome::Fail(format!("Mooneye registers {:02X?}", regs))
+    }
+}
+
+/// ```
+/// Contains
+///
+/// Whether some bytes appear anywhere within others
+///
+/// Inputs:
+///     Bytes to search (&[u8])
+///     Bytes to find (&[u8])
+///
+/// Output:
+///     Whether found (bool)
+/// ```
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty() || haystack.windows(needle.len()).any(|w| w == needle)
+}
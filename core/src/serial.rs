@@ -0,0 +1,403 @@
+// Serial port
+
+use crate::state::{StateError, StateReader, StateWriter};
+use crate::utils::ModifyBits;
+
+/*
+ * Serial transfer registers
+ *
+ * SB - $FF01
+ * Byte to send, replaced bit by bit with the byte received
+ *
+ * SC - $FF02
+ * Bit | Info
+ * ----+----------------------------------------------------
+ * 7   | Transfer start / in progress
+ * 1   | Clock speed, CGB only (0: Normal, 1: Fast)
+ * 0   | Clock source (0: External, 1: Internal)
+ *
+ * With the internal clock, this Game Boy drives the transfer, shifting one
+ * bit every 128 M-cycles (8192 Hz), or every 4 in CGB fast mode. With the
+ * external clock, it waits for the other side to drive the transfer.
+ * Either way, bits go out most significant first while the other side's
+ * come in, and the serial interrupt fires once all eight have moved.
+ *
+ * With nothing connected, every bit read is 1.
+ *
+ * Every byte sent can also be captured, as test ROMs report their results
+ * this way.
+ */
+
+// =============
+// = Constants =
+// =============
+pub const SB: u16 = 0xFF01;
+pub const SC: u16 = 0xFF02;
+
+const SC_START_BIT: u8 = 7;
+const SC_SPEED_BIT: u8 = 1;
+const SC_CLOCK_BIT: u8 = 0;
+const SC_DMG_MASK: u8 = 0b1000_0001;
+const SC_CGB_MASK: u8 = 0b1000_0011;
+
+const BIT_CYCLES: u16 = 128;
+const FAST_BIT_CYCLES: u16 = 4;
+const BITS_PER_BYTE: u8 = 8;
+const DISCONNECTED: u8 = 0xFF;
+
+/// ```
+/// Serial device
+///
+/// Anything which can be plugged into the serial port, such as a link cable or printer
+/// ```
+pub trait SerialDevice {
+    /// ```
+    /// Transfer
+    ///
+    /// Called when this Game Boy starts a transfer using its internal clock
+    ///
+    /// Input:
+    ///     Byte being sent (u8)
+    ///
+    /// Output:
+    ///     Byte received in exchange (u8)
+    /// ```
+    fn transfer(&mut self, byte: u8) -> u8;
+
+    /// ```
+    /// Poll external
+    ///
+    /// Called while this Game Boy waits for the other side to clock a transfer.
+    /// Devices which never drive the clock can leave this as is.
+    ///
+    /// Input:
+    ///     Byte waiting to be sent (u8)
+    ///
+    /// Output:
+    ///     Byte received, if the other side started a transfer (Option<u8>)
+    /// ```
+    fn poll_external(&mut self, _byte: u8) -> Option<u8> {
+        None
+    }
+
+    /// ```
+    /// Tick
+    ///
+    /// Called every CPU step, for devices which need to keep time with the Game Boy
+    ///
+    /// Inputs:
+    ///     Number of CPU cycles elapsed (u8)
+    ///     Current value of SB (u8)
+    /// ```
+    fn tick(&mut self, _cycles: u8, _sb: u8) {}
+
+    /// ```
+    /// Is lockstep
+    ///
+    /// Whether this device keeps another emulator running in step with this one,
+    /// which stepping backwards in time would break
+    ///
+    /// Output:
+    ///     If another emulator depends on this one's timing (bool)
+    /// ```
+    fn is_lockstep(&self) -> bool {
+        false
+    }
+}
+
+pub struct Serial {
+    sb: u8,
+    sc: u8,
+    incoming: u8,
+    bits_left: u8,
+    cycles: u16,
+    cgb: bool,
+    device: Option<Box<dyn SerialDevice + Send>>,
+    capture: Option<Vec<u8>>,
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Serial {
+    pub fn new() -> Serial {
+        Serial {
+            sb: 0,
+            sc: 0,
+            incoming: DISCONNECTED,
+            bits_left: 0,
+            cycles: 0,
+            cgb: false,
+            device: None,
+            capture: None,
+        }
+    }
+
+    /// ```
+    /// Set CGB
+    ///
+    /// Sets whether the fast clock speed is available
+    ///
+    /// Input:
+    ///     Whether to enable CGB features (bool)
+    /// ```
+    pub fn set_cgb(&mut self, cgb: bool) {
+        self.cgb = cgb;
+    }
+
+    /// ```
+    /// Connect
+    ///
+    /// Plugs a device into the serial port, replacing any already there
+    ///
+    /// Input:
+    ///     Device to connect (Box<dyn SerialDevice + Send>)
+    /// ```
+    pub fn connect(&mut self, device: Box<dyn SerialDevice + Send>) {
+        self.device = Some(device);
+    }
+
+    /// ```
+    /// Disconnect
+    ///
+    /// Unplugs the device from the serial port
+    ///
+    /// Output:
+    ///     Device which was connected, if any (Option<Box<dyn SerialDevice + Send>>)
+    /// ```
+    pub fn disconnect(&mut self) -> Option<Box<dyn SerialDevice + Send>> {
+        self.device.take()
+    }
+
+    /// ```
+    /// Is lockstep
+    ///
+    /// Whether the connected device keeps another emulator in step with this one
+    ///
+    /// Output:
+    ///     If a lockstep device is connected (bool)
+    /// ```
+    pub fn is_lockstep(&self) -> bool {
+        self.device.as_ref().is_some_and(|d| d.is_lockstep())
+    }
+
+    /// ```
+    /// Set capture
+    ///
+    /// Sets whether to keep every byte sent, alongside any connected device
+    ///
+    /// Input:
+    ///     Whether to capture sent bytes (bool)
+    /// ```
+    pub fn set_capture(&mut self, enabled: bool) {
+        self.capture = if enabled { Some(self.capture.take().unwrap_or_default()) } else { None };
+    }
+
+    /// ```
+    /// Take output
+    ///
+    /// Removes the bytes captured so far
+    ///
+    /// Output:
+    ///     Bytes sent since last taken, empty if not capturing (Vec<u8>)
+    /// ```
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.capture.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// ```
+    /// Read register
+    ///
+    /// Reads from SB or SC
+    ///
+    /// Input:
+    ///     Register address (u16)
+    ///
+    /// Output:
+    ///     Register value (u8)
+    /// ```
+    pub fn read_reg(&self, addr: u16) -> u8 {
+        match addr {
+            SB => { self.sb },
+            _ => {
+                // Unused bits read as 1
+                let mask = if self.cgb { SC_CGB_MASK } else { SC_DMG_MASK };
+                self.sc | !mask
+            }
+        }
+    }
+
+    /// ```
+    /// Write register
+    ///
+    /// Writes to SB or SC, starting a transfer if requested
+    ///
+    /// Inputs:
+    ///     Register address (u16)
+    ///     Value to write (u8)
+    /// ```
+    pub fn write_reg(&mut self, addr: u16, val: u8) {
+        match addr {
+            SB => {
+                self.sb = val;
+            },
+            _ => {
+                let mask = if self.cgb { SC_CGB_MASK } else { SC_DMG_MASK };
+                self.sc = val & mask;
+                if self.sc.get_bit(SC_START_BIT) {
+                    self.start_transfer();
+                } else {
+                    self.bits_left = 0;
+                }
+            }
+        }
+    }
+
+    /// ```
+    /// Tick
+    ///
+    /// Advances any transfer in progress
+    ///
+    /// Input:
+    ///     Number of CPU cycles to advance (u8)
+    ///
+    /// Output:
+    ///     Whether a transfer finished, raising the serial interrupt (bool)
+    /// ```
+    pub fn tick(&mut self, cycles: u8) -> bool {
+        let sb = self.sb;
+        if let Some(device) = &mut self.device {
+            device.tick(cycles, sb);
+        }
+
+        if !self.sc.get_bit(SC_START_BIT) {
+            return false;
+        }
+
+        if !self.sc.get_bit(SC_CLOCK_BIT) {
+            // The other side drives the clock, and moves the whole byte at once
+            if let Some(byte) = self.device.as_mut().and_then(|d| d.poll_external(sb)) {
+                self.sb = byte;
+                return self.finish_transfer();
+            }
+            return false;
+        }
+
+        self.cycles += cycles as u16;
+        let bit_cycles = self.get_bit_cycles();
+        while self.cycles >= bit_cycles && self.bits_left > 0 {
+            self.cycles -= bit_cycles;
+            self.bits_left -= 1;
+            let bit_in = self.incoming.get_bit(self.bits_left);
+            self.sb = (self.sb << 1) | (bit_in as u8);
+        }
+
+        if self.bits_left == 0 {
+            return self.finish_transfer();
+        }
+        false
+    }
+
+    /// ```
+    /// Save state
+    ///
+    /// Writes the serial registers and any transfer in progress to a save state
+    ///
+    /// Input:
+    ///     Save state writer (&mut StateWriter)
+    /// ```
+    pub fn save_state(&self, state: &mut StateWriter) {
+        state.write_u8(self.sb);
+        state.write_u8(self.sc);
+        state.write_u8(self.incoming);
+        state.write_u8(self.bits_left);
+        state.write_u16(self.cycles);
+    }
+
+    /// ```
+    /// Load state
+    ///
+    /// Reads the serial registers back from a save state. Connected devices are kept.
+    ///
+    /// Input:
+    ///     Save state reader (&mut StateReader)
+    ///
+    /// Output:
+    ///     Whether the state was valid (Result<(), StateError>)
+    /// ```
+    pub fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let sb = state.read_u8()?;
+        let sc = state.read_u8()?;
+        let incoming = state.read_u8()?;
+        let bits_left = state.read_u8()?;
+        let cycles = state.read_u16()?;
+        if bits_left > BITS_PER_BYTE {
+            return Err(StateError::Invalid);
+        }
+
+        self.sb = sb;
+        self.sc = sc;
+        self.incoming = incoming;
+        self.bits_left = bits_left;
+        self.cycles = cycles;
+        Ok(())
+    }
+}
+
+// Private functions
+impl Serial {
+    /// ```
+    /// Start transfer
+    ///
+    /// Begins shifting out SB. With the internal clock, the device is asked for its byte right away.
+    /// Capture sees the byte as soon as it's sent, like the device would.
+    /// ```
+    fn start_transfer(&mut self) {
+        self.bits_left = BITS_PER_BYTE;
+        self.cycles = 0;
+        if let Some(capture) = &mut self.capture {
+            capture.push(self.sb);
+        }
+        if self.sc.get_bit(SC_CLOCK_BIT) {
+            let sb = self.sb;
+            self.incoming = match &mut self.device {
+                Some(device) => { device.transfer(sb) },
+                None => { DISCONNECTED }
+            };
+        }
+    }
+
+    /// ```
+    /// Finish transfer
+    ///
+    /// Marks the transfer as done
+    ///
+    /// Output:
+    ///     Always true, as the serial interrupt should fire (bool)
+    /// ```
+    fn finish_transfer(&mut self) -> bool {
+        self.sc.clear_bit(SC_START_BIT);
+        self.bits_left = 0;
+        self.cycles = 0;
+        true
+    }
+
+    /// ```
+    /// Get bit cycles
+    ///
+    /// How long each bit takes with the internal clock
+    ///
+    /// Output:
+    ///     CPU cycles per bit (u16)
+    /// ```
+    fn get_bit_cycles(&self) -> u16 {
+        if self.cgb && self.sc.get_bit(SC_SPEED_BIT) {
+            FAST_BIT_CYCLES
+        } else {
+            BIT_CYCLES
+        }
+    }
+}
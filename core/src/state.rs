@@ -0,0 +1,279 @@
+use std::fmt;
+
+/*
+ * Save states
+ *
+ * A save state is a snapshot of the entire machine, written as a flat
+ * little-endian byte stream. Each component writes its own fields in a
+ * fixed order, and reads them back in the same order.
+ *
+ * Header
+ * Offset | Size | Info
+ * -------+------+----------------------------------
+ * 0      | 4    | Magic, "SBST"
+ * 4      | 4    | Format version
+ * 8      | 4    | CRC32 of the ROM the state is for
+ * 12     | 4    | Length of the remaining data
+ *
+ * The version must be bumped whenever any component's layout changes,
+ * as older states can't be read correctly afterwards.
+ */
+
+// =============
+// = Constants =
+// =============
+pub const STATE_VERSION: u32 = 3;
+const STATE_MAGIC: &[u8; 4] = b"SBST";
+const HEADER_SIZE: usize = 16;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum StateError {
+    BadMagic,
+    Version(u32),
+    WrongRom,
+    WrongModel,
+    Truncated,
+    Invalid,
+    NoBootRom,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::BadMagic =>     { write!(f, "Not a save state") },
+            StateError::Version(v) =>   { write!(f, "Save state version {} is not supported (expected {})", v, STATE_VERSION) },
+            StateError::WrongRom =>     { write!(f, "Save state is for a different game") },
+            StateError::WrongModel =>   { write!(f, "Save state is for a different hardware model") },
+            StateError::Truncated =>    { write!(f, "Save state is incomplete") },
+            StateError::Invalid =>      { write!(f, "Save state contains invalid data") },
+            StateError::NoBootRom =>    { write!(f, "Save state was made during the boot ROM, which isn't loaded") },
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    /// ```
+    /// New
+    ///
+    /// Starts a new save state, writing its header
+    ///
+    /// Input:
+    ///     CRC32 of the loaded ROM (u32)
+    ///
+    /// Output:
+    ///     State writer (StateWriter)
+    /// ```
+    pub fn new(rom_crc: u32) -> StateWriter {
+        let mut writer = StateWriter { data: Vec::new() };
+        writer.write_bytes(STATE_MAGIC);
+        writer.write_u32(STATE_VERSION);
+        writer.write_u32(rom_crc);
+        // Length is filled in once finished
+        writer.write_u32(0);
+        writer
+    }
+
+    /// ```
+    /// Finish
+    ///
+    /// Completes the save state, filling in its length
+    ///
+    /// Output:
+    ///     Save state data (Vec<u8>)
+    /// ```
+    pub fn finish(mut self) -> Vec<u8> {
+        let len = (self.data.len() - HEADER_SIZE) as u32;
+        self.data[(HEADER_SIZE - 4)..HEADER_SIZE].copy_from_slice(&len.to_le_bytes());
+        self.data
+    }
+
+    pub fn write_u8(&mut self, val: u8) {
+        self.data.push(val);
+    }
+
+    pub fn write_bool(&mut self, val: bool) {
+        self.write_u8(val as u8);
+    }
+
+    pub fn write_u16(&mut self, val: u16) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_i16(&mut self, val: i16) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, val: u32) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_i32(&mut self, val: i32) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, val: u64) {
+        self.data.extend_from_slice(&val.to_le_bytes());
+    }
+
+    /// ```
+    /// Write bytes
+    ///
+    /// Writes a fixed size block of data, whose length the reader already knows
+    ///
+    /// Input:
+    ///     Data to write (&[u8])
+    /// ```
+    pub fn write_bytes(&mut self, data: &[u8]) {
+        self.data.extend_from_slice(data);
+    }
+
+    /// ```
+    /// Write vector
+    ///
+    /// Writes a variable sized block of data, preceded by its length
+    ///
+    /// Input:
+    ///     Data to write (&[u8])
+    /// ```
+    pub fn write_vec(&mut self, data: &[u8]) {
+        self.write_u32(data.len() as u32);
+        self.write_bytes(data);
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    /// ```
+    /// New
+    ///
+    /// Checks a save state's header, and prepares to read its contents
+    ///
+    /// Inputs:
+    ///     Save state data (&[u8])
+    ///     CRC32 of the loaded ROM (u32)
+    ///
+    /// Output:
+    ///     State reader, or why the state can't be loaded (Result<StateReader, StateError>)
+    /// ```
+    pub fn new(data: &'a [u8], rom_crc: u32) -> Result<StateReader<'a>, StateError> {
+        let mut reader = StateReader { data, pos: 0 };
+
+        let mut magic = [0; 4];
+        reader.read_bytes(&mut magic).map_err(|_| StateError::BadMagic)?;
+        if &magic != STATE_MAGIC {
+            return Err(StateError::BadMagic);
+        }
+
+        let version = reader.read_u32()?;
+        if version != STATE_VERSION {
+            return Err(StateError::Version(version));
+        }
+
+        if reader.read_u32()? != rom_crc {
+            return Err(StateError::WrongRom);
+        }
+
+        // Check the length up front, so a cut off state is rejected before anything is loaded
+        let len = reader.read_u32()? as usize;
+        if data.len() != HEADER_SIZE + len {
+            return Err(StateError::Truncated);
+        }
+
+        Ok(reader)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, StateError> {
+        let mut buf = [0; 1];
+        self.read_bytes(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, StateError> {
+        match self.read_u8()? {
+            0 => { Ok(false) },
+            1 => { Ok(true) },
+            _ => { Err(StateError::Invalid) }
+        }
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, StateError> {
+        let mut buf = [0; 2];
+        self.read_bytes(&mut buf)?;
+        Ok(u16::from_le_bytes(buf))
+    }
+
+    pub fn read_i16(&mut self) -> Result<i16, StateError> {
+        let mut buf = [0; 2];
+        self.read_bytes(&mut buf)?;
+        Ok(i16::from_le_bytes(buf))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, StateError> {
+        let mut buf = [0; 4];
+        self.read_bytes(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, StateError> {
+        let mut buf = [0; 4];
+        self.read_bytes(&mut buf)?;
+        Ok(i32::from_le_bytes(buf))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, StateError> {
+        let mut buf = [0; 8];
+        self.read_bytes(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    /// ```
+    /// Read bytes
+    ///
+    /// Reads a fixed size block of data
+    ///
+    /// Input:
+    ///     Buffer to fill (&mut [u8])
+    ///
+    /// Output:
+    ///     Whether there was enough data (Result<(), StateError>)
+    /// ```
+    pub fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), StateError> {
+        let end = self.pos + buf.len();
+        if end > self.data.len() {
+            return Err(StateError::Truncated);
+        }
+
+        buf.copy_from_slice(&self.data[self.pos..end]);
+        self.pos = end;
+        Ok(())
+    }
+
+    /// ```
+    /// Read vector
+    ///
+    /// Reads a variable sized block of data, preceded by its length
+    ///
+    /// Output:
+    ///     Data read (Result<Vec<u8>, StateError>)
+    /// ```
+    pub fn read_vec(&mut self) -> Result<Vec<u8>, StateError> {
+        let len = self.read_u32()? as usize;
+        if self.pos + len > self.data.len() {
+            return Err(StateError::Truncated);
+        }
+
+        let mut buf = vec![0; len];
+        self.read_bytes(&mut buf)?;
+        Ok(buf)
+    }
+}
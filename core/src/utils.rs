@@ -225,3 +225,28 @@ pub fn unpack_u8(byte: u8) -> [u8; 4] {
 
     bytes
 }
+
+/// ```
+/// CRC32
+///
+/// Calculates the standard (IEEE 802.3) CRC32 checksum of some data
+///
+/// Input:
+///     Data to checksum (&[u8])
+///
+/// Output:
+///     Checksum (u32)
+/// ```
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
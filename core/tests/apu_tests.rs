@@ -0,0 +1,189 @@
+extern crate songbird_core;
+
+use songbird_core::apu::*;
+
+// Number of M-cycles between frame sequencer steps
+const FRAME_STEP: u8 = 128;
+const STEPS_PER_FRAME_SEQ: usize = 16;
+
+/// Runs the APU for the given number of frame sequencer steps
+fn run_steps(apu: &mut APU, steps: usize) {
+    for _ in 0..(steps * STEPS_PER_FRAME_SEQ) {
+        apu.tick(FRAME_STEP);
+    }
+}
+
+#[test]
+/// Tests that unused register bits are read back as 1s
+fn test_read_masks() {
+    let mut apu = APU::new();
+    for addr in SOUND_START..0xFF30 {
+        if addr != 0xFF26 {
+            apu.write_reg(addr, 0x00);
+        }
+    }
+
+    let expected = [
+        0x80, 0x3F, 0x00, 0xFF, 0xBF,
+        0xFF, 0x3F, 0x00, 0xFF, 0xBF,
+        0x7F, 0xFF, 0x9F, 0xFF, 0xBF,
+        0xFF, 0xFF, 0x00, 0x00, 0xBF,
+        0x00, 0x00, 0xF0,
+    ];
+    for (i, val) in expected.iter().enumerate() {
+        assert_eq!(apu.read_reg(SOUND_START + i as u16), *val);
+    }
+
+    // Unused registers
+    for addr in 0xFF27..0xFF30 {
+        assert_eq!(apu.read_reg(addr), 0xFF);
+    }
+}
+
+#[test]
+/// Tests that triggering a channel sets its status bit, if its DAC is on
+fn test_trigger() {
+    let mut apu = APU::new();
+
+    // DAC off, channel 1 shouldn't enable
+    apu.write_reg(0xFF12, 0x00);
+    apu.write_reg(0xFF14, 0x80);
+    assert_eq!(apu.read_reg(0xFF26) & 0x0F, 0x00);
+
+    apu.write_reg(0xFF12, 0xF0);
+    apu.write_reg(0xFF14, 0x80);
+    assert_eq!(apu.read_reg(0xFF26) & 0x0F, 0x01);
+
+    // Wave channel
+    apu.write_reg(0xFF1A, 0x80);
+    apu.write_reg(0xFF1E, 0x80);
+    assert_eq!(apu.read_reg(0xFF26) & 0x0F, 0x05);
+
+    // Turning off the DAC disables the channel
+    apu.write_reg(0xFF12, 0x00);
+    assert_eq!(apu.read_reg(0xFF26) & 0x0F, 0x04);
+}
+
+#[test]
+/// Tests that the length counter disables the channel once it expires
+fn test_length() {
+    let mut apu = APU::new();
+    // Sync up the frame sequencer, so the next step clocks the length counter
+    apu.write_reg(0xFF26, 0x00);
+    apu.write_reg(0xFF26, 0x80);
+
+    // Channel 2, length of 64 - 60 = 4
+    apu.write_reg(0xFF17, 0xF0);
+    apu.write_reg(0xFF16, 60);
+    apu.write_reg(0xFF19, 0xC0);
+    assert_eq!(apu.read_reg(0xFF26) & 0x0F, 0x02);
+
+    // Length is clocked every other step
+    run_steps(&mut apu, 6);
+    assert_eq!(apu.read_reg(0xFF26) & 0x0F, 0x02);
+    run_steps(&mut apu, 1);
+    assert_eq!(apu.read_reg(0xFF26) & 0x0F, 0x00);
+}
+
+#[test]
+/// Tests that powering off clears registers, but not wave RAM
+fn test_power_off() {
+    let mut apu = APU::new();
+    apu.write_reg(0xFF24, 0x77);
+    apu.write_reg(0xFF30, 0x12);
+
+    apu.write_reg(0xFF26, 0x00);
+    assert_eq!(apu.read_reg(0xFF26), 0x70);
+    assert_eq!(apu.read_reg(0xFF24), 0x00);
+    assert_eq!(apu.read_reg(0xFF30), 0x12);
+
+    // Registers are read-only while off
+    apu.write_reg(0xFF24, 0x77);
+    assert_eq!(apu.read_reg(0xFF24), 0x00);
+}
+
+#[test]
+/// Tests that channel 1's sweep overflow disables the channel
+fn test_sweep_overflow() {
+    let mut apu = APU::new();
+
+    // Sweep period 1, shift 1, frequency $7FF
+    apu.write_reg(0xFF10, 0x11);
+    apu.write_reg(0xFF12, 0xF0);
+    apu.write_reg(0xFF13, 0xFF);
+    apu.write_reg(0xFF14, 0x87);
+
+    // Overflow is detected on trigger
+    assert_eq!(apu.read_reg(0xFF26) & 0x01, 0x00);
+}
+
+#[test]
+/// Tests that samples are generated at the requested rate
+fn test_sample_rate() {
+    let mut apu = APU::new();
+    apu.set_sample_rate(44_100);
+
+    // One second of emulated time
+    for _ in 0..(1_048_576 / 16) {
+        apu.tick(16);
+    }
+
+    let avail = apu.samples_avail();
+    assert!((44_099..=44_101).contains(&avail));
+
+    let mut buf = vec![0; 1000];
+    assert_eq!(apu.drain_samples(&mut buf), 1000);
+    assert_eq!(apu.samples_avail(), avail - 500);
+}
+
+#[test]
+/// Tests that NR51 routes a channel to only one side
+fn test_panning() {
+    let mut apu = APU::new();
+    apu.write_reg(0xFF24, 0x77);
+    // Channel 2 on the left only
+    apu.write_reg(0xFF25, 0x20);
+    apu.write_reg(0xFF16, 0x80);
+    apu.write_reg(0xFF17, 0xF0);
+    apu.write_reg(0xFF18, 0x00);
+    apu.write_reg(0xFF19, 0x87);
+
+    run_steps(&mut apu, 2);
+    let mut buf = vec![0; 2 * apu.samples_avail()];
+    let len = apu.drain_samples(&mut buf);
+
+    let left = buf[..len].iter().step_by(2).any(|s| *s != 0);
+    let right = buf[..len].iter().skip(1).step_by(2).any(|s| *s != 0);
+    assert!(left);
+    assert!(!right);
+}
+
+#[test]
+/// Tests that undrained audio is capped, keeping the newest second
+fn test_sample_limit() {
+    let mut apu = APU::new();
+    apu.set_sample_rate(44_100);
+
+    // Five seconds of emulated time, never drained
+    for _ in 0..(5 * 1_048_576 / 16) {
+        apu.tick(16);
+    }
+
+    let avail = apu.samples_avail();
+    assert!((44_100..=2 * 44_101).contains(&avail));
+}
+
+#[test]
+/// Tests that no samples are made while output is turned off
+fn test_output_disabled() {
+    let mut apu = APU::new();
+    apu.set_output_enabled(false);
+    apu.tick(100);
+    assert_eq!(apu.samples_avail(), 0);
+
+    apu.set_output_enabled(true);
+    for _ in 0..(1_048_576 / 16) {
+        apu.tick(16);
+    }
+    assert!(apu.samples_avail() > 0);
+}
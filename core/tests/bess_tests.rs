@@ -0,0 +1,131 @@
+extern crate songbird_core;
+
+mod common;
+
+use common::counter_rom;
+use songbird_core::cpu::*;
+use songbird_core::cpu::model::Model;
+use songbird_core::rewind::DEFAULT_BUDGET;
+use songbird_core::state::*;
+
+#[test]
+/// Test that the footer points back at the first block
+fn test_bess_footer() {
+    let mut gb = Cpu::new();
+    gb.load_game(&counter_rom(0));
+    let state = gb.export_bess();
+
+    let len = state.len();
+    assert_eq!(&state[(len - 4)..], b"BESS");
+    let mut offset = [0; 4];
+    offset.copy_from_slice(&state[(len - 8)..(len - 4)]);
+    let first = u32::from_le_bytes(offset) as usize;
+    assert_eq!(&state[first..(first + 4)], b"NAME");
+}
+
+#[test]
+/// Test that importing a BESS state restores registers and memory
+fn test_bess_round_trip() {
+    let mut gb = Cpu::with_model(Model::CGB);
+    let mut rom = counter_rom(0);
+    // CGB flag
+    rom[0x0143] = 0x80;
+    gb.load_game(&rom);
+    for _ in 0..1000 {
+        gb.tick();
+    }
+    gb.write_ram(0xC123, 0x45);
+    gb.write_ram(0xFF70, 0x03);
+    gb.write_ram(0xD000, 0x67);
+    gb.write_ram(0xFF4F, 0x01);
+    gb.write_ram(0x8010, 0xFF);
+    gb.write_ram(0xFF80, 0x89);
+
+    let state = gb.export_bess();
+    let pc = gb.get_pc();
+    let a = gb.get_reg(Regs::A);
+
+    for _ in 0..1000 {
+        gb.tick();
+    }
+    gb.write_ram(0xC123, 0x00);
+    gb.write_ram(0xD000, 0x00);
+    gb.write_ram(0x8010, 0x00);
+    gb.write_ram(0xFF70, 0x01);
+    gb.write_ram(0xFF4F, 0x00);
+    gb.write_ram(0xFF80, 0x00);
+
+    gb.import_bess(&state).unwrap();
+    assert_eq!(gb.get_pc(), pc);
+    assert_eq!(gb.get_reg(Regs::A), a);
+    assert_eq!(gb.read_ram(0xC123), 0x45);
+    assert_eq!(gb.read_ram(0xFF70), 0xFB);
+    assert_eq!(gb.read_ram(0xD000), 0x67);
+    assert_eq!(gb.read_ram(0xFF4F), 0xFF);
+    assert_eq!(gb.read_ram(0x8010), 0xFF);
+    assert_eq!(gb.read_ram(0xFF80), 0x89);
+
+    // Exporting again gives an identical state
+    assert_eq!(gb.export_bess(), state);
+}
+
+#[test]
+/// Test that importing a state discards rewind snapshots from before it
+fn test_bess_clears_rewind() {
+    let mut gb = Cpu::new();
+    gb.load_game(&counter_rom(0));
+    let state = gb.export_bess();
+    gb.enable_rewind(DEFAULT_BUDGET, 1);
+    let mut frames = 0;
+    while frames < 3 {
+        if gb.tick() {
+            frames += 1;
+        }
+    }
+
+    gb.import_bess(&state).unwrap();
+    assert!(!gb.rewind());
+}
+
+#[test]
+/// Test that states for another game or model are rejected
+fn test_bess_mismatch() {
+    let mut gb = Cpu::new();
+    gb.load_game(&counter_rom(0));
+    let state = gb.export_bess();
+
+    let mut other = Cpu::new();
+    other.load_game(&counter_rom(1));
+    assert_eq!(other.import_bess(&state), Err(StateError::WrongRom));
+
+    let mut cgb = Cpu::with_model(Model::CGB);
+    cgb.load_game(&counter_rom(0));
+    assert_eq!(cgb.import_bess(&state), Err(StateError::WrongModel));
+
+    assert_eq!(gb.import_bess(b"not a state"), Err(StateError::BadMagic));
+    // Footer alone, pointing at blocks which aren't there
+    assert_eq!(gb.import_bess(&state[(state.len() - 8)..]), Err(StateError::Truncated));
+}
+
+#[test]
+/// Test that untrusted clock timestamps and block lengths are handled without panicking
+fn test_bess_untrusted() {
+    let mut rom = counter_rom(0);
+    // MBC3 with timer, RAM and battery
+    rom[0x0147] = 0x10;
+    rom[0x0149] = 0x02;
+    let mut gb = Cpu::new();
+    gb.load_game(&rom);
+    let state = gb.export_bess();
+
+    // A timestamp of 0 claims the clock has been running since 1970
+    let rtc = state.windows(4).position(|id| id == b"RTC ").unwrap() + 8;
+    let mut old = state.clone();
+    old[(rtc + 0x28)..(rtc + 0x30)].copy_from_slice(&0u64.to_le_bytes());
+    gb.import_bess(&old).unwrap();
+
+    // A block claiming to run past the end of the file
+    let mut long = state.clone();
+    long[(rtc - 4)..rtc].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(gb.import_bess(&long), Err(StateError::Truncated));
+}
@@ -0,0 +1,53 @@
+// Fixtures shared by the integration tests, which don't all use every one
+#![allow(dead_code)]
+
+use songbird_core::cpu::Cpu;
+use songbird_core::memory::FlatMemory;
+
+/// Creates a blank ROM which starts by executing the given program
+pub fn program_rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100..(0x0100 + program.len())].copy_from_slice(program);
+    rom
+}
+
+/// Creates a blank CGB ROM which starts by executing the given program
+pub fn cgb_rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = program_rom(program);
+    rom[0x0143] = 0x80;
+    rom
+}
+
+/// Creates a ROM which loops forever, incrementing A, with an ID in its title to tell games apart
+pub fn counter_rom(id: u8) -> Vec<u8> {
+    // INC A; JR -3
+    let mut rom = program_rom(&[0x3C, 0x18, 0xFD]);
+    rom[0x0134] = id;
+    rom
+}
+
+/// Creates a program which sends text over serial the way Blargg's tests do, then loops forever
+pub fn serial_program(text: &[u8]) -> Vec<u8> {
+    let mut program = Vec::new();
+    for &c in text {
+        // LD A,c; LDH (SB),A; LD A,$81; LDH (SC),A; LDH A,(SC); BIT 7,A; JR NZ,-6
+        program.extend_from_slice(&[0x3E, c, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0xF0, 0x02, 0xCB, 0x7F, 0x20, 0xFA]);
+    }
+    // JR -2
+    program.extend_from_slice(&[0x18, 0xFE]);
+    program
+}
+
+/// Creates an emulator running the given program
+pub fn load_program(program: &[u8]) -> Cpu {
+    let mut gb = Cpu::new();
+    gb.load_game(&program_rom(program));
+    gb
+}
+
+/// Creates a CPU over flat memory, with a program loaded at $0000
+pub fn flat_cpu(program: &[u8]) -> Cpu<FlatMemory> {
+    let mut ram = FlatMemory::new();
+    ram.load(0x0000, program);
+    Cpu::with_bus(ram)
+}
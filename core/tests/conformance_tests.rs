@@ -0,0 +1,157 @@
+extern crate songbird_core;
+
+use songbird_core::cpu::Cpu;
+use songbird_core::cpu::model::Model;
+use songbird_core::runner::TestRunner;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Test ROMs aren't distributed with the emulator, so they're looked for here.
+// Blargg's go in blargg/<suite>/, and Mooneye's in mooneye/acceptance/.
+const DEFAULT_ROM_DIR: &str = "tests/roms";
+const ROM_DIR_VAR: &str = "SONGBIRD_TEST_ROMS";
+const REPORT_NAME: &str = "results.md";
+
+const PASS: &str = ":white_check_mark:";
+const FAIL: &str = ":x:";
+
+/// Finds every ROM under a directory, in sorted order
+fn find_roms(dir: &Path) -> Vec<PathBuf> {
+    let mut roms = Vec::new();
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => { entries.filter_map(|e| e.ok()).map(|e| e.path()).collect() },
+        Err(_) => { return roms; }
+    };
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            roms.extend(find_roms(&path));
+        } else if matches!(path.extension().and_then(|e| e.to_str()), Some("gb") | Some("gbc")) {
+            roms.push(path);
+        }
+    }
+    roms
+}
+
+/// Runs a single ROM, returning whether it passed
+fn run_rom(path: &Path, runner: &TestRunner) -> bool {
+    let rom = fs::read(path).expect("Error reading test ROM");
+    let mut gb = Cpu::with_model(Model::detect(&rom));
+    gb.load_game(&rom);
+    let report = runner.run(&mut gb);
+    eprintln!("{}: {}", path.display(), report.outcome.get_reason());
+    report.outcome.is_pass()
+}
+
+/// Gets the test number from a name like "01-special", if it has one
+fn test_number(path: &Path) -> Option<usize> {
+    let stem = path.file_stem()?.to_str()?;
+    let digits: String = stem.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Formats a Markdown table, padding each column to line up
+fn markdown_table(header: &[String], rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = (0..header.len()).map(|i| {
+        rows.iter().map(|r| r[i].len()).chain(std::iter::once(header[i].len())).max().unwrap()
+    }).collect();
+
+    let format_row = |cells: &[String]| -> String {
+        let padded: Vec<String> = cells.iter().zip(&widths).map(|(c, w)| format!("{:w$}", c, w = w)).collect();
+        format!("| {} |\n", padded.join(" | "))
+    };
+
+    let mut table = format_row(header);
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    table.push_str(&format_row(&rule));
+    for row in rows {
+        table.push_str(&format_row(row));
+    }
+    table
+}
+
+/// Runs Blargg's suites, returning a matrix of test number against suite
+fn blargg_report(dir: &Path) -> Option<String> {
+    let mut suites: Vec<PathBuf> = fs::read_dir(dir).ok()?.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()).collect();
+    suites.sort();
+
+    let runner = TestRunner::blargg();
+    let mut results: Vec<(String, BTreeMap<usize, bool>)> = Vec::new();
+    for suite in suites {
+        let roms = find_roms(&suite);
+        let numbered: Vec<(usize, &PathBuf)> = roms.iter().filter_map(|r| test_number(r).map(|n| (n, r))).collect();
+        // A suite's combined ROM is left out when the individual tests are available
+        let tests: Vec<(usize, &PathBuf)> = if numbered.is_empty() {
+            roms.iter().take(1).map(|r| (1, r)).collect()
+        } else {
+            numbered
+        };
+
+        let mut suite_results = BTreeMap::new();
+        for (num, rom) in tests {
+            suite_results.insert(num, run_rom(rom, &runner));
+        }
+        let name = suite.file_name().unwrap().to_string_lossy().to_string();
+        results.push((name, suite_results));
+    }
+
+    let max_test = results.iter().filter_map(|(_, r)| r.keys().max()).max().copied()?;
+    let mut header = vec!["Test number".to_string()];
+    header.extend(results.iter().map(|(name, _)| name.clone()));
+    let rows: Vec<Vec<String>> = (1..=max_test).map(|num| {
+        let mut row = vec![num.to_string()];
+        row.extend(results.iter().map(|(_, r)| {
+            match r.get(&num) {
+                Some(true) => { PASS.to_string() },
+                Some(false) => { FAIL.to_string() },
+                None => { String::new() }
+            }
+        }));
+        row
+    }).collect();
+
+    Some(format!("## Blargg Tests\n\n{}", markdown_table(&header, &rows)))
+}
+
+/// Runs Mooneye's acceptance tests, returning a table of results
+fn mooneye_report(dir: &Path) -> Option<String> {
+    let acceptance = dir.join("acceptance");
+    let roms = find_roms(&acceptance);
+    if roms.is_empty() {
+        return None;
+    }
+
+    let runner = TestRunner::mooneye();
+    let rows: Vec<Vec<String>> = roms.iter().map(|rom| {
+        let name = rom.strip_prefix(&acceptance).unwrap().with_extension("");
+        let result = if run_rom(rom, &runner) { PASS } else { FAIL };
+        vec![name.to_string_lossy().to_string(), result.to_string()]
+    }).collect();
+    let header = vec!["Test".to_string(), "Success".to_string()];
+
+    Some(format!("## Mooneye Tests\n\n### Acceptance\n\n{}", markdown_table(&header, &rows)))
+}
+
+#[test]
+/// Runs every test ROM found, writing a pass/fail report in the same format as tests.md
+fn test_conformance() {
+    let rom_dir = PathBuf::from(env::var(ROM_DIR_VAR).unwrap_or_else(|_| DEFAULT_ROM_DIR.to_string()));
+    if !rom_dir.is_dir() {
+        eprintln!("Skipping, no test ROMs found in {}", rom_dir.display());
+        return;
+    }
+
+    let sections: Vec<String> = [
+        blargg_report(&rom_dir.join("blargg")),
+        mooneye_report(&rom_dir.join("mooneye")),
+    ].iter().flatten().cloned().collect();
+
+    let report = format!("# Test Results\n\n{}", sections.join("\n"));
+    let report_path = rom_dir.join(REPORT_NAME);
+    fs::write(&report_path, &report).expect("Error writing report");
+    println!("{}", report);
+}
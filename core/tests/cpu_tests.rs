@@ -1,6 +1,10 @@
 extern crate songbird_core;
 
+mod common;
+
+use common::cgb_rom;
 use songbird_core::cpu::*;
+use songbird_core::cpu::model::Model;
 use songbird_core::utils::ModifyBytes;
 
 #[test]
@@ -457,3 +461,150 @@ pub fn test_shift() {
     assert_eq!(gb.get_flag(Flags::Z), false);
     assert_eq!(gb.get_flag(Flags::C), true);
 }
+
+#[test]
+/// Test CGB speed switch via KEY1 and STOP
+fn test_speed_switch() {
+    let mut gb = Cpu::with_model(Model::CGB);
+    // STOP, NOP
+    gb.load_game(&cgb_rom(&[0x10, 0x00]));
+
+    // STOP does nothing unless a switch was prepared
+    gb.tick();
+    assert_eq!(gb.is_double_speed(), false);
+
+    gb.set_pc(0x0100);
+    gb.write_ram(0xFF4D, 0x01);
+    assert_eq!(gb.read_ram(0xFF4D), 0x7F);
+    gb.tick();
+    assert!(gb.is_double_speed());
+    assert_eq!(gb.read_ram(0xFF4D), 0xFE);
+}
+
+#[test]
+/// Test CGB work RAM bank switching
+fn test_wram_banks() {
+    let mut gb = Cpu::with_model(Model::CGB);
+    gb.load_game(&cgb_rom(&[]));
+
+    for bank in 1..8 {
+        gb.write_ram(0xFF70, bank);
+        gb.write_ram(0xD000, bank);
+    }
+
+    // Bank 0 selects bank 1
+    gb.write_ram(0xFF70, 0);
+    assert_eq!(gb.read_ram(0xFF70), 0xF8);
+    assert_eq!(gb.read_ram(0xD000), 1);
+
+    gb.write_ram(0xFF70, 5);
+    assert_eq!(gb.read_ram(0xD000), 5);
+    gb.write_ram(0xC000, 0xAB);
+    gb.write_ram(0xFF70, 2);
+    assert_eq!(gb.read_ram(0xC000), 0xAB);
+}
+
+#[test]
+/// Test CGB VRAM DMA transfers
+fn test_hdma() {
+    let mut gb = Cpu::with_model(Model::CGB);
+    gb.load_game(&cgb_rom(&[]));
+
+    for i in 0..0x20 {
+        gb.write_ram(0xC000 + i, i as u8);
+    }
+    gb.write_ram(0xFF51, 0xC0);
+    gb.write_ram(0xFF52, 0x00);
+    gb.write_ram(0xFF53, 0x00);
+    gb.write_ram(0xFF54, 0x00);
+
+    // General purpose DMA copies everything immediately
+    gb.write_ram(0xFF55, 0x01);
+    assert_eq!(gb.read_ram(0xFF55), 0xFF);
+    for i in 0..0x20 {
+        assert_eq!(gb.read_ram(0x8000 + i), i as u8);
+    }
+
+    // HBlank DMA waits for HBlank, and can be cancelled
+    gb.write_ram(0xFF51, 0xC0);
+    gb.write_ram(0xFF52, 0x00);
+    gb.write_ram(0xFF53, 0x01);
+    gb.write_ram(0xFF54, 0x00);
+    gb.write_ram(0xFF55, 0x83);
+    assert_eq!(gb.read_ram(0xFF55), 0x03);
+    assert_eq!(gb.read_ram(0x9000), 0x00);
+    gb.write_ram(0xFF55, 0x00);
+    assert_eq!(gb.read_ram(0xFF55), 0x83);
+}
+
+#[test]
+/// Test registers left by each model's boot ROM
+fn test_model_regs() {
+    let gb = Cpu::with_model(Model::DMG);
+    assert_eq!(gb.get_reg_16(Regs16::AF), 0x01B0);
+    assert_eq!(gb.get_reg_16(Regs16::HL), 0x014D);
+    assert_eq!(gb.read_ram(0xFF04), 0xAB);
+
+    let gb = Cpu::with_model(Model::CGB);
+    assert_eq!(gb.get_reg(Regs::A), 0x11);
+    assert_eq!(gb.get_reg_16(Regs16::DE), 0xFF56);
+
+    let gb = Cpu::with_model(Model::SGB);
+    assert_eq!(gb.get_reg_16(Regs16::BC), 0x0014);
+    assert_eq!(gb.read_ram(0xFF26) & 0xF0, 0xF0);
+
+    // CGB features need both CGB hardware and a CGB game
+    let mut gb = Cpu::with_model(Model::DMG);
+    gb.load_game(&cgb_rom(&[]));
+    gb.write_ram(0xD000, 0x12);
+    gb.write_ram(0xFF70, 0x02);
+    assert_eq!(gb.read_ram(0xD000), 0x12);
+    assert_eq!(Model::detect(&cgb_rom(&[])), Model::CGB);
+}
+
+#[test]
+/// Test that the boot ROM runs first, and unmaps itself via $FF50
+fn test_boot_rom() {
+    let mut boot = vec![0; 0x100];
+    // LD A, $01; LDH ($50), A
+    boot[0..4].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+    let mut rom = vec![0; 0x8000];
+    rom[0] = 0xAB;
+
+    let mut gb = Cpu::new();
+    gb.load_boot_rom(&boot);
+    gb.load_game(&rom);
+    assert_eq!(gb.get_pc(), 0x0000);
+    assert_eq!(gb.read_ram(0x0000), 0x3E);
+
+    gb.tick();
+    gb.tick();
+    assert_eq!(gb.get_pc(), 0x0004);
+    assert_eq!(gb.read_ram(0x0000), 0xAB);
+}
+
+#[test]
+/// Test the CGB boot ROM's mapping, and its switch into DMG compatibility mode
+fn test_cgb_boot_rom() {
+    let mut boot = vec![0xCC; 0x900];
+    // LD A, $04; LDH ($4C), A
+    boot[0..4].copy_from_slice(&[0x3E, 0x04, 0xE0, 0x4C]);
+    let mut rom = vec![0; 0x8000];
+    rom[0x0150] = 0xAB;
+
+    let mut gb = Cpu::with_model(Model::CGB);
+    gb.load_boot_rom(&boot);
+    gb.load_game(&rom);
+    assert_eq!(gb.read_ram(0x0150), 0xAB);
+    assert_eq!(gb.read_ram(0x0200), 0xCC);
+
+    // Boot ROM starts in CGB mode, even for a DMG game
+    gb.write_ram(0xFF70, 0x02);
+    assert_eq!(gb.read_ram(0xFF70), 0xFA);
+
+    gb.tick();
+    gb.tick();
+    gb.write_ram(0xD000, 0x12);
+    gb.write_ram(0xFF70, 0x03);
+    assert_eq!(gb.read_ram(0xD000), 0x12);
+}
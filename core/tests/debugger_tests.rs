@@ -0,0 +1,155 @@
+extern crate songbird_core;
+
+mod common;
+
+use common::flat_cpu;
+use songbird_core::cpu::Cpu;
+use songbird_core::debug::*;
+use songbird_core::debug::access::*;
+use songbird_core::debug::symbols::Symbols;
+
+/// Creates an MBC1 ROM with 4 banks, which switches to bank 3 then jumps into it
+fn banked_rom() -> Vec<u8> {
+    let mut rom = vec![0; 0x10000];
+    // MBC1, 64 KiB
+    rom[0x0147] = 0x01;
+    rom[0x0148] = 0x01;
+    // LD A, 3; LD ($2000), A; JP $4000
+    rom[0x0100..0x0108].copy_from_slice(&[0x3E, 0x03, 0xEA, 0x00, 0x20, 0xC3, 0x00, 0x40]);
+    // Bank 3: JR -2
+    rom[0xC000..0xC002].copy_from_slice(&[0x18, 0xFE]);
+    rom
+}
+
+#[test]
+/// Addresses outside of switchable ROM and cart RAM ignore any bank given
+fn test_point_banks() {
+    assert_eq!(Point::new(Some(2), 0x4123).bank, Some(2));
+    assert_eq!(Point::new(Some(1), 0xA000).bank, Some(1));
+    assert_eq!(Point::new(Some(0), 0x0150).bank, None);
+    assert_eq!(Point::new(Some(1), 0xC000).bank, None);
+
+    assert_eq!(Point::new(Some(2), 0x4123).to_string(), "02:4123");
+    assert_eq!(Point::new(None, 0x4123).to_string(), "$4123");
+}
+
+#[test]
+/// Points can be typed as bank:address, a plain address, or a label
+fn test_parse_point() {
+    let mut agbd = debugger::new();
+    agbd.set_symbols(Symbols::parse("00:0150 Main\n02:4000 PlayMusic\n"));
+
+    assert_eq!(agbd.parse_point("02:4123"), Some(Point::new(Some(2), 0x4123)));
+    assert_eq!(agbd.parse_point("$1f:$7fff"), Some(Point::new(Some(0x1F), 0x7FFF)));
+    assert_eq!(agbd.parse_point("4123"), Some(Point::new(None, 0x4123)));
+    assert_eq!(agbd.parse_point("PlayMusic"), Some(Point::new(Some(2), 0x4000)));
+    assert_eq!(agbd.parse_point("Main"), Some(Point::new(None, 0x0150)));
+    assert_eq!(agbd.parse_point("nowhere"), None);
+    assert_eq!(agbd.parse_point("xx:4000"), None);
+}
+
+#[test]
+/// A banked point only matches while its bank is mapped in
+fn test_point_mapped() {
+    let mut gb = Cpu::new();
+    gb.load_game(&banked_rom());
+    let bank1 = Point::new(Some(1), 0x4000);
+    let bank3 = Point::new(Some(3), 0x4000);
+    let any = Point::new(None, 0x4000);
+
+    assert!(bank1.is_mapped(&gb));
+    assert!(!bank3.is_mapped(&gb));
+    for _ in 0..3 {
+        gb.tick();
+    }
+    assert_eq!(gb.get_rom_bank(), 3);
+    assert!(!bank1.is_mapped(&gb));
+    assert!(bank3.is_mapped(&gb));
+    assert!(any.is_mapped(&gb));
+}
+
+#[test]
+/// Breakpoints in switchable ROM only fire in their own bank
+fn test_bank_breakpoint() {
+    let mut gb = Cpu::new();
+    gb.load_game(&banked_rom());
+    let mut wrong_bank = debugger::new();
+    wrong_bank.add_break(Point::new(Some(2), 0x4000));
+    let mut right_bank = debugger::new();
+    right_bank.add_break(Point::new(Some(3), 0x4000));
+
+    for _ in 0..3 {
+        gb.tick();
+    }
+    assert_eq!(gb.get_pc(), 0x4000);
+    assert!(!wrong_bank.check_break(&gb));
+    assert!(right_bank.check_break(&gb));
+}
+
+#[test]
+/// Reads and writes are reported with the instruction which made them, even if nothing changes
+fn test_access_read_write() {
+    // LD HL, $C000; INC (HL); LD (HL), $01
+    let mut cpu = flat_cpu(&[0x21, 0x00, 0xC0, 0x34, 0x36, 0x01]);
+    cpu.set_access_breaks(vec![
+        AccessBreak::new(AccessType::Read, 0xC000, 0xC0FF),
+        AccessBreak::new(AccessType::Write, 0xC000, 0xC000),
+    ]);
+
+    // Fetching the address from within the range isn't a read
+    cpu.step();
+    assert!(cpu.take_access_hits().is_empty());
+
+    cpu.step();
+    assert_eq!(cpu.take_access_hits(), vec![
+        AccessHit { kind: AccessType::Read, pc: 0x0003, addr: 0xC000, val: 0x00, old: 0x00, new: 0x00 },
+        AccessHit { kind: AccessType::Write, pc: 0x0003, addr: 0xC000, val: 0x01, old: 0x00, new: 0x01 },
+    ]);
+
+    cpu.step();
+    assert_eq!(cpu.take_access_hits(), vec![
+        AccessHit { kind: AccessType::Write, pc: 0x0004, addr: 0xC000, val: 0x01, old: 0x01, new: 0x01 },
+    ]);
+}
+
+#[test]
+/// Execute breakpoints report the instruction as it starts
+fn test_access_execute() {
+    // NOP; NOP; INC A
+    let mut cpu = flat_cpu(&[0x00, 0x00, 0x3C]);
+    cpu.set_access_breaks(vec![AccessBreak::new(AccessType::Execute, 0x0002, 0x0001)]);
+
+    cpu.step();
+    assert!(cpu.take_access_hits().is_empty());
+    cpu.step();
+    cpu.step();
+    let hits = cpu.take_access_hits();
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[1], AccessHit { kind: AccessType::Execute, pc: 0x0002, addr: 0x0002, val: 0x3C, old: 0x3C, new: 0x3C });
+    assert_eq!(hits[1].to_string(), "$0002: executing opcode $3c");
+}
+
+#[test]
+/// Accesses from outside of an instruction aren't reported, and clearing stops all checks
+fn test_access_outside_instruction() {
+    let mut gb = Cpu::new();
+    gb.load_game(&banked_rom());
+    let mut agbd = debugger::new();
+    agbd.add_access_break(&mut gb, AccessType::Read, (0xC000, 0xC0FF));
+
+    gb.read_ram(0xC000);
+    assert!(gb.take_access_hits().is_empty());
+
+    agbd.add_access_break(&mut gb, AccessType::Write, (0x2000, 0x2000));
+    for _ in 0..2 {
+        gb.tick();
+    }
+    let hits = gb.take_access_hits();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].to_string(), "$0102: wrote $03 to $2000 ($00 -> $00)");
+
+    agbd.del_access_break(&mut gb, (0xC0FF, 0xC000));
+    agbd.del_access_break(&mut gb, (0x2000, 0x2000));
+    gb.tick();
+    assert!(gb.take_access_hits().is_empty());
+}
@@ -0,0 +1,108 @@
+extern crate songbird_core;
+
+use songbird_core::debug::disasm::*;
+
+#[test]
+/// Immediates are decoded and shown in place of their placeholders
+fn test_decode_operands() {
+    let inst = decode(&[0x01, 0x34, 0x12], 0x0100).unwrap();
+    assert_eq!(inst.operand, Some(Operand::Imm16(0x1234)));
+    assert_eq!(inst.get_length(), 3);
+    assert_eq!(inst.to_string(), "LD BC, $1234");
+
+    let inst = decode(&[0xE0, 0x44], 0x0100).unwrap();
+    assert_eq!(inst.operand, Some(Operand::Addr(0xFF44)));
+    assert_eq!(inst.to_string(), "LDH ($FF44), A");
+
+    let inst = decode(&[0xC6, 0x01], 0x0100).unwrap();
+    assert_eq!(inst.to_string(), "ADD A, $01");
+
+    let inst = decode(&[0xF8, 0xFD], 0x0100).unwrap();
+    assert_eq!(inst.operand, Some(Operand::Offset(-3)));
+    assert_eq!(inst.to_string(), "LD HL, SP-3");
+
+    // Not enough bytes for the operand
+    assert!(decode(&[0xC3, 0x50], 0x0100).is_none());
+
+    // STOP takes up the byte after it too, but doesn't show it
+    let inst = decode(&[0x10, 0x00, 0x00], 0x0100).unwrap();
+    assert_eq!(inst.operand, None);
+    assert_eq!(inst.get_length(), 2);
+    assert_eq!(inst.get_next_addr(), 0x0102);
+    assert_eq!(inst.to_string(), "STOP");
+    assert!(decode(&[0x10], 0x0100).is_none());
+}
+
+#[test]
+/// Jumps and calls report where they go, with relative jumps resolved
+fn test_decode_targets() {
+    let inst = decode(&[0x18, 0xFE], 0x0150).unwrap();
+    assert_eq!(inst.get_target(), Some(0x0150));
+    assert_eq!(inst.to_string(), "JR $0150");
+
+    let inst = decode(&[0x20, 0x05], 0x0150).unwrap();
+    assert_eq!(inst.get_target(), Some(0x0157));
+
+    let inst = decode(&[0xCD, 0x00, 0x40], 0x0150).unwrap();
+    assert_eq!(inst.get_target(), Some(0x4000));
+    assert_eq!(inst.to_string(), "CALL $4000");
+
+    let inst = decode(&[0xEF], 0x0150).unwrap();
+    assert_eq!(inst.get_target(), Some(0x0028));
+
+    // Addresses which aren't jumped to aren't targets
+    let inst = decode(&[0xEA, 0x00, 0xC0], 0x0150).unwrap();
+    assert_eq!(inst.get_target(), None);
+}
+
+#[test]
+/// Every CB prefixed opcode is two bytes, with its own name
+fn test_decode_cb() {
+    let names: Vec<String> = (0..=0xFF).map(|op| {
+        let inst = decode(&[0xCB, op], 0x0000).unwrap();
+        assert_eq!(inst.get_length(), 2);
+        inst.to_string()
+    }).collect();
+
+    assert_eq!(names[0x00], "RLC B");
+    assert_eq!(names[0x36], "SWAP (HL)");
+    assert_eq!(names[0x3F], "SRL A");
+    assert_eq!(names[0x7C], "BIT 7, H");
+    assert_eq!(names[0x86], "RES 0, (HL)");
+    assert_eq!(names[0xFF], "SET 7, A");
+}
+
+#[test]
+/// Banks are disassembled at the addresses the CPU would see them
+fn test_disassemble_bank() {
+    let mut rom = vec![0; 0x10000];
+    // Bank 2: LD A, $12; CALL $4000
+    rom[0x8000..0x8005].copy_from_slice(&[0x3E, 0x12, 0xCD, 0x00, 0x40]);
+
+    let insts = disassemble_bank(&rom, 2, 0x4000, 2);
+    assert_eq!(insts.len(), 2);
+    assert_eq!(insts[0].addr, 0x4000);
+    assert_eq!(insts[0].to_string(), "LD A, $12");
+    assert_eq!(insts[1].addr, 0x4002);
+    assert_eq!(insts[1].to_string(), "CALL $4000");
+
+    // Stops at the end of the bank
+    assert_eq!(disassemble_bank(&rom, 2, 0x7FFE, 5).len(), 2);
+    // Outside the bank
+    assert!(disassemble_bank(&rom, 2, 0x0100, 5).is_empty());
+    assert!(disassemble_bank(&rom, 8, 0x4000, 5).is_empty());
+}
+
+#[test]
+/// A whole ROM is listed bank by bank, with cut off instructions left as data
+fn test_export_rom() {
+    let mut rom = vec![0; 0x8000];
+    rom[0x0100..0x0103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+    rom[0x7FFE..0x8000].copy_from_slice(&[0x00, 0xCD]);
+
+    let listing = export_rom(&rom, None);
+    assert!(listing.starts_with("; ROM bank $00\n00:0000  00        NOP\n"));
+    assert!(listing.contains("00:0100  C3 50 01  JP $0150\n"));
+    assert!(listing.contains("; ROM bank $01\n01:4000  00        NOP\n"));
+    assert!(listing.contains("01:7FFF  CD        DB $CD\n"));
+}
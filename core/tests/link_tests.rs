@@ -0,0 +1,202 @@
+extern crate songbird_core;
+
+mod common;
+
+use common::program_rom;
+use songbird_core::cpu::*;
+use songbird_core::link::*;
+use songbird_core::serial::SerialDevice;
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// A connection which counts how many messages are sent over it
+struct Counted {
+    stream: TcpStream,
+    flushes: Arc<AtomicUsize>,
+}
+
+impl Read for Counted {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for Counted {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes.fetch_add(1, Ordering::SeqCst);
+        self.stream.flush()
+    }
+}
+
+/// Runs a link on its own, with nothing using the serial port
+fn run_idle<S: Read + Write>(link: &mut Link<S>, cycles: u32) {
+    for _ in 0..(cycles / 4) {
+        link.tick(4, 0xFF);
+    }
+}
+
+/// Runs a ROM with a link for a fixed time, returning the final value of SB
+fn run_linked(program: Vec<u8>, stream: TcpStream) -> u8 {
+    let mut gb = Cpu::new();
+    gb.load_game(&program_rom(&program));
+    gb.connect_serial(Box::new(Link::new(stream, DEFAULT_QUANTUM).unwrap()));
+    while gb.get_cycles() < 20_000 {
+        gb.tick();
+    }
+    gb.read_ram(0xFF01)
+}
+
+/// Waits a while so the other side is ready, then sends $42 with the internal clock
+fn master_program() -> Vec<u8> {
+    // LD B,0; DEC B; JR NZ,-3; LD A,$42; LDH (SB),A; LD A,$81; LDH (SC),A; JR -2
+    vec![0x06, 0x00, 0x05, 0x20, 0xFD, 0x3E, 0x42, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02, 0x18, 0xFE]
+}
+
+/// Waits to receive a byte with the external clock, sending $99
+fn slave_program() -> Vec<u8> {
+    // LD A,$99; LDH (SB),A; LD A,$80; LDH (SC),A; JR -2
+    vec![0x3E, 0x99, 0xE0, 0x01, 0x3E, 0x80, 0xE0, 0x02, 0x18, 0xFE]
+}
+
+/// Creates a pair of emulators running the master and slave programs
+fn linked_pair() -> LinkedPair {
+    let mut master = Cpu::new();
+    master.load_game(&program_rom(&master_program()));
+    let mut slave = Cpu::new();
+    slave.load_game(&program_rom(&slave_program()));
+    LinkedPair::new(master, slave)
+}
+
+#[test]
+/// Test that two linked emulators exchange bytes
+fn test_link_transfer() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let master = master_program();
+    let slave = slave_program();
+
+    let master = thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        run_linked(master, stream)
+    });
+    let (stream, _) = listener.accept().unwrap();
+    let slave_sb = run_linked(slave, stream);
+
+    assert_eq!(master.join().unwrap(), 0x99);
+    assert_eq!(slave_sb, 0x42);
+}
+
+#[test]
+/// Test that both sides must agree on the quantum
+fn test_link_handshake() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let other = thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        Link::new(stream, DEFAULT_QUANTUM * 2).is_ok()
+    });
+    let (stream, _) = listener.accept().unwrap();
+    assert!(Link::new(stream, DEFAULT_QUANTUM).is_err());
+    assert!(!other.join().unwrap());
+}
+
+#[test]
+/// Test that a peer which stops answering drops the link instead of freezing this side
+fn test_link_timeout() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (done, wait) = mpsc::channel::<()>();
+
+    let other = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        // Completes the handshake, then never syncs, while keeping the connection open
+        let _link = Link::new(stream, DEFAULT_QUANTUM).unwrap();
+        wait.recv().ok();
+    });
+    let mut link = Link::connect(addr).unwrap();
+    assert!(link.is_connected());
+
+    // Runs one quantum, then waits on the other side
+    for _ in 0..(DEFAULT_QUANTUM / 4) {
+        link.tick(4, 0x00);
+    }
+    assert!(!link.is_connected());
+    assert_eq!(link.transfer(0x42), 0xFF);
+
+    done.send(()).unwrap();
+    other.join().unwrap();
+}
+
+#[test]
+/// Test that a link nobody is using syncs much less often than once a quantum
+fn test_link_idle() {
+    const CYCLES: u32 = 200_000;
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let other = thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut link = Link::new(stream, DEFAULT_QUANTUM).unwrap();
+        run_idle(&mut link, CYCLES);
+        link.is_connected()
+    });
+    let (stream, _) = listener.accept().unwrap();
+    let flushes = Arc::new(AtomicUsize::new(0));
+    let mut link = Link::new(Counted { stream, flushes: flushes.clone() }, DEFAULT_QUANTUM).unwrap();
+    run_idle(&mut link, CYCLES);
+
+    assert!(other.join().unwrap());
+    assert!(link.is_connected());
+    let syncs = flushes.load(Ordering::SeqCst) as u32;
+    assert!(syncs < CYCLES / DEFAULT_QUANTUM / 4, "{} syncs", syncs);
+}
+
+#[test]
+/// Test that a byte sent before the other side is waiting for it is lost, rather than arriving late
+fn test_linked_pair_unarmed() {
+    let mut master = Cpu::new();
+    master.load_game(&program_rom(&master_program()));
+    // Waits about 8 times as long as the master before waiting for a byte:
+    // LD C,8; LD B,0; DEC B; JR NZ,-3; DEC C; JR NZ,-8; then as the slave program
+    let mut program = vec![0x0E, 0x08, 0x06, 0x00, 0x05, 0x20, 0xFD, 0x0D, 0x20, 0xF8];
+    program.extend_from_slice(&slave_program());
+    let mut slave = Cpu::new();
+    slave.load_game(&program_rom(&program));
+
+    let mut pair = LinkedPair::new(master, slave);
+    pair.run_cycles(20_000);
+    // Still waiting, with its own byte in SB
+    assert_eq!(pair.second().read_ram(0xFF02), 0xFE);
+    assert_eq!(pair.second().read_ram(0xFF01), 0x99);
+    assert_eq!(pair.second().read_ram(0xFF0F) & 0b1000, 0);
+}
+
+#[test]
+/// Test that a linked pair in the same process exchanges bytes, the same way every time
+fn test_linked_pair() {
+    let mut pair = linked_pair();
+    pair.run_cycles(20_000);
+    assert_eq!(pair.first().read_ram(0xFF01), 0x99);
+    assert_eq!(pair.second().read_ram(0xFF01), 0x42);
+    // Serial interrupt requested on both sides
+    assert_ne!(pair.first().read_ram(0xFF0F) & 0b1000, 0);
+    assert_ne!(pair.second().read_ram(0xFF0F) & 0b1000, 0);
+
+    let mut again = linked_pair();
+    again.run_cycles(20_000);
+    assert_eq!(pair.first().save_state(), again.first().save_state());
+    assert_eq!(pair.second().save_state(), again.second().save_state());
+
+    // Handing the emulators back unplugs the wire
+    let (mut first, _) = pair.into_inner();
+    assert!(first.disconnect_serial().is_none());
+}
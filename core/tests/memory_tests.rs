@@ -0,0 +1,62 @@
+extern crate songbird_core;
+
+mod common;
+
+use common::flat_cpu;
+use songbird_core::cpu::*;
+use songbird_core::memory::*;
+
+#[test]
+/// Runs a small program in flat memory, where every address is plain RAM
+fn test_flat_memory() {
+    // LD HL,$FF05; LD A,$42; LD (HL),A; INC A; HALT
+    let mut cpu = flat_cpu(&[0x21, 0x05, 0xFF, 0x3E, 0x42, 0x77, 0x3C, 0x76]);
+    while !cpu.is_halted() {
+        cpu.step();
+    }
+
+    // $FF05 is the timer counter on a Game Boy, but here it holds whatever was written
+    assert_eq!(cpu.get_bus().read_ram(0xFF05), 0x42);
+    assert_eq!(cpu.get_reg(Regs::A), 0x43);
+    assert_eq!(cpu.get_pc(), 0x0008);
+    assert_eq!(cpu.get_cycles(), 3 + 2 + 2 + 1 + 1);
+}
+
+#[test]
+/// Step services interrupts from IE and IF in flat memory
+fn test_flat_interrupt() {
+    // EI; NOP
+    let mut cpu = flat_cpu(&[0xFB, 0x00]);
+    cpu.set_sp(0xD000);
+    cpu.get_bus_mut().load(0xFFFF, &[0x04]);
+    cpu.get_bus_mut().load(0xFF0F, &[0x04]);
+
+    cpu.step();
+    cpu.step();
+
+    assert_eq!(cpu.get_pc(), 0x0051);
+    assert!(!cpu.get_ime());
+    assert_eq!(cpu.get_bus().read_ram(0xFF0F), 0x00);
+}
+
+#[test]
+/// Every access goes through the recording bus, in order
+fn test_recording_bus() {
+    // LD (HL),$99 with HL at $C000
+    let mut ram = FlatMemory::new();
+    ram.load(0x0000, &[0x36, 0x99]);
+    ram.load(0xC000, &[0x11]);
+    let mut cpu = Cpu::with_bus(RecordingBus::new(ram));
+    cpu.set_reg_16(Regs16::HL, 0xC000);
+
+    cpu.step();
+
+    let accesses = cpu.get_bus().take_accesses();
+    assert_eq!(accesses, vec![
+        Access { addr: 0x0000, val: 0x36, kind: AccessKind::Read },
+        Access { addr: 0x0001, val: 0x99, kind: AccessKind::Read },
+        Access { addr: 0xC000, val: 0x99, kind: AccessKind::Write },
+    ]);
+    assert!(cpu.get_bus().take_accesses().is_empty());
+    assert_eq!(cpu.get_bus().get_inner().read_ram(0xC000), 0x99);
+}
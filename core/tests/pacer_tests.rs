@@ -0,0 +1,43 @@
+extern crate songbird_core;
+
+use songbird_core::pacer::*;
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 48_000;
+
+#[test]
+/// Tests that unthrottled mode never waits
+fn test_unthrottled() {
+    let mut pacer = Pacer::new(PaceMode::Unthrottled);
+    assert_eq!(pacer.get_delay(CPU_FREQ * 10, SAMPLE_RATE as usize, SAMPLE_RATE), Duration::from_secs(0));
+}
+
+#[test]
+/// Tests that audio mode waits until the queue drains to its target latency
+fn test_audio() {
+    let mut pacer = Pacer::new(PaceMode::Audio);
+
+    // Only 10 ms of audio queued, keep going
+    let delay = pacer.get_delay(0, SAMPLE_RATE as usize / 100, SAMPLE_RATE);
+    assert_eq!(delay, Duration::from_secs(0));
+
+    // 100 ms queued, wait for half of it to play
+    let delay = pacer.get_delay(0, SAMPLE_RATE as usize / 10, SAMPLE_RATE);
+    assert_eq!(delay, Duration::from_millis(50));
+}
+
+#[test]
+/// Tests that wall clock mode waits for real time to catch up to emulated time
+fn test_wall_clock() {
+    let mut pacer = Pacer::new(PaceMode::WallClock);
+    pacer.reset(0);
+
+    // A full second of emulation was done instantly
+    let delay = pacer.get_delay(CPU_FREQ, 0, SAMPLE_RATE);
+    assert!(delay > Duration::from_millis(900));
+    assert!(delay <= Duration::from_secs(1));
+
+    // No cycles have run yet
+    let delay = pacer.get_delay(0, 0, SAMPLE_RATE);
+    assert_eq!(delay, Duration::from_secs(0));
+}
@@ -0,0 +1,81 @@
+extern crate songbird_core;
+
+use songbird_core::cpu::clock::*;
+use songbird_core::ppu::PPU;
+
+#[test]
+/// Tests that VRAM bank 1 is separate from bank 0, and only exists on CGB
+fn test_vram_banks() {
+    let mut ppu = PPU::new();
+    ppu.write_vram(0xFF4F, 0x01);
+    assert_eq!(ppu.read_vram(0xFF4F), 0xFF);
+    ppu.write_vram(0x8000, 0x12);
+    assert_eq!(ppu.read_vram(0x8000), 0x12);
+
+    ppu.set_cgb(true);
+    ppu.write_vram(0xFF4F, 0x01);
+    assert_eq!(ppu.read_vram(0xFF4F), 0xFF);
+    ppu.write_vram(0x8000, 0x34);
+    assert_eq!(ppu.read_vram(0x8000), 0x34);
+
+    ppu.write_vram(0xFF4F, 0x00);
+    assert_eq!(ppu.read_vram(0xFF4F), 0xFE);
+    assert_eq!(ppu.read_vram(0x8000), 0x12);
+}
+
+#[test]
+/// Tests that palette RAM index auto-increments and wraps around
+fn test_palette_ram() {
+    let mut ppu = PPU::new();
+    ppu.set_cgb(true);
+
+    // Start at the last byte, with auto-increment
+    ppu.write_vram(0xFF68, 0xBF);
+    ppu.write_vram(0xFF69, 0x55);
+    assert_eq!(ppu.read_vram(0xFF68), 0xC0);
+    ppu.write_vram(0xFF69, 0xAA);
+
+    ppu.write_vram(0xFF68, 0x3F);
+    assert_eq!(ppu.read_vram(0xFF69), 0x55);
+    ppu.write_vram(0xFF68, 0x00);
+    assert_eq!(ppu.read_vram(0xFF69), 0xAA);
+
+    // Object palettes are separate
+    ppu.write_vram(0xFF6A, 0x00);
+    ppu.write_vram(0xFF6B, 0x12);
+    assert_eq!(ppu.read_vram(0xFF69), 0xAA);
+    assert_eq!(ppu.read_vram(0xFF6B), 0x12);
+}
+
+#[test]
+/// Tests that each mode lasts as long as on hardware, counted in M-cycles
+fn test_mode_lengths() {
+    let mut clock = Clock::new();
+    clock.set_position(0, 2);
+
+    // OAM read: 80 dots
+    assert!(clock.clock_step(19) == ClockResults::NoAction);
+    assert_eq!(clock.get_mode(), 2);
+    clock.clock_step(1);
+    assert_eq!(clock.get_mode(), 3);
+
+    // VRAM read: 172 dots, then the line is drawn
+    assert!(clock.clock_step(42) == ClockResults::NoAction);
+    assert_eq!(clock.get_mode(), 3);
+    assert!(clock.clock_step(1) == ClockResults::RenderScanline);
+    assert_eq!(clock.get_mode(), 0);
+
+    // HBlank: 204 dots, for 456 per line in all
+    clock.clock_step(50);
+    assert_eq!(clock.get_mode(), 0);
+    clock.clock_step(1);
+    assert_eq!(clock.get_mode(), 2);
+    assert_eq!(clock.get_scanline(), 1);
+
+    // VBlank lines are also 456 dots
+    clock.set_position(150, 1);
+    clock.clock_step(113);
+    assert_eq!(clock.get_scanline(), 150);
+    clock.clock_step(1);
+    assert_eq!(clock.get_scanline(), 151);
+}
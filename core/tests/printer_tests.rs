@@ -0,0 +1,121 @@
+extern crate songbird_core;
+
+use songbird_core::png::*;
+use songbird_core::printer::*;
+use songbird_core::serial::SerialDevice;
+
+/// Sends a whole packet to the printer, returning its last two replies
+fn send_packet(printer: &mut Printer, command: u8, compression: u8, data: &[u8]) -> (u8, u8) {
+    let len = data.len() as u16;
+    let mut packet = vec![0x88, 0x33, command, compression];
+    packet.extend_from_slice(&len.to_le_bytes());
+    packet.extend_from_slice(data);
+    let sum = packet[2..].iter().fold(0u16, |sum, &b| sum.wrapping_add(b as u16));
+    packet.extend_from_slice(&sum.to_le_bytes());
+
+    for byte in packet {
+        assert_eq!(printer.transfer(byte), 0x00);
+    }
+    (printer.transfer(0x00), printer.transfer(0x00))
+}
+
+/// Creates two rows of tiles, with each tile filled with a single color
+fn tile_rows(color: u8) -> Vec<u8> {
+    let low = if (color & 0b01) != 0 { 0xFF } else { 0x00 };
+    let high = if (color & 0b10) != 0 { 0xFF } else { 0x00 };
+    [low, high].repeat(0x280 / 2)
+}
+
+#[test]
+/// Test packet replies and status bits
+fn test_printer_status() {
+    let mut printer = Printer::new(None);
+    assert_eq!(send_packet(&mut printer, 0x01, 0, &[]), (0x81, 0x00));
+    assert_eq!(send_packet(&mut printer, 0x0F, 0, &[]), (0x81, 0x00));
+
+    // Data waiting to be printed
+    assert_eq!(send_packet(&mut printer, 0x04, 0, &tile_rows(0)), (0x81, 0x08));
+    assert_eq!(send_packet(&mut printer, 0x04, 0, &[]), (0x81, 0x08));
+
+    // Busy while printing, then done
+    let (_, status) = send_packet(&mut printer, 0x02, 0, &[0x01, 0x13, 0xE4, 0x40]);
+    assert_eq!(status & 0x0A, 0x02);
+    let mut status = 0xFF;
+    for _ in 0..10 {
+        status = send_packet(&mut printer, 0x0F, 0, &[]).1;
+    }
+    assert_eq!(status, 0x00);
+
+    // Bad checksum
+    for byte in [0x88, 0x33, 0x0F, 0x00, 0x00, 0x00, 0x12, 0x34] {
+        printer.transfer(byte);
+    }
+    printer.transfer(0x00);
+    assert_eq!(printer.transfer(0x00) & 0x01, 0x01);
+}
+
+#[test]
+/// Test that uncompressed and compressed tiles print with the given palette
+fn test_printer_print() {
+    let mut printer = Printer::new(None);
+    send_packet(&mut printer, 0x01, 0, &[]);
+    send_packet(&mut printer, 0x04, 0, &tile_rows(3));
+    // Two rows of color 1, as literal pairs of $FF $00
+    let mut compressed = Vec::new();
+    for _ in 0..(0x280 / 2) {
+        compressed.extend_from_slice(&[0x01, 0xFF, 0x00]);
+    }
+    // Then a run of 0x40 zero bytes, too few for a full row of tiles, so left off
+    compressed.extend_from_slice(&[0x80 | 0x3E, 0x00]);
+    send_packet(&mut printer, 0x04, 1, &compressed);
+    send_packet(&mut printer, 0x04, 0, &[]);
+    // Inverted palette, so color 3 is white and color 1 is dark gray, with a bottom margin to finish
+    send_packet(&mut printer, 0x02, 0, &[0x01, 0x01, 0x1B, 0x40]);
+
+    let image = printer.get_last_print().unwrap();
+    assert_eq!(image.len(), PRINT_WIDTH * 32);
+    assert!(image[..(PRINT_WIDTH * 16)].iter().all(|&p| p == 0xFF));
+    assert!(image[(PRINT_WIDTH * 16)..].iter().all(|&p| p == 0x55));
+}
+
+#[test]
+/// Test that strips without a bottom margin build up into a single printout
+fn test_printer_printout() {
+    let mut printer = Printer::new(None);
+    // Top margin of one line, with no bottom margin
+    send_packet(&mut printer, 0x04, 0, &tile_rows(3));
+    send_packet(&mut printer, 0x02, 0, &[0x01, 0x10, 0xE4, 0x40]);
+    assert!(printer.get_last_print().is_none());
+
+    // Two copies, lighter than normal, then finished by the bottom margin
+    send_packet(&mut printer, 0x01, 0, &[]);
+    send_packet(&mut printer, 0x04, 0, &tile_rows(3));
+    send_packet(&mut printer, 0x02, 0, &[0x02, 0x02, 0xE4, 0x00]);
+
+    let image = printer.get_last_print().unwrap();
+    assert_eq!(image.len(), PRINT_WIDTH * (8 + 16 * 3));
+    assert!(image[..(PRINT_WIDTH * 8)].iter().all(|&p| p == 0xFF));
+    assert!(image[(PRINT_WIDTH * 8)..(PRINT_WIDTH * 24)].iter().all(|&p| p == 0x00));
+    assert!(image[(PRINT_WIDTH * 24)..].iter().all(|&p| p == 0x40));
+
+    // A feed on its own doesn't replace the last printout
+    send_packet(&mut printer, 0x01, 0, &[]);
+    send_packet(&mut printer, 0x02, 0, &[0x00, 0x03, 0xE4, 0x40]);
+    assert_eq!(printer.get_last_print().unwrap().len(), PRINT_WIDTH * (8 + 16 * 3));
+}
+
+#[test]
+/// Test that encoded images have a valid PNG layout
+fn test_png_encode() {
+    let png = encode_png(3, 2, ColorType::Gray, &[0, 1, 2, 3, 4, 5]);
+    assert_eq!(&png[..8], &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']);
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..24], &[0, 0, 0, 3, 0, 0, 0, 2]);
+    assert_eq!(&png[(png.len() - 8)..(png.len() - 4)], b"IEND");
+
+    // IDAT holds a zlib stream of filtered scanlines
+    let idat_len = u32::from_be_bytes([png[33], png[34], png[35], png[36]]) as usize;
+    assert_eq!(&png[37..41], b"IDAT");
+    let zlib = &png[41..(41 + idat_len)];
+    assert_eq!(&zlib[7..15], &[0, 0, 1, 2, 0, 3, 4, 5]);
+}
@@ -0,0 +1,135 @@
+extern crate songbird_core;
+
+mod common;
+
+use common::counter_rom;
+use songbird_core::cpu::*;
+use songbird_core::link::LinkedPair;
+use songbird_core::rewind::*;
+
+#[test]
+/// Test that snapshots come back out newest first, unchanged
+fn test_rewind_order() {
+    let mut rewind = Rewind::new(DEFAULT_BUDGET, 1);
+    let mut states = Vec::new();
+    for i in 0..10u8 {
+        let mut state = vec![0; 0x1000];
+        state[i as usize] = i;
+        state[0x800 + i as usize * 3] = 0xFF - i;
+        // Lengths may change, such as after loading a game with more cartridge RAM
+        state.resize(0x1000 + i as usize, i);
+        rewind.push(&state);
+        states.push(state);
+    }
+    assert_eq!(rewind.len(), 10);
+
+    while let Some(state) = states.pop() {
+        assert_eq!(rewind.pop(), Some(state));
+    }
+    assert_eq!(rewind.pop(), None);
+    assert!(rewind.is_empty());
+}
+
+#[test]
+/// Test that unchanged data compresses, and old snapshots are dropped once over budget
+fn test_rewind_budget() {
+    let state = vec![0x55; 0x8000];
+    let mut rewind = Rewind::new(0x9000, 1);
+    for _ in 0..10 {
+        rewind.push(&state);
+    }
+    // Identical snapshots need only a few bytes each
+    assert_eq!(rewind.len(), 10);
+    assert!(rewind.get_size() < 0x8000 + 10 * 0x200);
+
+    let mut rewind = Rewind::new(0x9000, 1);
+    for i in 0..10u8 {
+        rewind.push(&vec![i; 0x8000]);
+    }
+    assert!(rewind.get_size() <= 0x9000);
+    assert!(rewind.len() < 10);
+    assert_eq!(rewind.pop(), Some(vec![9; 0x8000]));
+}
+
+#[test]
+/// Test that the CPU can step back to an earlier frame
+fn test_cpu_rewind() {
+    let mut gb = Cpu::new();
+    gb.load_game(&counter_rom(0));
+    assert!(!gb.rewind());
+
+    gb.enable_rewind(DEFAULT_BUDGET, 2);
+    let mut frames = Vec::new();
+    while frames.len() < 6 {
+        if gb.tick() {
+            frames.push(gb.save_state());
+        }
+    }
+
+    // Snapshots were taken every other frame
+    assert!(gb.rewind());
+    assert_eq!(gb.save_state(), frames[5]);
+    assert!(gb.rewind());
+    assert_eq!(gb.save_state(), frames[3]);
+    assert!(gb.rewind());
+    assert_eq!(gb.save_state(), frames[1]);
+    assert!(!gb.rewind());
+}
+
+/// Runs until the given number of frames have finished
+fn run_frames(gb: &mut Cpu, frames: usize) {
+    let mut done = 0;
+    while done < frames {
+        if gb.tick() {
+            done += 1;
+        }
+    }
+}
+
+#[test]
+/// Test that loading a save state discards snapshots from before it
+fn test_load_clears_rewind() {
+    let mut gb = Cpu::new();
+    gb.load_game(&counter_rom(0));
+    gb.enable_rewind(DEFAULT_BUDGET, 1);
+    let state = gb.save_state();
+    run_frames(&mut gb, 3);
+
+    gb.load_state(&state).unwrap();
+    assert!(!gb.rewind());
+    assert_eq!(gb.save_state(), state);
+}
+
+#[test]
+/// Test that rewinding is turned off while linked to another emulator
+fn test_linked_rewind() {
+    let mut first = Cpu::new();
+    first.load_game(&counter_rom(0));
+    first.enable_rewind(DEFAULT_BUDGET, 1);
+    run_frames(&mut first, 3);
+    let mut second = Cpu::new();
+    second.load_game(&counter_rom(0));
+
+    let mut pair = LinkedPair::new(first, second);
+    for _ in 0..3 {
+        pair.run_frame();
+    }
+    assert!(!pair.first_mut().rewind());
+
+    // Snapshots from before the link was connected are gone too
+    let (mut first, _) = pair.into_inner();
+    first.disconnect_serial();
+    assert!(!first.rewind());
+}
+
+#[test]
+/// Test that loading a game discards snapshots of whatever ran before
+fn test_load_game_clears_rewind() {
+    let mut gb = Cpu::new();
+    gb.load_game(&counter_rom(0));
+    gb.enable_rewind(DEFAULT_BUDGET, 1);
+    run_frames(&mut gb, 3);
+
+    gb.load_game(&counter_rom(1));
+    assert!(!gb.rewind());
+}
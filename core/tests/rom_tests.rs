@@ -1,6 +1,6 @@
 extern crate songbird_core;
 
-use songbird_core::cartridge::MBC;
+use songbird_core::cartridge::{Cart, MBC};
 use songbird_core::cpu::*;
 use std::fs::File;
 use std::io::Read;
@@ -9,12 +9,31 @@ use std::io::Read;
 // TODO: Someday make your own ROM you can use for unit tests
 const GAME_PATH: &str = "tests/Tetris.gb";
 
+/// Loads the test ROM, if it's been put in place
+fn load_test_rom() -> Option<Vec<u8>> {
+    let mut buffer: Vec<u8> = Vec::new();
+    match File::open(GAME_PATH) {
+        Ok(mut f) => {
+            f.read_to_end(&mut buffer).expect("Error reading to buffer");
+            Some(buffer)
+        },
+        Err(_) => {
+            eprintln!("Skipping, {} not found", GAME_PATH);
+            None
+        }
+    }
+}
+
 #[test]
 /// Tests that the title can be extracted from ROM header
 fn test_title() {
+    let rom = match load_test_rom() {
+        Some(rom) => { rom },
+        None => { return; }
+    };
     let mut gb = Cpu::new();
-    gb.load_game(GAME_PATH);
-    let raw_title = String::from(gb.bus.get_title());
+    gb.load_game(&rom);
+    let raw_title = String::from(gb.get_title());
 
     // Remove trailing null characters from string
     let title = raw_title.trim_end_matches(char::from(0));
@@ -24,11 +43,14 @@ fn test_title() {
 #[test]
 /// Tests that the MBC type can be fetched from ROM header
 fn test_get_mbc() {
-    let mut gb = Cpu::new();
-    gb.load_game(GAME_PATH);
-    let mbc = gb.bus.get_mbc();
+    let rom = match load_test_rom() {
+        Some(rom) => { rom },
+        None => { return; }
+    };
+    let mut cart = Cart::new();
+    cart.load_cart(&rom);
 
-    match mbc {
+    match cart.get_mbc() {
         MBC::NONE => { /* Correct, do nothing */ },
         _ =>         { panic!("Incorrect")       }
     }
@@ -37,18 +59,15 @@ fn test_get_mbc() {
 #[test]
 /// Tests that the entire ROM is loaded when there is no MBC
 fn test_mbc_none() {
+    let rom = match load_test_rom() {
+        Some(rom) => { rom },
+        None => { return; }
+    };
     let mut gb = Cpu::new();
-    gb.load_game(GAME_PATH);
-    // TODO: Check that mbc is NONE
-    let ram = gb.bus.get_ram();
-
-    // Load game file into a buffer
-    let mut buffer: Vec<u8> = Vec::new();
-    let mut f = File::open(GAME_PATH).expect("Error opening test ROM");
-    f.read_to_end(&mut buffer).expect("Error reading to buffer");
+    gb.load_game(&rom);
 
-    // Ensure that RAM values equal those in the buffer
-    for i in 0..buffer.len() {
-        assert_eq!(ram[i], buffer[i]);
+    // Ensure that memory values equal those in the ROM
+    for (addr, byte) in rom.iter().enumerate() {
+        assert_eq!(gb.read_ram(addr as u16), *byte);
     }
 }
@@ -0,0 +1,76 @@
+extern crate songbird_core;
+
+mod common;
+
+use common::{load_program, program_rom, serial_program};
+use songbird_core::cpu::Cpu;
+use songbird_core::runner::*;
+
+/// Creates a program which signals Mooneye's "test done" with the given B-L registers
+fn mooneye_program(regs: [u8; 6]) -> Vec<u8> {
+    // LD B,n; LD C,n; LD D,n; LD E,n; LD H,n; LD L,n; LD B,B; JR -2
+    let mut program = Vec::new();
+    for (opcode, val) in [0x06, 0x0E, 0x16, 0x1E, 0x26, 0x2E].iter().zip(regs.iter()) {
+        program.extend_from_slice(&[*opcode, *val]);
+    }
+    program.extend_from_slice(&[0x40, 0x18, 0xFE]);
+    program
+}
+
+#[test]
+/// Test that Blargg's serial results are detected, and looping without them fails
+fn test_runner_serial() {
+    let mut gb = load_program(&serial_program(b"Test\nPassed\n"));
+    let report = TestRunner::blargg().run(&mut gb);
+    assert!(report.outcome.is_pass());
+    assert_eq!(report.serial, b"Test\nPassed");
+
+    let mut gb = load_program(&serial_program(b"Failed\n"));
+    let report = TestRunner::blargg().run(&mut gb);
+    assert!(!report.outcome.is_pass());
+    assert_eq!(report.serial, b"Failed\n");
+}
+
+#[test]
+/// Test that Mooneye's register signature is checked
+fn test_runner_mooneye() {
+    let mut gb = load_program(&mooneye_program([3, 5, 8, 13, 21, 34]));
+    assert!(TestRunner::mooneye().run(&mut gb).outcome.is_pass());
+
+    let mut gb = load_program(&mooneye_program([0x42; 6]));
+    assert!(!TestRunner::mooneye().run(&mut gb).outcome.is_pass());
+}
+
+#[test]
+/// Test the frame limit, and that a runner with no detectors passes once it's reached
+fn test_runner_frames() {
+    let mut gb = load_program(&[0x18, 0xFE]);
+    let report = TestRunner::new(5).run(&mut gb);
+    assert!(report.outcome.is_pass());
+    assert_eq!(report.frames, 5);
+
+    let mut gb = load_program(&[0x18, 0xFE]);
+    let runner = TestRunner { pc_loop: true, ..TestRunner::new(60) };
+    let report = runner.run(&mut gb);
+    assert!(report.outcome.is_pass());
+    assert!(report.frames < 60);
+}
+
+#[test]
+/// Test that a loop the game later leaves has to start over before it counts as stuck
+fn test_runner_loop_left() {
+    // LD A,$01; LDH (IE),A; EI; JR -2
+    let mut rom = program_rom(&[0x3E, 0x01, 0xE0, 0xFF, 0xFB, 0x18, 0xFE]);
+    // VBlank handler leaves the loop for good: POP HL; JP $0150
+    rom[0x0040..0x0044].copy_from_slice(&[0xE1, 0xC3, 0x50, 0x01]);
+    // Counts BC down from $10000, about 26 frames, then goes back to the loop with interrupts off
+    // LD BC,$0000; DEC BC; LD A,B; OR C; JR NZ,-5; JP $0105
+    rom[0x0150..0x015B].copy_from_slice(&[0x01, 0x00, 0x00, 0x0B, 0x78, 0xB1, 0x20, 0xFB, 0xC3, 0x05, 0x01]);
+    let mut gb = Cpu::new();
+    gb.load_game(&rom);
+
+    let runner = TestRunner { pc_loop: true, ..TestRunner::new(120) };
+    let report = runner.run(&mut gb);
+    assert!(report.outcome.is_pass());
+    assert!(report.frames >= 30);
+}
@@ -0,0 +1,125 @@
+extern crate songbird_core;
+
+mod common;
+
+use common::{program_rom, serial_program};
+use songbird_core::cpu::*;
+use songbird_core::serial::*;
+
+use std::sync::{Arc, Mutex};
+
+const IF: u16 = 0xFF0F;
+const SERIAL_FLAG: u8 = 0b1000;
+
+// JR -2
+const LOOP: [u8; 2] = [0x18, 0xFE];
+
+/// Runs until at least the given number of M-cycles have passed
+fn run_cycles(gb: &mut Cpu, cycles: u64) {
+    let end = gb.get_cycles() + cycles;
+    while gb.get_cycles() < end {
+        gb.tick();
+    }
+}
+
+/// Records bytes sent to it, replying with a fixed byte
+struct Echo {
+    sent: Arc<Mutex<Vec<u8>>>,
+    reply: u8,
+}
+
+impl SerialDevice for Echo {
+    fn transfer(&mut self, byte: u8) -> u8 {
+        self.sent.lock().unwrap().push(byte);
+        self.reply
+    }
+}
+
+#[test]
+/// Test that an internally clocked transfer takes 8 bits at 8192 Hz, then raises the interrupt
+fn test_serial_timing() {
+    let mut gb = Cpu::new();
+    gb.load_game(&program_rom(&LOOP));
+    gb.write_ram(IF, 0x00);
+
+    gb.write_ram(0xFF01, 0x42);
+    gb.write_ram(0xFF02, 0x81);
+    run_cycles(&mut gb, 1000);
+    assert_eq!(gb.read_ram(0xFF02), 0xFF);
+    assert_eq!(gb.read_ram(IF) & SERIAL_FLAG, 0);
+
+    run_cycles(&mut gb, 100);
+    assert_eq!(gb.read_ram(0xFF02), 0x7F);
+    assert_ne!(gb.read_ram(IF) & SERIAL_FLAG, 0);
+    // Nothing connected, so only 1 bits came in
+    assert_eq!(gb.read_ram(0xFF01), 0xFF);
+}
+
+#[test]
+/// Test that bytes are exchanged with a connected device
+fn test_serial_device() {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let mut gb = Cpu::new();
+    gb.load_game(&program_rom(&LOOP));
+    gb.connect_serial(Box::new(Echo { sent: sent.clone(), reply: 0x5A }));
+
+    gb.write_ram(0xFF01, 0x42);
+    gb.write_ram(0xFF02, 0x81);
+    // Halfway through, half the incoming byte has been shifted in
+    run_cycles(&mut gb, 4 * 128);
+    assert_eq!(gb.read_ram(0xFF01), 0x25);
+    run_cycles(&mut gb, 4 * 128 + 16);
+    assert_eq!(gb.read_ram(0xFF01), 0x5A);
+    assert_eq!(*sent.lock().unwrap(), vec![0x42]);
+
+    assert!(gb.disconnect_serial().is_some());
+    assert!(gb.disconnect_serial().is_none());
+}
+
+#[test]
+/// Test that an externally clocked transfer waits for the other side
+fn test_serial_external() {
+    let mut gb = Cpu::new();
+    gb.load_game(&program_rom(&LOOP));
+    gb.write_ram(IF, 0x00);
+
+    gb.write_ram(0xFF01, 0x42);
+    gb.write_ram(0xFF02, 0x80);
+    run_cycles(&mut gb, 10000);
+    assert_eq!(gb.read_ram(0xFF02), 0xFE);
+    assert_eq!(gb.read_ram(0xFF01), 0x42);
+    assert_eq!(gb.read_ram(IF) & SERIAL_FLAG, 0);
+}
+
+#[test]
+/// Test that bytes sent by the game can be captured, as test ROMs report results
+fn test_serial_capture() {
+    let mut gb = Cpu::new();
+    gb.load_game(&program_rom(&serial_program(b"Hi")));
+    gb.set_serial_capture(true);
+    run_cycles(&mut gb, 3000);
+    assert_eq!(gb.take_serial_output(), b"Hi");
+
+    // Nothing is kept once capture is turned off
+    gb.set_serial_capture(false);
+    gb.write_ram(0xFF02, 0x81);
+    run_cycles(&mut gb, 1100);
+    assert!(gb.take_serial_output().is_empty());
+}
+
+#[test]
+/// Test that an emulator with a device plugged in can still be moved to another thread
+fn test_serial_send() {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let mut gb = Cpu::new();
+    gb.load_game(&program_rom(&LOOP));
+    gb.connect_serial(Box::new(Echo { sent, reply: 0x00 }));
+    // Boxed, as the whole system is too large for a new thread's stack
+    let mut gb = Box::new(gb);
+
+    let handle = std::thread::spawn(move || {
+        gb.tick();
+        gb.get_cycles()
+    });
+    assert!(handle.join().unwrap() > 0);
+}
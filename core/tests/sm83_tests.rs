@@ -0,0 +1,178 @@
+extern crate songbird_core;
+
+use songbird_core::cpu::*;
+use songbird_core::cpu::opcodes::execute;
+use songbird_core::memory::{Access, AccessKind, FlatMemory, MemoryInterface, RecordingBus};
+
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// The SM83 single step tests aren't distributed with the emulator, so they're looked for here,
+// with one JSON file per opcode, such as "00.json" or "cb 00.json"
+const DEFAULT_TEST_DIR: &str = "tests/sm83";
+// A few cases in the same format kept with the repo, so the harness itself always gets run
+const FIXTURE_DIR: &str = "tests/fixtures/sm83";
+const TEST_DIR_VAR: &str = "SONGBIRD_SM83_TESTS";
+const REPORT_NAME: &str = "results.md";
+
+const IE: u16 = 0xFFFF;
+
+const REGS: [(&str, Regs); 8] = [
+    ("a", Regs::A), ("b", Regs::B), ("c", Regs::C), ("d", Regs::D),
+    ("e", Regs::E), ("f", Regs::F), ("h", Regs::H), ("l", Regs::L),
+];
+
+type TestCpu = Cpu<RecordingBus<FlatMemory>>;
+
+/// Reads a number from a JSON object, if present
+fn get_num(obj: &Value, key: &str) -> Option<u64> {
+    obj.get(key).and_then(|v| v.as_u64())
+}
+
+/// Reads the list of [address, value] pairs from a test state
+fn get_ram(state: &Value) -> Vec<(u16, u8)> {
+    state["ram"].as_array().map(|ram| {
+        ram.iter().map(|pair| (pair[0].as_u64().unwrap() as u16, pair[1].as_u64().unwrap() as u8)).collect()
+    }).unwrap_or_default()
+}
+
+/// Reads the expected memory accesses, skipping cycles where the bus is idle
+fn get_accesses(cycles: &Value) -> Vec<Access> {
+    let mut accesses = Vec::new();
+    for cycle in cycles.as_array().into_iter().flatten() {
+        let kind = match cycle.get(2).and_then(|k| k.as_str()) {
+            Some(kind) if kind.contains('r') => { AccessKind::Read },
+            Some(kind) if kind.contains('w') => { AccessKind::Write },
+            _ => { continue; }
+        };
+        let addr = cycle[0].as_u64().unwrap() as u16;
+        let val = cycle[1].as_u64().unwrap_or(0) as u8;
+        accesses.push(Access { addr, val, kind });
+    }
+    accesses
+}
+
+/// Creates a CPU in a test's initial state
+fn setup(initial: &Value) -> TestCpu {
+    let mut ram = FlatMemory::new();
+    for (addr, val) in get_ram(initial) {
+        ram.load(addr, &[val]);
+    }
+    if let Some(ie) = get_num(initial, "ie") {
+        ram.load(IE, &[ie as u8]);
+    }
+
+    let mut cpu = Cpu::with_bus(RecordingBus::new(ram));
+    for (name, reg) in REGS.iter() {
+        cpu.set_reg(*reg, get_num(initial, name).unwrap() as u8);
+    }
+    cpu.set_pc(get_num(initial, "pc").unwrap() as u16);
+    cpu.set_sp(get_num(initial, "sp").unwrap() as u16);
+    cpu.set_ime(get_num(initial, "ime").unwrap_or(0) != 0);
+    cpu
+}
+
+/// Runs one test case, returning a description of what didn't match, if anything
+fn run_case(case: &Value) -> Option<String> {
+    let expected = &case["final"];
+    let mut cpu = setup(&case["initial"]);
+    let cycles = execute(&mut cpu);
+    let mut errors = Vec::new();
+
+    for (name, reg) in REGS.iter() {
+        let want = get_num(expected, name).unwrap() as u8;
+        let got = cpu.get_reg(*reg);
+        if want != got {
+            errors.push(format!("{}: ${:02X} != ${:02X}", name.to_uppercase(), got, want));
+        }
+    }
+    let pc = get_num(expected, "pc").unwrap() as u16;
+    if cpu.get_pc() != pc {
+        errors.push(format!("PC: ${:04X} != ${:04X}", cpu.get_pc(), pc));
+    }
+    let sp = get_num(expected, "sp").unwrap() as u16;
+    if cpu.get_sp() != sp {
+        errors.push(format!("SP: ${:04X} != ${:04X}", cpu.get_sp(), sp));
+    }
+    if let Some(ime) = get_num(expected, "ime") {
+        if cpu.get_ime() != (ime != 0) {
+            errors.push(format!("IME: {} != {}", cpu.get_ime() as u8, ime));
+        }
+    }
+    for (addr, want) in get_ram(expected) {
+        let got = cpu.get_bus().get_inner().read_ram(addr);
+        if got != want {
+            errors.push(format!("${:04X}: ${:02X} != ${:02X}", addr, got, want));
+        }
+    }
+
+    let want_cycles = case["cycles"].as_array().map(|c| c.len()).unwrap_or(0);
+    if cycles as usize != want_cycles {
+        errors.push(format!("cycles: {} != {}", cycles, want_cycles));
+    }
+    let want_accesses = get_accesses(&case["cycles"]);
+    if cpu.get_bus().take_accesses() != want_accesses {
+        errors.push("bus activity differs".to_string());
+    }
+
+    if errors.is_empty() {
+        None
+    } else {
+        Some(format!("{}: {}", case["name"].as_str().unwrap_or("?"), errors.join(", ")))
+    }
+}
+
+/// Runs every test file in a folder, returning a report table and the number of mismatched cases
+fn run_dir(test_dir: &Path) -> (String, usize) {
+    let mut files: Vec<PathBuf> = fs::read_dir(test_dir).expect("Error reading test folder")
+        .filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    files.retain(|f| f.extension().is_some_and(|e| e == "json"));
+    files.sort();
+    assert!(!files.is_empty(), "No SM83 tests found in {}", test_dir.display());
+
+    let mut report = String::from("| Opcode | Passed | First mismatch |\n| ------ | ------ | -------------- |\n");
+    let mut total_failed = 0;
+    for file in files {
+        let data = fs::read_to_string(&file).expect("Error reading test file");
+        let cases: Value = serde_json::from_str(&data).expect("Error parsing test file");
+        let cases = cases.as_array().expect("Test file should hold a list of cases");
+
+        let failures: Vec<String> = cases.iter().filter_map(run_case).collect();
+        total_failed += failures.len();
+        let opcode = file.file_stem().unwrap().to_string_lossy().to_uppercase();
+        let first = failures.first().cloned().unwrap_or_default();
+        report.push_str(&format!("| {} | {}/{} | {} |\n", opcode, cases.len() - failures.len(), cases.len(), first));
+    }
+    (report, total_failed)
+}
+
+#[test]
+/// Runs the SM83 cases kept with the repo, which should all pass
+fn test_sm83_fixtures() {
+    let (report, total_failed) = run_dir(Path::new(FIXTURE_DIR));
+    assert_eq!(total_failed, 0, "\n{}", report);
+}
+
+#[test]
+/// Runs every SM83 single step test found, reporting mismatches for each opcode
+fn test_sm83() {
+    let test_dir = match env::var(TEST_DIR_VAR) {
+        // Asked for by name, so it has to be there
+        Ok(dir) => { PathBuf::from(dir) },
+        Err(_) => {
+            let test_dir = PathBuf::from(DEFAULT_TEST_DIR);
+            if !test_dir.is_dir() {
+                println!("Full SM83 suite not found in {}, set {} to run it", test_dir.display(), TEST_DIR_VAR);
+                return;
+            }
+            test_dir
+        }
+    };
+
+    let (report, total_failed) = run_dir(&test_dir);
+    fs::write(test_dir.join(REPORT_NAME), &report).expect("Error writing report");
+    println!("{}", report);
+    println!("{} mismatched cases", total_failed);
+}
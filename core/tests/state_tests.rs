@@ -0,0 +1,118 @@
+extern crate songbird_core;
+
+mod common;
+
+use common::counter_rom;
+use songbird_core::cpu::*;
+use songbird_core::state::*;
+use songbird_core::utils::crc32;
+
+#[test]
+/// Test checksum against known value
+fn test_crc32() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+/// Test that loading a state restores the machine to where it was saved
+fn test_save_load() {
+    let mut gb = Cpu::new();
+    gb.load_game(&counter_rom(0));
+    for _ in 0..1000 {
+        gb.tick();
+    }
+    gb.write_ram(0xC123, 0x45);
+    gb.write_ram(0x8010, 0xFF);
+
+    let state = gb.save_state();
+    let pc = gb.get_pc();
+    let a = gb.get_reg(Regs::A);
+    let screen = gb.render().to_vec();
+
+    for _ in 0..1000 {
+        gb.tick();
+    }
+    gb.write_ram(0xC123, 0x00);
+    gb.write_ram(0x8010, 0x00);
+
+    gb.load_state(&state).unwrap();
+    assert_eq!(gb.get_pc(), pc);
+    assert_eq!(gb.get_reg(Regs::A), a);
+    assert_eq!(gb.read_ram(0xC123), 0x45);
+    assert_eq!(gb.read_ram(0x8010), 0xFF);
+    assert_eq!(gb.render().to_vec(), screen);
+
+    // Saving again gives an identical state
+    assert_eq!(gb.save_state(), state);
+}
+
+#[test]
+/// Test that invalid states are rejected
+fn test_bad_states() {
+    let mut gb = Cpu::new();
+    gb.load_game(&counter_rom(0));
+    let state = gb.save_state();
+
+    let mut other = Cpu::new();
+    other.load_game(&counter_rom(1));
+    assert_eq!(other.load_state(&state), Err(StateError::WrongRom));
+
+    assert_eq!(gb.load_state(&state[..state.len() - 1]), Err(StateError::Truncated));
+    assert_eq!(gb.load_state(b"not a state"), Err(StateError::BadMagic));
+
+    let mut old = state.clone();
+    old[4] = 0;
+    assert_eq!(gb.load_state(&old), Err(StateError::Version(0)));
+}
+
+#[test]
+/// Test that a state which is only found to be invalid near its end leaves the machine untouched
+fn test_invalid_state_unchanged() {
+    let mut gb = Cpu::new();
+    gb.load_game(&counter_rom(0));
+    gb.write_ram(0xC000, 0x12);
+    let mut bad = gb.save_state();
+    // The serial port is saved last, ending with its bits left (u8) and cycle count (u16)
+    let bits_left = bad.len() - 3;
+    bad[bits_left] = 0xFF;
+
+    for _ in 0..1000 {
+        gb.tick();
+    }
+    gb.write_ram(0xC000, 0x34);
+    let before = gb.save_state();
+
+    assert_eq!(gb.load_state(&bad), Err(StateError::Invalid));
+    assert_eq!(gb.read_ram(0xC000), 0x34);
+    assert_eq!(gb.save_state(), before);
+}
+
+#[test]
+/// Test that states only record whether the boot ROM is mapped, not its contents
+fn test_state_boot_rom() {
+    // JR -2, padded out to a DMG boot ROM
+    let mut boot_rom = vec![0; 0x100];
+    boot_rom[..2].copy_from_slice(&[0x18, 0xFE]);
+    let mut gb = Cpu::new();
+    gb.load_boot_rom(&boot_rom);
+    gb.load_game(&counter_rom(0));
+    for _ in 0..1000 {
+        gb.tick();
+    }
+    let state = gb.save_state();
+
+    let mut plain = Cpu::new();
+    plain.load_game(&counter_rom(0));
+    assert_eq!(state.len(), plain.save_state().len());
+
+    // Still running the boot ROM, which this one doesn't have
+    assert_eq!(plain.load_state(&state), Err(StateError::NoBootRom));
+    assert_eq!(plain.read_ram(0x0000), 0x00);
+
+    let mut booted = Cpu::new();
+    booted.load_boot_rom(&boot_rom);
+    booted.load_game(&counter_rom(0));
+    booted.load_state(&state).unwrap();
+    assert_eq!(booted.get_pc(), 0x0000);
+    assert_eq!(booted.read_ram(0x0000), 0x18);
+}
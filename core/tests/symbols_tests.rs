@@ -0,0 +1,72 @@
+extern crate songbird_core;
+
+use songbird_core::debug::disasm::*;
+use songbird_core::debug::symbols::*;
+
+const SYM_FILE: &str = "; File generated by rgblink
+00:0100 Start
+00:0150 Main
+00:0153 Main.loop ; inner loop
+01:4000 LoadTiles
+02:4000 PlayMusic
+00:c000 wCounter
+not a symbol
+";
+
+#[test]
+/// Labels are read from an RGBDS sym file, skipping comments and bad lines
+fn test_parse() {
+    let symbols = Symbols::parse(SYM_FILE);
+    assert_eq!(symbols.len(), 6);
+    assert_eq!(symbols.get_addr("Main.loop"), Some((0, 0x0153)));
+    assert_eq!(symbols.get_addr("PlayMusic"), Some((2, 0x4000)));
+    assert_eq!(symbols.get_addr("Missing"), None);
+}
+
+#[test]
+/// Switchable ROM is looked up using the mapped bank
+fn test_get_label() {
+    let symbols = Symbols::parse(SYM_FILE);
+    assert_eq!(symbols.get_label(0x0150, 1), Some("Main"));
+    assert_eq!(symbols.get_label(0x4000, 1), Some("LoadTiles"));
+    assert_eq!(symbols.get_label(0x4000, 2), Some("PlayMusic"));
+    assert_eq!(symbols.get_label(0x4000, 3), None);
+    assert_eq!(symbols.get_label(0xC000, 5), Some("wCounter"));
+
+    // Outside switchable ROM, bank 0 wins, then the lowest bank with a label there
+    let mut symbols = Symbols::parse(SYM_FILE);
+    symbols.add(3, 0xD000, "wBank3");
+    symbols.add(1, 0xD000, "wBank1");
+    assert_eq!(symbols.get_label(0xD000, 1), Some("wBank1"));
+    symbols.add(0, 0xD000, "wShared");
+    assert_eq!(symbols.get_label(0xD000, 1), Some("wShared"));
+    assert_eq!(symbols.get_label(0xD001, 1), None);
+    let symbols = Symbols::parse(SYM_FILE);
+
+    assert_eq!(symbols.get_nearest_label(0x0155, 1), Some(("Main.loop", 2)));
+    assert_eq!(symbols.get_nearest_label(0x4010, 2), Some(("PlayMusic", 0x10)));
+    // ROM labels don't describe RAM
+    assert_eq!(symbols.get_nearest_label(0xC100, 1), Some(("wCounter", 0x100)));
+    assert_eq!(symbols.get_nearest_label(0x8000, 1), None);
+}
+
+#[test]
+/// Disassembly names jump targets and addresses which have labels
+fn test_labelled_disassembly() {
+    let symbols = Symbols::parse(SYM_FILE);
+    let label = |addr: u16| symbols.get_label(addr, 1).map(|l| l.to_string());
+
+    let inst = decode(&[0x18, 0xFE], 0x0153).unwrap();
+    assert_eq!(inst.format_with(label), "JR Main.loop");
+    let inst = decode(&[0xCD, 0x00, 0x40], 0x0155).unwrap();
+    assert_eq!(inst.format_with(label), "CALL LoadTiles");
+    let inst = decode(&[0xFA, 0x00, 0xC0], 0x0158).unwrap();
+    assert_eq!(inst.format_with(label), "LD A, (wCounter)");
+
+    let mut rom = vec![0; 0x8000];
+    rom[0x0153..0x0155].copy_from_slice(&[0x18, 0xFE]);
+    let listing = export_rom(&rom, Some(&symbols));
+    assert!(listing.contains("Main.loop:\n00:0153  18 FE     JR Main.loop\n"));
+    assert!(listing.contains("LoadTiles:\n01:4000"));
+    assert!(!listing.contains("PlayMusic"));
+}
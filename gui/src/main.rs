@@ -10,10 +10,18 @@ extern crate glium;
 #[macro_use]
 extern crate imgui;
 
+#[cfg(feature = "audio")]
+use songbird_audio::Audio;
 use crate::menu::{MenuState, DisplayOptions, Shaders};
 use songbird_core::cpu::Cpu;
+use songbird_core::cpu::model::Model;
 use songbird_core::io::Buttons;
+use songbird_core::link::Link;
+use songbird_core::pacer::{PaceMode, Pacer};
 use songbird_core::ppu::palette::Palettes;
+use songbird_core::printer::Printer;
+use songbird_core::rewind::{DEFAULT_BUDGET, DEFAULT_INTERVAL};
+use songbird_core::serial::SerialDevice;
 use songbird_core::utils::{SCREEN_HEIGHT, SCREEN_WIDTH};
 
 use imgui::Context;
@@ -35,6 +43,7 @@ use std::env;
 use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
 use std::io::Read;
+use std::time::{Duration, Instant};
 
 // Constants
 const SCALE: usize = 5;
@@ -42,6 +51,7 @@ const WINDOW_WIDTH: u32 = (SCREEN_WIDTH * SCALE) as u32;
 const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT * SCALE) as u32;
 const IMGUI_OFFSET: u32 = 8;
 const MENU_BAR_HEIGHT: u32 = 11;
+const MENU_FRAME_TIME: u64 = 16667; // In microseconds
 
 #[derive(Copy, Clone)]
 struct Vertex {
@@ -51,15 +61,52 @@ struct Vertex {
 implement_vertex!(Vertex, position);
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
-    let filename = if args.len() > 1 {
-        Some(args[1].clone())
-    } else {
-        None
+    let mut filename = None;
+    let mut link = None;
+    let mut printer = false;
+    // The printer plugs into the link port, so can't be used alongside a link
+    let args: Vec<String> = env::args().skip(1).collect();
+    let linked = args.iter().any(|arg| arg == "--link" || arg == "--link-listen");
+    if linked && args.iter().any(|arg| arg == "--printer") {
+        println!("--printer can't be used with --link or --link-listen");
+        return;
+    }
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--link" => {
+                let addr = args.next().unwrap_or_default();
+                link = Some(Link::connect(addr.as_str()));
+            },
+            "--link-listen" => {
+                let arg = args.next().unwrap_or_default();
+                let port = match arg.parse() {
+                    Ok(port) => { port },
+                    Err(_) => {
+                        println!("Invalid port for --link-listen: '{}'", arg);
+                        return;
+                    }
+                };
+                println!("Waiting for link cable connection on port {}...", port);
+                link = Some(Link::listen(port));
+            },
+            "--printer" => { printer = true; },
+            _ => { filename = Some(arg); }
+        }
+    }
+
+    // The link cable is plugged into whichever game gets loaded
+    let link: Option<Box<dyn SerialDevice + Send>> = match link {
+        Some(Ok(link)) => { Some(Box::new(link)) },
+        Some(Err(e)) => {
+            println!("Unable to connect link cable: {}", e);
+            None
+        },
+        None => { None }
     };
 
     let is = ImguiSystem::new();
-    is.main_loop(filename);
+    is.main_loop(filename, link, printer);
 }
 
 // Imgui interface taken from here: https://gist.github.com/RainbowCookie32/7e5d76acf33d88f2145d5ebc047a5799
@@ -80,7 +127,7 @@ impl Default for ImguiSystem {
 impl ImguiSystem {
     pub fn new() -> ImguiSystem {
         let event_loop = EventLoop::new();
-        let cb = ContextBuilder::new().with_vsync(true);
+        let cb = ContextBuilder::new().with_vsync(false);
         let wb = WindowBuilder::new().with_inner_size(LogicalSize {
                     width: WINDOW_WIDTH,
                     height: WINDOW_HEIGHT + MENU_BAR_HEIGHT + IMGUI_OFFSET,
@@ -110,8 +157,13 @@ impl ImguiSystem {
     /// Main event loop
     ///
     /// Passes control of emulation to the event loop, which runs forever
+    ///
+    /// Inputs:
+    ///     Game to load at startup (Option<String>)
+    ///     Link cable to connect to the game (Option<Box<dyn SerialDevice + Send>>)
+    ///     Whether to connect a printer instead, saving prints next to the game (bool)
     /// ```
-    pub fn main_loop(self, filename: Option<String>) {
+    pub fn main_loop(self, filename: Option<String>, mut link: Option<Box<dyn SerialDevice + Send>>, printer: bool) {
         let ImguiSystem {
             event_loop,
             display,
@@ -127,6 +179,10 @@ impl ImguiSystem {
         let mut gb = Cpu::new();
         let mut curr_disp_opts = DisplayOptions::new(Palettes::GRAYSCALE, Shaders::None);
         let mut running = false;
+        let mut rewinding = false;
+        let mut pacer = Pacer::new(PaceMode::WallClock);
+        #[cfg(feature = "audio")]
+        let mut audio = Audio::new();
 
         event_loop.run(move |event, _, control_flow| {
             let mut program = load_shader(&display, curr_disp_opts.shader);
@@ -155,18 +211,53 @@ impl ImguiSystem {
                         // Send keyboard inputs to emulator core
                         if let Some(btn) = key2btn(keycode) {
                             gb.toggle_button(btn, state == ElementState::Pressed);
+                        } else if keycode == VirtualKeyCode::R {
+                            rewinding = state == ElementState::Pressed;
                         }
                 },
                 Event::MainEventsCleared => {
                     let gl_window = display.gl_window();
                     platform.prepare_frame(imgui.io_mut(), &gl_window.window()).unwrap();
-                    gl_window.window().request_redraw();
+
+                    // While a game is running, only draw once the pacer says the next frame is due
+                    let delay = if running {
+                        #[cfg(feature = "audio")]
+                        let (queued, rate) = audio.as_ref().map_or((0, 0), |a| (a.get_queued(), a.get_sample_rate()));
+                        #[cfg(not(feature = "audio"))]
+                        let (queued, rate) = (0, 0);
+                        pacer.get_delay(gb.get_cycles(), queued, rate)
+                    } else {
+                        Duration::from_micros(MENU_FRAME_TIME)
+                    };
+
+                    if !running || delay == Duration::from_secs(0) {
+                        gl_window.window().request_redraw();
+                    }
+
+                    if *control_flow != ControlFlow::Exit {
+                        *control_flow = ControlFlow::WaitUntil(Instant::now() + delay);
+                    }
                 },
                 Event::RedrawRequested(_) => {
                     // If new file has been selected in menu, load that ROM into emulator
                     if main_menu.is_load_time() {
                         let filename = main_menu.get_rom_filename();
+                        let device: Option<Box<dyn SerialDevice + Send>> = if printer {
+                            Some(Box::new(Printer::new(Some(filename.to_string()))))
+                        } else {
+                            // Move the link cable over from the previous game, if any
+                            gb.disconnect_serial().or_else(|| link.take())
+                        };
                         setup_emu(&mut gb, filename);
+                        if let Some(device) = device {
+                            gb.connect_serial(device);
+                        }
+                        pacer.set_mode(PaceMode::WallClock, gb.get_cycles());
+                        #[cfg(feature = "audio")]
+                        if let Some(audio) = &audio {
+                            gb.set_sample_rate(audio.get_sample_rate());
+                            pacer.set_mode(PaceMode::Audio, gb.get_cycles());
+                        }
                         running = true;
                     }
 
@@ -187,8 +278,19 @@ impl ImguiSystem {
 
                     // Only run emulator and draw screen if ROM has actually been selected and loaded
                     if running {
-                        let filename = main_menu.get_rom_filename();
-                        tick_until_draw(&mut gb, filename);
+                        if rewinding {
+                            // Step back one snapshot per frame while the rewind key is held
+                            if gb.rewind() {
+                                pacer.reset(gb.get_cycles());
+                            }
+                        } else {
+                            let filename = main_menu.get_rom_filename();
+                            tick_until_draw(&mut gb, filename);
+                        }
+                        #[cfg(feature = "audio")]
+                        if let Some(audio) = &mut audio {
+                            audio.queue_samples(&mut gb);
+                        }
                         let disp_arr = gb.render();
 
                         let image = RawImage2d::from_raw_rgba_reversed(&disp_arr.to_vec(), (SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32));
@@ -322,10 +424,11 @@ fn key2btn(key: VirtualKeyCode) -> Option<Buttons> {
 /// ```
 fn setup_emu(gb: &mut Cpu, filename: &str) {
     // In case anything is currently running, simply make a new Cpu instance
-    *gb = Cpu::new();
     let rom = load_rom(filename);
+    *gb = Cpu::with_model(Model::detect(&rom));
     gb.load_game(&rom);
     load_battery_save(gb, filename);
+    gb.enable_rewind(DEFAULT_BUDGET, DEFAULT_INTERVAL);
 }
 
 /// ```
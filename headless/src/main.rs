@@ -0,0 +1,133 @@
+// Songbird Game Boy emulator headless test runner
+// Austin Bricker 2019-2020
+
+// Includes
+use songbird_core::cpu::Cpu;
+use songbird_core::cpu::model::Model;
+use songbird_core::png::{ColorType, encode_png};
+use songbird_core::runner::{BLARGG_PASS_TEXT, DEFAULT_MAX_FRAMES, TestRunner};
+use songbird_core::utils::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::process::exit;
+
+/*
+ * Runs a test ROM without any display, stopping once it reports a result
+ *
+ * Option        | Stops when                                | Result
+ * --------------+-------------------------------------------+------------------------------------
+ * --serial TEXT | Serial output contains TEXT               | Pass
+ * --blargg-ram  | Cart RAM holds Blargg's result            | Pass if the result is 0
+ * --mooneye     | LD B,B runs, Mooneye's "test done" signal | Pass if B-L hold Fibonacci numbers
+ * --pc-loop     | An instruction keeps jumping to itself    | Pass, unless waiting on --serial
+ * --frames N    | N frames have run (default 3600)          | Fail, unless no other conditions
+ *
+ * --blargg is the same as --serial Passed --blargg-ram --pc-loop.
+ *
+ * Exit codes are 0 for pass, 1 for fail, and 2 if the ROM couldn't be run.
+ */
+
+// =============
+// = Constants =
+// =============
+const EXIT_PASS: i32 = 0;
+const EXIT_FAIL: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
+struct Options {
+    filename: String,
+    runner: TestRunner,
+    png: Option<String>,
+    serial_stdout: bool,
+}
+
+fn main() {
+    let opts = match parse_args() {
+        Some(opts) => { opts },
+        None => {
+            println!("Usage: songbird_headless [--frames N] [--blargg] [--serial TEXT] [--blargg-ram] [--mooneye] [--pc-loop] [--png path/to/out.png] [--serial-stdout] path/to/test.gb");
+            exit(EXIT_ERROR);
+        }
+    };
+
+    let rom = match load_rom(&opts.filename) {
+        Some(rom) => { rom },
+        None => {
+            println!("{}: Unable to read ROM", opts.filename);
+            exit(EXIT_ERROR);
+        }
+    };
+
+    let mut gb = Cpu::with_model(Model::detect(&rom));
+    gb.load_game(&rom);
+
+    let report = opts.runner.run(&mut gb);
+
+    if opts.serial_stdout {
+        print!("{}", String::from_utf8_lossy(&report.serial));
+    }
+
+    if let Some(filename) = &opts.png {
+        let png = encode_png(SCREEN_WIDTH, SCREEN_HEIGHT, ColorType::Rgba, &gb.render());
+        let written = File::create(filename).and_then(|mut f| f.write_all(&png));
+        if let Err(e) = written {
+            println!("Unable to write {}: {}", filename, e);
+            exit(EXIT_ERROR);
+        }
+    }
+
+    let result = if report.outcome.is_pass() { "PASS" } else { "FAIL" };
+    println!("{}: {} ({}, {} frames)", opts.filename, result, report.outcome.get_reason(), report.frames);
+    exit(if report.outcome.is_pass() { EXIT_PASS } else { EXIT_FAIL });
+}
+
+/// ```
+/// Parse args
+///
+/// Reads the command line options
+///
+/// Output:
+///     Options, or None if they were invalid (Option<Options>)
+/// ```
+fn parse_args() -> Option<Options> {
+    let mut filename = None;
+    let mut opts = Options {
+        filename: String::new(),
+        runner: TestRunner::new(DEFAULT_MAX_FRAMES),
+        png: None,
+        serial_stdout: false,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" =>         { opts.runner.max_frames = args.next()?.parse().ok()?; },
+            "--blargg" =>         {
+                opts.runner.serial = Some(BLARGG_PASS_TEXT.to_string());
+                opts.runner.blargg_ram = true;
+                opts.runner.pc_loop = true;
+            },
+            "--serial" =>         { opts.runner.serial = Some(args.next()?); },
+            "--blargg-ram" =>     { opts.runner.blargg_ram = true; },
+            "--mooneye" =>        { opts.runner.mooneye = true; },
+            "--pc-loop" =>        { opts.runner.pc_loop = true; },
+            "--png" =>            { opts.png = Some(args.next()?); },
+            "--serial-stdout" =>  { opts.serial_stdout = true; },
+            _ =>                  { filename = Some(arg); }
+        }
+    }
+
+    opts.filename = filename?;
+    Some(opts)
+}
+
+fn load_rom(filename: &str) -> Option<Vec<u8>> {
+    let mut buffer: Vec<u8> = Vec::new();
+
+    let mut f = File::open(filename).ok()?;
+    f.read_to_end(&mut buffer).ok()?;
+
+    Some(buffer)
+}
@@ -2,8 +2,20 @@
 // Austin Bricker 2019-2020
 
 // Includes
+
+#[cfg(feature = "audio")]
+use songbird_audio::Audio;
 use songbird_core::cpu::Cpu;
+use songbird_core::cpu::model::Model;
+use songbird_core::debug::{debugger, Point};
+use songbird_core::debug::access::AccessType;
+use songbird_core::debug::disasm::export_rom;
+use songbird_core::debug::symbols::Symbols;
 use songbird_core::io::Buttons;
+use songbird_core::link::Link;
+use songbird_core::pacer::{PaceMode, Pacer};
+use songbird_core::printer::Printer;
+use songbird_core::rewind::{DEFAULT_BUDGET, DEFAULT_INTERVAL};
 use songbird_core::utils::{SCREEN_HEIGHT, SCREEN_WIDTH, COLOR_CHANNELS};
 use termion::{async_stdin, cursor, clear, style};
 use termion::color::{Bg, Fg, Rgb};
@@ -14,42 +26,91 @@ use termion::raw::{IntoRawMode, RawTerminal};
 use std::env;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Read, Write};
+use std::path::Path;
 use std::thread::sleep;
 use std::time::Duration;
 
-const FRAME_TIME: u64 = 16667; // In microseconds
+// DMG boot ROM is 256 bytes, anything larger is for the CGB
+const DMG_BOOT_ROM_SIZE: usize = 0x100;
+
+enum LinkMode {
+    Connect(String),
+    Listen(u16),
+    // Not another Game Boy, but plugged into the same port
+    Printer,
+}
 
 struct Emu {
     gb: Cpu,
     filename: String,
+    boot_filename: Option<String>,
+    link_mode: Option<LinkMode>,
+    // Everything the game has sent over the serial port, if being shown
+    serial_output: Option<String>,
+    pacer: Pacer,
+    #[cfg(feature = "audio")]
+    audio: Option<Audio>,
 }
 
 impl Emu {
-    pub fn new(filename: &str) -> Emu {
+    pub fn new(filename: &str, boot_filename: Option<String>, link_mode: Option<LinkMode>, serial_stdout: bool) -> Emu {
         Emu {
             gb: Cpu::new(),
             filename: filename.to_string(),
+            boot_filename,
+            link_mode,
+            serial_output: if serial_stdout { Some(String::new()) } else { None },
+            pacer: Pacer::new(PaceMode::WallClock),
+            #[cfg(feature = "audio")]
+            audio: Audio::new(),
         }
     }
 
     pub fn init(&mut self) {
         let rom = self.load_rom();
+        match self.load_boot_rom() {
+            Some(boot_rom) => {
+                let model = if boot_rom.len() > DMG_BOOT_ROM_SIZE { Model::CGB } else { Model::DMG };
+                self.gb = Cpu::with_model(model);
+                self.gb.load_boot_rom(&boot_rom);
+            },
+            None => {
+                self.gb = Cpu::with_model(Model::detect(&rom));
+            }
+        }
         self.gb.load_game(&rom);
         self.load_battery_save();
+        self.gb.enable_rewind(DEFAULT_BUDGET, DEFAULT_INTERVAL);
+        self.gb.set_serial_capture(self.serial_output.is_some());
+        self.connect_link();
+
+        #[cfg(feature = "audio")]
+        if let Some(audio) = &self.audio {
+            self.gb.set_sample_rate(audio.get_sample_rate());
+            self.pacer.set_mode(PaceMode::Audio, self.gb.get_cycles());
+        }
     }
 
     pub fn run(&mut self) {
         let mut stdin = async_stdin().keys();
         let mut stdout = io::stdout().into_raw_mode().unwrap();
-        let delay = Duration::from_micros(FRAME_TIME);
         let mut old_btns = Vec::new();
         let mut new_btns = Vec::new();
         write!(stdout, "{}", clear::All).unwrap();
+        self.pacer.reset(self.gb.get_cycles());
 
         'gameloop: loop {
+            // Terminals don't report key releases, so rewind for as long as the key keeps repeating
+            let mut rewinding = false;
             while let Some(Ok(k)) = stdin.next() {
                 if k == Key::Char('q') {
                     break 'gameloop;
+                } else if k == Key::Char('r') {
+                    rewinding = true;
+                } else if k == Key::F(5) {
+                    self.write_save_state();
+                } else if k == Key::F(7) {
+                    self.load_save_state();
                 } else if let Some(btn) = key2btn(k) {
                     new_btns.push(btn);
                     if !old_btns.contains(&btn) {
@@ -67,13 +128,142 @@ impl Emu {
             old_btns.clear();
             old_btns.append(&mut new_btns);
 
-            self.tick_until_draw();
+            if rewinding {
+                if self.gb.rewind() {
+                    self.pacer.reset(self.gb.get_cycles());
+                }
+            } else {
+                self.tick_until_draw();
+            }
+            #[cfg(feature = "audio")]
+            if let Some(audio) = &mut self.audio {
+                audio.queue_samples(&mut self.gb);
+            }
+
             let disp_arr = self.gb.render();
             self.print_frame(&mut stdout, &disp_arr);
-            sleep(delay);
+            self.print_serial(&mut stdout);
+            sleep(self.get_frame_delay());
+        }
+    }
+
+    /// ```
+    /// Debug
+    ///
+    /// Runs the game from a debugger prompt rather than the screen,
+    /// stopping at breakpoints, or when a watched address changes
+    /// ```
+    pub fn debug(&mut self) {
+        let mut agbd = debugger::new();
+        if let Some(symbols) = load_symbols(&self.filename) {
+            println!("Loaded {} symbols", symbols.len());
+            agbd.set_symbols(symbols);
+        }
+        let stdin = io::stdin();
+        agbd.print_info(self.gb.get_pc());
+
+        loop {
+            print!("(agbd) ");
+            io::stdout().flush().unwrap();
+
+            let mut input = String::new();
+            // Treat the end of input the same as quitting
+            if stdin.read_line(&mut input).unwrap_or(0) == 0 {
+                break;
+            }
+            let words: Vec<&str> = input.split_whitespace().collect();
+            if words.is_empty() {
+                continue;
+            }
+
+            match words[0] {
+                "b" =>      {
+                    match words.get(1).and_then(|w| agbd.parse_point(w)) {
+                        Some(point) =>  { agbd.add_break(point); },
+                        None =>         { println!("Usage: b <[bank:]address | label>"); }
+                    }
+                },
+                "bt" =>     { agbd.print_backtrace(&self.gb); },
+                "c" =>      { self.debug_continue(&agbd); },
+                "del" =>    {
+                    let point = words.get(1).and_then(|w| agbd.parse_point(w));
+                    let range = words.get(1).and_then(|w| agbd.parse_range(w));
+                    if let Some(point) = point {
+                        agbd.del_break(point);
+                    }
+                    if let Some(range) = range {
+                        agbd.del_access_break(&mut self.gb, range);
+                    }
+                    if point.is_none() && range.is_none() {
+                        println!("Usage: del <[bank:]address | label | start-end>");
+                    }
+                },
+                "disass" => { agbd.disassemble(&self.gb); },
+                "help" =>   { agbd.print_help(); },
+                "info" =>   { agbd.list_points(); },
+                "n" =>      {
+                    self.debug_step();
+                    agbd.disassemble(&self.gb);
+                },
+                "p" =>      {
+                    match words.get(1).and_then(|w| agbd.parse_addr(w)) {
+                        Some(addr) => { agbd.print_ram(addr, &self.gb); },
+                        None =>       { println!("Usage: p <address>"); }
+                    }
+                },
+                "q" =>      { break; },
+                "rb" | "wb" | "xb" => {
+                    let kind = match words[0] {
+                        "rb" => { AccessType::Read },
+                        "wb" => { AccessType::Write },
+                        _ =>    { AccessType::Execute }
+                    };
+                    match words.get(1).and_then(|w| agbd.parse_range(w)) {
+                        Some(range) =>  { agbd.add_access_break(&mut self.gb, kind, range); },
+                        None =>         { println!("Usage: {} <address | label | start-end>", words[0]); }
+                    }
+                },
+                "reg" =>    { print!("{}", agbd.print_registers(&self.gb)); },
+                "watch" =>  {
+                    match words.get(1).and_then(|w| agbd.parse_point(w)) {
+                        Some(point) =>  { agbd.add_watch(point); },
+                        None =>         { println!("Usage: watch <[bank:]address | label>"); }
+                    }
+                },
+                _ =>        { println!("Unknown command '{}', type 'help' for a list of commands", words[0]); }
+            }
         }
     }
 
+    /// ```
+    /// Get serial output
+    ///
+    /// Everything the game sent over the serial port, if it was being captured
+    ///
+    /// Output:
+    ///     Serial output (Option<&str>)
+    /// ```
+    pub fn get_serial_output(&self) -> Option<&str> {
+        self.serial_output.as_deref()
+    }
+
+    /// ```
+    /// Get frame delay
+    ///
+    /// How long to wait before emulating the next frame
+    ///
+    /// Output:
+    ///     Time to sleep (Duration)
+    /// ```
+    fn get_frame_delay(&mut self) -> Duration {
+        #[cfg(feature = "audio")]
+        if let Some(audio) = &self.audio {
+            return self.pacer.get_delay(self.gb.get_cycles(), audio.get_queued(), audio.get_sample_rate());
+        }
+
+        self.pacer.get_delay(self.gb.get_cycles(), 0, 0)
+    }
+
     fn print_frame(&mut self, stdout: &mut RawTerminal<io::Stdout>, arr: &[u8]) {
         // Input array is 160x144 RGBA values, where four elements together make up one pixel
         // Alpha channel is always 100%, and can be ignored
@@ -105,6 +295,94 @@ impl Emu {
         stdout.flush().unwrap();
     }
 
+    fn print_serial(&mut self, stdout: &mut RawTerminal<io::Stdout>) {
+        let new_output = self.gb.take_serial_output();
+        if let Some(output) = &mut self.serial_output {
+            if new_output.is_empty() {
+                return;
+            }
+            output.push_str(&String::from_utf8_lossy(&new_output));
+
+            // Shown below the screen, redrawn only when something new arrives
+            let top = (SCREEN_HEIGHT / 2) as u16 + 2;
+            write!(stdout, "{}{}", style::Reset, cursor::Goto(1, top)).unwrap();
+            for line in output.lines() {
+                write!(stdout, "{}{}\r\n", clear::CurrentLine, line).unwrap();
+            }
+            stdout.flush().unwrap();
+        }
+    }
+
+    /// ```
+    /// Debug step
+    ///
+    /// Runs until the next instruction has executed, waking from HALT if needed
+    /// ```
+    fn debug_step(&mut self) {
+        loop {
+            let executing = !self.gb.is_halted();
+            self.debug_tick();
+            if executing && !self.gb.is_halted() {
+                break;
+            }
+        }
+        self.print_access_hits();
+    }
+
+    /// ```
+    /// Debug continue
+    ///
+    /// Runs until a breakpoint is reached, a watched address changes, or memory is accessed
+    /// where an access breakpoint is set
+    ///
+    /// Input:
+    ///     Debugger holding the break/watchpoints (&debugger)
+    /// ```
+    fn debug_continue(&mut self, agbd: &debugger) {
+        loop {
+            let watch_vals = agbd.get_watch_vals(&self.gb);
+            self.debug_tick();
+
+            // Shown with the bank, if running from switchable ROM
+            let pc = Point::new(Some(self.gb.get_rom_bank()), self.gb.get_pc());
+            if agbd.check_break(&self.gb) {
+                println!("Breakpoint reached at {}", pc);
+                break;
+            }
+            if agbd.check_watch(&self.gb, watch_vals) {
+                println!("Watchpoint triggered at {}", pc);
+                break;
+            }
+            if self.print_access_hits() {
+                break;
+            }
+        }
+        agbd.disassemble(&self.gb);
+    }
+
+    /// ```
+    /// Print access hits
+    ///
+    /// Prints any memory access breakpoints hit since last checked
+    ///
+    /// Output:
+    ///     Whether any were hit (bool)
+    /// ```
+    fn print_access_hits(&mut self) -> bool {
+        let hits = self.gb.take_access_hits();
+        for hit in &hits {
+            println!("Access breakpoint: {}", hit);
+        }
+        !hits.is_empty()
+    }
+
+    fn debug_tick(&mut self) {
+        self.gb.tick();
+        if self.gb.is_battery_dirty() {
+            self.write_battery_save();
+        }
+    }
+
     fn tick_until_draw(&mut self) {
         loop {
             let draw_time = self.gb.tick();
@@ -127,6 +405,37 @@ impl Emu {
         buffer
     }
 
+    fn load_boot_rom(&self) -> Option<Vec<u8>> {
+        let filename = self.boot_filename.as_ref()?;
+        let mut buffer: Vec<u8> = Vec::new();
+
+        let mut f = File::open(filename).expect("Error opening boot ROM");
+        f.read_to_end(&mut buffer).expect("Error reading boot ROM to buffer");
+
+        Some(buffer)
+    }
+
+    fn connect_link(&mut self) {
+        let link = match &self.link_mode {
+            Some(LinkMode::Connect(addr)) => { Link::connect(addr.as_str()) },
+            Some(LinkMode::Listen(port)) => {
+                println!("Waiting for link cable connection on port {}...", port);
+                Link::listen(*port)
+            },
+            Some(LinkMode::Printer) => {
+                // Prints are saved next to the ROM
+                self.gb.connect_serial(Box::new(Printer::new(Some(self.filename.clone()))));
+                return;
+            },
+            None => { return; }
+        };
+
+        match link {
+            Ok(link) => { self.gb.connect_serial(Box::new(link)); },
+            Err(e) => { println!("Unable to connect link cable: {}", e); }
+        }
+    }
+
     fn load_battery_save(&mut self) {
         if self.gb.has_battery() {
             let mut battery_ram: Vec<u8> = Vec::new();
@@ -140,6 +449,29 @@ impl Emu {
         }
     }
 
+    fn write_save_state(&self) {
+        let mut filename = self.filename.to_owned();
+        filename.push_str(".state");
+
+        let state = self.gb.save_state();
+        let mut file = File::create(filename).expect("Error opening save state file");
+        file.write_all(&state).unwrap();
+    }
+
+    fn load_save_state(&mut self) {
+        let mut filename = self.filename.to_owned();
+        filename.push_str(".state");
+
+        let mut state = Vec::new();
+        if let Ok(mut f) = File::open(filename) {
+            f.read_to_end(&mut state).expect("Error reading save state");
+            // A bad state leaves the game running as it was
+            if self.gb.load_state(&state).is_ok() {
+                self.pacer.reset(self.gb.get_cycles());
+            }
+        }
+    }
+
     fn write_battery_save(&mut self) {
         if self.gb.has_battery() {
             let ram_data = self.gb.get_ext_ram();
@@ -155,16 +487,106 @@ impl Emu {
 }
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
-    if args.len() == 1 {
-        println!("Usage: songbird_term path/to/game.gb");
+    let mut filename = None;
+    let mut boot_filename = None;
+    let mut link_mode = None;
+    let mut serial_stdout = false;
+    let mut debug = false;
+    let mut listing_filename = None;
+    // The printer plugs into the link port, so can't be used alongside a link
+    let args: Vec<String> = env::args().skip(1).collect();
+    let linked = args.iter().any(|arg| arg == "--link" || arg == "--link-listen");
+    if linked && args.iter().any(|arg| arg == "--printer") {
+        println!("--printer can't be used with --link or --link-listen");
         return;
     }
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--boot" =>           { boot_filename = args.next(); },
+            "--link" =>           { link_mode = args.next().map(LinkMode::Connect); },
+            "--link-listen" =>    {
+                let arg = args.next().unwrap_or_default();
+                match arg.parse() {
+                    Ok(port) => { link_mode = Some(LinkMode::Listen(port)); },
+                    Err(_) => {
+                        println!("Invalid port for --link-listen: '{}'", arg);
+                        return;
+                    }
+                }
+            },
+            "--printer" =>        { link_mode = Some(LinkMode::Printer); },
+            "--serial-stdout" =>  { serial_stdout = true; },
+            "--debug" =>          { debug = true; },
+            "--disassemble" =>    { listing_filename = args.next(); },
+            _ =>                  { filename = Some(arg); }
+        }
+    }
+
+    let filename = match filename {
+        Some(f) => { f },
+        None => {
+            println!("Usage: songbird_term [--boot path/to/boot.bin] [--link host:port | --link-listen port | --printer] [--serial-stdout] [--debug] [--disassemble path/to/out.txt] path/to/game.gb");
+            return;
+        }
+    };
 
-    let mut emu = Emu::new(&args[1]);
+    if let Some(listing_filename) = listing_filename {
+        write_listing(&filename, &listing_filename);
+        return;
+    }
+
+    let mut emu = Emu::new(&filename, boot_filename, link_mode, serial_stdout);
     emu.init();
+    if debug {
+        emu.debug();
+        return;
+    }
     emu.run();
     print!("{}", style::Reset);
+
+    // Leave the results on their own, so test ROM output can be read or copied easily
+    if let Some(output) = emu.get_serial_output() {
+        print!("{}{}", clear::All, cursor::Goto(1, 1));
+        println!("{}", output);
+    }
+}
+
+/// ```
+/// Write listing
+///
+/// Disassembles every bank of a ROM to a text file, labelled if there's a symbol file
+///
+/// Inputs:
+///     ROM to disassemble (&str)
+///     File to write the listing to (&str)
+/// ```
+fn write_listing(filename: &str, listing_filename: &str) {
+    let mut rom: Vec<u8> = Vec::new();
+    let mut f = File::open(filename).expect("Error opening ROM");
+    f.read_to_end(&mut rom).expect("Error reading ROM to buffer");
+
+    let symbols = load_symbols(filename);
+    let mut file = File::create(listing_filename).expect("Error opening listing file");
+    file.write_all(export_rom(&rom, symbols.as_ref()).as_bytes()).unwrap();
+}
+
+/// ```
+/// Load symbols
+///
+/// Loads the RGBDS symbol file next to a ROM, such as game.sym for game.gb
+///
+/// Input:
+///     ROM filename (&str)
+///
+/// Output:
+///     Symbol table, if the file exists (Option<Symbols>)
+/// ```
+fn load_symbols(filename: &str) -> Option<Symbols> {
+    let mut text = String::new();
+    let mut f = File::open(Path::new(filename).with_extension("sym")).ok()?;
+    f.read_to_string(&mut text).ok()?;
+    Some(Symbols::parse(&text))
 }
 
 fn key2btn(key: Key) -> Option<Buttons> {
@@ -1,17 +1,21 @@
 // Functions to be exported out of .wasm for JS usage
-use js_sys::Uint8Array;
+use js_sys::{Float32Array, Uint8Array};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::{Clamped, JsCast};
 use web_sys::{ImageData, KeyboardEvent};
 
 use songbird_core::cpu::Cpu;
+use songbird_core::cpu::model::Model;
 use songbird_core::io::Buttons;
+use songbird_core::pacer::{PaceMode, Pacer};
 use songbird_core::utils::{SCREEN_HEIGHT, SCREEN_WIDTH};
 
 #[wasm_bindgen]
 pub struct GB {
     cpu: Cpu,
-    ctx: web_sys::CanvasRenderingContext2d
+    ctx: web_sys::CanvasRenderingContext2d,
+    pacer: Pacer,
+    sample_rate: u32
 }
 
 #[wasm_bindgen]
@@ -35,7 +39,9 @@ impl GB {
 
         let gb = GB {
             cpu: cpu,
-            ctx: ctx
+            ctx: ctx,
+            pacer: Pacer::new(PaceMode::WallClock),
+            sample_rate: 0
         };
 
         Ok(gb)
@@ -49,6 +55,7 @@ impl GB {
     #[wasm_bindgen]
     pub fn reset(&mut self) {
         self.cpu = Cpu::new();
+        self.pacer.reset(self.cpu.get_cycles());
     }
 
     /// ```
@@ -67,6 +74,10 @@ impl GB {
             rom_data.push(data.get_index(i));
         }
 
+        self.cpu = Cpu::with_model(Model::detect(&rom_data));
+        if self.sample_rate != 0 {
+            self.cpu.set_sample_rate(self.sample_rate);
+        }
         self.cpu.load_game(&rom_data)
     }
 
@@ -83,6 +94,59 @@ impl GB {
         self.cpu.tick()
     }
 
+    /// ```
+    /// Set sample rate
+    ///
+    /// Sets the rate audio is generated at, to match the browser's AudioContext
+    ///
+    /// Input:
+    ///     Sample rate, in Hz (u32)
+    /// ```
+    #[wasm_bindgen]
+    pub fn set_sample_rate(&mut self, rate: u32) {
+        self.cpu.set_sample_rate(rate);
+        self.sample_rate = rate;
+        self.pacer.set_mode(PaceMode::Audio, self.cpu.get_cycles());
+    }
+
+    /// ```
+    /// Get frame delay
+    ///
+    /// How long to wait before emulating the next frame
+    ///
+    /// Input:
+    ///     Number of audio sample frames scheduled but not yet played (u32)
+    ///
+    /// Output:
+    ///     Delay, in milliseconds (f64)
+    /// ```
+    #[wasm_bindgen]
+    pub fn get_frame_delay(&mut self, queued_samples: u32) -> f64 {
+        let delay = self.pacer.get_delay(self.cpu.get_cycles(), queued_samples as usize, self.sample_rate);
+        delay.as_secs_f64() * 1000.0
+    }
+
+    /// ```
+    /// Get audio
+    ///
+    /// Drains all generated audio samples
+    ///
+    /// Output:
+    ///     Interleaved left/right samples, from -1.0 to 1.0 (Float32Array)
+    /// ```
+    #[wasm_bindgen]
+    pub fn get_audio(&mut self) -> Float32Array {
+        let mut samples = vec![0; 2 * self.cpu.audio_samples_avail()];
+        let len = self.cpu.drain_audio(&mut samples);
+
+        let output_array = Float32Array::new_with_length(len as u32);
+        for (i, sample) in samples[..len].iter().enumerate() {
+            output_array.set_index(i as u32, *sample as f32 / 32768.0);
+        }
+
+        output_array
+    }
+
     /// ```
     /// Get title
     ///
@@ -9,6 +9,9 @@ let scale_elem = document.getElementById("scale")
 let my_storage = window.localStorage
 let anim_frame = 0
 
+let audio_ctx = null
+let next_audio_time = 0
+
 let canvas = document.getElementById("canvas")
 update_canvas()
 
@@ -31,7 +34,7 @@ async function run() {
     document.getElementById('fileinput').addEventListener("change", function (e) {
         // Stop previous game from rendering, if one exists
         if (anim_frame != 0) {
-            window.cancelAnimationFrame(anim_frame)
+            window.clearTimeout(anim_frame)
         }
 
         let file = e.target.files[0]
@@ -46,6 +49,7 @@ async function run() {
             const rom = new Uint8Array(buffer)
 
             gb.reset()
+            init_audio(gb)
             gb.load_rom(rom)
             load_save(gb)
             let title = gb.get_title()
@@ -63,6 +67,7 @@ function mainloop(gb) {
         let draw_time = gb.tick()
         if (draw_time) {
             gb.draw_screen()
+            play_audio(gb)
             // Rescale image if needed
             if (scale > 1) {
                 let ctx = canvas.getContext('2d')
@@ -75,15 +80,67 @@ function mainloop(gb) {
                 save_game(gb)
             }
 
-            anim_frame = window.requestAnimationFrame(() => {
+            anim_frame = window.setTimeout(() => {
                 mainloop(gb)
-            })
+            }, gb.get_frame_delay(queued_audio()))
 
             break
         }
     }
 }
 
+/// Init audio
+///
+/// Creates the AudioContext, which must happen after a user gesture
+function init_audio(gb) {
+    if (audio_ctx == null) {
+        audio_ctx = new AudioContext()
+    }
+    gb.set_sample_rate(audio_ctx.sampleRate)
+    next_audio_time = 0
+}
+
+/// Queued audio
+///
+/// Number of audio samples scheduled to play, but that haven't yet
+function queued_audio() {
+    if (audio_ctx == null) {
+        return 0
+    }
+    let queued = Math.max(0, next_audio_time - audio_ctx.currentTime)
+    return Math.floor(queued * audio_ctx.sampleRate)
+}
+
+/// Play audio
+///
+/// Queues the samples generated this frame to be played after the previous batch
+function play_audio(gb) {
+    let samples = gb.get_audio()
+    let len = samples.length / 2
+    if (audio_ctx == null || len == 0) {
+        return
+    }
+
+    let buffer = audio_ctx.createBuffer(2, len, audio_ctx.sampleRate)
+    let left = buffer.getChannelData(0)
+    let right = buffer.getChannelData(1)
+    for (let i = 0; i < len; i++) {
+        left[i] = samples[2 * i]
+        right[i] = samples[2 * i + 1]
+    }
+
+    let source = audio_ctx.createBufferSource()
+    source.buffer = buffer
+    source.connect(audio_ctx.destination)
+
+    // If we've fallen behind, start again slightly in the future
+    if (next_audio_time < audio_ctx.currentTime) {
+        next_audio_time = audio_ctx.currentTime + 0.05
+    }
+    source.start(next_audio_time)
+    next_audio_time += buffer.duration
+}
+
 /// Update Canvas
 ///
 /// Resizes canvas based on user input